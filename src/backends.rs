@@ -0,0 +1,252 @@
+//! Compares the pull based operators of `query` with hand-specialized push
+//! based pipelines of `pipeline` and with hand-written loops, on an aggregation
+//! and on a hash join followed by an aggregation over orderline, and with
+//! the vectorized operators of `vector` on a scan of stock. The hash join is
+//! also planned from SQL and run both by the operators and by the pipelines
+//! `pipeline::execute` compiles from the plan.
+
+use std::collections::{HashMap, HashSet};
+
+use time::precise_time_ns;
+
+use catalog::Catalog;
+use column::{read_column_table, ColumnTable};
+use dynamic::{Row, Value, IntValue, NumericValue};
+use numeric::Numeric;
+use pipeline::{scan, execute, Predicate, Key, Aggregation, Select, Build, Probe, GroupBy};
+use planner::plan;
+use query::{rows, Operator, Scan, Filter, HashJoin, Aggregate, Sort, SortKey, Count, Sum,
+            Comparison, And, ColumnRef, Literal, IsEqual, IsLess};
+use sql;
+use sql::SelectStatement;
+use table::{read_table, Table};
+use tables::{Tables, Item, ItemTable, Orderline, OrderlineTable, StockTable, StockColumnTable};
+use vector::{ScanBatches, NumericFilter, sum_numeric};
+
+
+const ORDERLINE_FILE: &'static str = "../data/tpcc_orderline.tbl";
+const ITEM_FILE: &'static str = "../data/tpcc_item.tbl";
+const STOCK_FILE: &'static str = "../data/tpcc_stock.tbl";
+const PIPELINED: [&'static str, ..3] = ["iterator", "pipeline", "loop"];
+const COMPILED: [&'static str, ..3] = ["iterator", "compiled", "pipeline"];
+const VECTORIZED: [&'static str, ..3] = ["iterator", "loop", "vectorized"];
+
+pub fn compare_backends(catalog: &Catalog) {
+    let mut tables = Tables::new();
+    read_table(ORDERLINE_FILE, &mut tables.orderline_table);
+    read_table(ITEM_FILE, &mut tables.item_table);
+    let orderline = &tables.orderline_table;
+    let item = &tables.item_table;
+
+    let zero_quantity = Numeric::new(0, 12, 0);
+    let zero_amount = Numeric::new(0, 12, 2);
+
+    // select ol_number, sum(ol_quantity), sum(ol_amount), count(*) from orderline
+    // group by ol_number order by ol_number
+    measure("aggregate", PIPELINED, || {
+        let aggregate = box Aggregate::new(box Scan::new(orderline.scan()), vec![3],
+                                           vec![Sum(7), Sum(8), Count]);
+        let mut sort = Sort::new(aggregate, vec![SortKey { column: 0, descending: false }]);
+        rows(&mut sort as &mut Operator)
+    }, || {
+        let mut groups = GroupBy::new(QuantityAmountPerNumber);
+        scan(orderline, &mut groups);
+        sorted_rows(groups.groups, |(quantity, amount, count)| {
+            vec![NumericValue(quantity), NumericValue(amount), IntValue(count)]
+        })
+    }, || {
+        let mut groups = HashMap::new();
+        for ol in orderline.scan() {
+            let state = groups.find_or_insert_with(ol.ol_number,
+                                                   |_| (zero_quantity, zero_amount, 0i64));
+            let (quantity, amount, count) = *state;
+            *state = (quantity + ol.ol_quantity, amount + ol.ol_amount, count + 1);
+        }
+        sorted_rows(groups, |(quantity, amount, count)| {
+            vec![NumericValue(quantity), NumericValue(amount), IntValue(count)]
+        })
+    });
+
+    // select ol_w_id, sum(ol_amount), count(*) from orderline, item
+    // where ol_i_id = i_id and i_price < 10 group by ol_w_id order by ol_w_id
+    let ten = Numeric::new(1000, 5, 2);
+    measure("hash join + aggregate", PIPELINED, || {
+        let cheap = Comparison(box ColumnRef(3), IsLess, box Literal(NumericValue(ten)));
+        let join = box HashJoin::new(box Scan::new(orderline.scan()),
+                                     box Filter::new(box Scan::new(item.scan()), cheap),
+                                     vec![4], vec![0]);
+        let aggregate = box Aggregate::new(join, vec![2], vec![Sum(8), Count]);
+        let mut sort = Sort::new(aggregate, vec![SortKey { column: 0, descending: false }]);
+        rows(&mut sort as &mut Operator)
+    }, || {
+        amount_per_warehouse(orderline, item, ten)
+    }, || {
+        let cheap: HashSet<i32> = item.scan()
+            .filter(|i| i.i_price < ten)
+            .map(|i| i.i_id)
+            .collect();
+        let mut groups = HashMap::new();
+        for ol in orderline.scan() {
+            if cheap.contains(&ol.ol_i_id) {
+                let state = groups.find_or_insert_with(ol.ol_w_id, |_| (zero_amount, 0i64));
+                let (amount, count) = *state;
+                *state = (amount + ol.ol_amount, count + 1);
+            }
+        }
+        sorted_rows(groups, |(amount, count)| vec![NumericValue(amount), IntValue(count)])
+    });
+
+    let select = match sql::parse("select ol_w_id, sum(ol_amount), count(*) from orderline, item \
+                                   where ol_i_id = i_id and i_price < 10 group by ol_w_id \
+                                   order by ol_w_id") {
+        Ok(SelectStatement(select)) => select,
+        _ => unreachable!()
+    };
+    measure("planned hash join", COMPILED, || {
+        let mut planned = plan(catalog, &tables, &select, false).unwrap();
+        rows(&mut *planned.root)
+    }, || {
+        execute(&plan(catalog, &tables, &select, false).unwrap().physical)
+    }, || {
+        amount_per_warehouse(orderline, item, ten)
+    });
+
+    let mut stock_rows: StockTable = Table::new();
+    let mut stock_columns: StockColumnTable = ColumnTable::new();
+    read_table(STOCK_FILE, &mut stock_rows);
+    read_column_table(STOCK_FILE, &mut stock_columns);
+
+    // select count(*), sum(s_quantity) from stock where s_quantity < 50 and s_order_cnt = 0
+    let fifty = Numeric::new(50, 4, 0);
+    let zero = Numeric::new(0, 4, 0);
+    measure("scan stock", VECTORIZED, || {
+        let quantity = Comparison(box ColumnRef(2), IsLess, box Literal(NumericValue(fifty)));
+        let order_cnt = Comparison(box ColumnRef(14), IsEqual, box Literal(NumericValue(zero)));
+        let predicate = And(box quantity, box order_cnt);
+        let filter = box Filter::new(box Scan::new(stock_rows.scan()), predicate);
+        let mut aggregate = Aggregate::new(filter, Vec::new(), vec![Count, Sum(2)]);
+        rows(&mut aggregate as &mut Operator)
+    }, || {
+        let mut count = 0i64;
+        let mut sum = Numeric::new(0, 12, 0);
+        for s in stock_rows.scan() {
+            if s.s_quantity < fifty && s.s_order_cnt == zero {
+                count += 1;
+                sum = sum + s.s_quantity;
+            }
+        }
+        vec![vec![IntValue(count), NumericValue(sum)]]
+    }, || {
+        let columns = stock_columns.columns();
+        let scan = box ScanBatches::new(stock_columns.size());
+        let quantity = box NumericFilter::new(scan, columns.s_quantity[], IsLess, fifty);
+        let mut order_cnt = NumericFilter::new(quantity, columns.s_order_cnt[], IsEqual, zero);
+        let (count, sum) = sum_numeric(&mut order_cnt, columns.s_quantity[],
+                                       Numeric::new(0, 12, 0));
+        vec![vec![IntValue(count as i64), NumericValue(sum)]]
+    });
+}
+
+/// sum(ol_amount) and count(*) per ol_w_id of the orderlines of items
+/// cheaper than `price`, as a hand-specialized pipeline.
+fn amount_per_warehouse(orderline: &OrderlineTable, item: &ItemTable, price: Numeric)
+        -> Vec<Row> {
+    let mut items = HashMap::new();
+    scan(item, &mut Select::new(PriceBelow { price: price }, Build::new(&mut items, ItemId)));
+    let mut groups = GroupBy::new(AmountPerWarehouse);
+    scan(orderline, &mut Probe::new(&items, OrderlineItem, &mut groups));
+    sorted_rows(groups.groups, |(amount, count)| vec![NumericValue(amount), IntValue(count)])
+}
+
+struct QuantityAmountPerNumber;
+
+impl<'a> Aggregation<&'a Orderline, i32, (Numeric, Numeric, i64)> for QuantityAmountPerNumber {
+    fn key(&self, ol: &&'a Orderline) -> i32 {
+        ol.ol_number
+    }
+
+    fn init(&self) -> (Numeric, Numeric, i64) {
+        (Numeric::new(0, 12, 0), Numeric::new(0, 12, 2), 0)
+    }
+
+    fn update(&self, state: &mut (Numeric, Numeric, i64), ol: &'a Orderline) {
+        let (quantity, amount, count) = *state;
+        *state = (quantity + ol.ol_quantity, amount + ol.ol_amount, count + 1);
+    }
+}
+
+struct PriceBelow {
+    price: Numeric,
+}
+
+impl<'a> Predicate<&'a Item> for PriceBelow {
+    fn test(&self, item: &&'a Item) -> bool {
+        item.i_price < self.price
+    }
+}
+
+struct ItemId;
+
+impl<'a> Key<&'a Item, i32> for ItemId {
+    fn key(&self, item: &&'a Item) -> i32 {
+        item.i_id
+    }
+}
+
+struct OrderlineItem;
+
+impl<'a> Key<&'a Orderline, i32> for OrderlineItem {
+    fn key(&self, ol: &&'a Orderline) -> i32 {
+        ol.ol_i_id
+    }
+}
+
+struct AmountPerWarehouse;
+
+impl<'a, 'b> Aggregation<(&'a Item, &'b Orderline), i32, (Numeric, i64)> for AmountPerWarehouse {
+    fn key(&self, tuple: &(&'a Item, &'b Orderline)) -> i32 {
+        let (_, ol) = *tuple;
+        ol.ol_w_id
+    }
+
+    fn init(&self) -> (Numeric, i64) {
+        (Numeric::new(0, 12, 2), 0)
+    }
+
+    fn update(&self, state: &mut (Numeric, i64), (_, ol): (&'a Item, &'b Orderline)) {
+        let (amount, count) = *state;
+        *state = (amount + ol.ol_amount, count + 1);
+    }
+}
+
+/// The groups as rows of the key followed by `values(state)`, ordered by the
+/// key like the `ORDER BY` of the iterator plans.
+fn sorted_rows<S>(groups: HashMap<i32, S>, values: |S| -> Vec<Value>) -> Vec<Row> {
+    let mut groups: Vec<(i32, S)> = groups.into_iter().collect();
+    groups.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+    groups.into_iter().map(|(key, state)| {
+        let mut row = vec![IntValue(key as i64)];
+        row.extend(values(state).into_iter());
+        row
+    }).collect()
+}
+
+/// Runs three versions of a query, checks that they agree and prints how
+/// long each took.
+fn measure(name: &str, labels: [&str, ..3], first: || -> Vec<Row>, second: || -> Vec<Row>,
+           third: || -> Vec<Row>) {
+    let (first_result, first_time) = timed(first);
+    let (second_result, second_time) = timed(second);
+    let (third_result, third_time) = timed(third);
+
+    assert_eq!(first_result, second_result);
+    assert_eq!(first_result, third_result);
+    println!("{:<24} {} {:>8.3}s  {} {:>8.3}s  {} {:>8.3}s", name, labels[0], first_time,
+             labels[1], second_time, labels[2], third_time);
+}
+
+fn timed(query: || -> Vec<Row>) -> (Vec<Row>, f64) {
+    let time = precise_time_ns();
+    let result = query();
+    (result, (precise_time_ns() - time) as f64 / 1e9f64)
+}
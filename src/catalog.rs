@@ -0,0 +1,139 @@
+//! Run time description of tables: their columns, types and keys.
+
+use std::fmt;
+
+
+#[deriving(PartialEq, Eq, Clone)]
+pub enum ColumnType {
+    IntegerType,
+    /// `numeric(len, precision)`
+    NumericType(uint, uint),
+    CharType(uint),
+    VarcharType(uint),
+    TimestampType,
+}
+
+impl fmt::Show for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntegerType => write!(f, "integer"),
+            NumericType(len, precision) => write!(f, "numeric({},{})", len, precision),
+            CharType(len) => write!(f, "char({})", len),
+            VarcharType(len) => write!(f, "varchar({})", len),
+            TimestampType => write!(f, "timestamp"),
+        }
+    }
+}
+
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub struct Column {
+    pub name: String,
+    pub ty: ColumnType,
+    pub nullable: bool,
+}
+
+/// A secondary index, `columns` are positions in the table's column list.
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub struct Index {
+    pub name: String,
+    pub columns: Vec<uint>,
+    pub unique: bool,
+}
+
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<Column>,
+    /// Positions in `columns`, in key order.
+    pub primary_key: Vec<uint>,
+    pub indexes: Vec<Index>,
+}
+
+impl TableSchema {
+    pub fn new(name: &str) -> TableSchema {
+        TableSchema {
+            name: name.to_string(),
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+            indexes: Vec::new(),
+        }
+    }
+
+    pub fn add_column(&mut self, name: &str, ty: ColumnType, nullable: bool)
+            -> Result<(), String> {
+        if self.column_index(name).is_some() {
+            return Err(format!("column {} specified more than once in {}", name, self.name));
+        }
+        self.columns.push(Column { name: name.to_string(), ty: ty, nullable: nullable });
+        Ok(())
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<uint> {
+        self.columns.iter().position(|column| column.name[] == name)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.column_index(name).map(|i| &self.columns[i])
+    }
+
+    pub fn set_primary_key(&mut self, columns: &[&str]) -> Result<(), String> {
+        if !self.primary_key.is_empty() {
+            return Err(format!("multiple primary keys for table {}", self.name));
+        }
+        self.primary_key = try!(self.resolve(columns));
+        Ok(())
+    }
+
+    pub fn add_index(&mut self, name: &str, columns: &[&str], unique: bool)
+            -> Result<(), String> {
+        if self.indexes.iter().any(|index| index.name[] == name) {
+            return Err(format!("index {} already exists on {}", name, self.name));
+        }
+        let columns = try!(self.resolve(columns));
+        self.indexes.push(Index { name: name.to_string(), columns: columns, unique: unique });
+        Ok(())
+    }
+
+    fn resolve(&self, columns: &[&str]) -> Result<Vec<uint>, String> {
+        let mut result = Vec::new();
+        for &name in columns.iter() {
+            match self.column_index(name) {
+                Some(i) => result.push(i),
+                None => return Err(format!("column {} does not exist in {}", name, self.name))
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// All tables known to the database, in creation order.
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub struct Catalog {
+    tables: Vec<TableSchema>,
+}
+
+impl Catalog {
+    pub fn new() -> Catalog {
+        Catalog { tables: Vec::new() }
+    }
+
+    pub fn add_table(&mut self, table: TableSchema) -> Result<(), String> {
+        if self.table(table.name[]).is_some() {
+            return Err(format!("relation {} already exists", table.name));
+        }
+        self.tables.push(table);
+        Ok(())
+    }
+
+    pub fn table(&self, name: &str) -> Option<&TableSchema> {
+        self.tables.iter().find(|table| table.name[] == name)
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> Option<&mut TableSchema> {
+        self.tables.iter_mut().find(|table| table.name[] == name)
+    }
+
+    pub fn tables(&self) -> &[TableSchema] {
+        self.tables[]
+    }
+}
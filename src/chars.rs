@@ -0,0 +1,158 @@
+//! Fixed capacity string types for `char(n)` and `varchar(n)` columns.
+//!
+//! Without integer type parameters there is no `Char<N>`, so each length used
+//! by the schema gets its own type generated by `char_type!`/`varchar_type!`
+//! (in `tables.rs`, see `codegen`). Values are stored inline, which makes them
+//! `Copy` and keeps a `Stock` row in a single allocation. Lengths are counted in
+//! bytes.
+
+use std::fmt;
+
+
+/// A value did not fit into the column it was meant for.
+#[deriving(PartialEq, Eq, Clone)]
+pub struct TooLong {
+    pub capacity: uint,
+    pub length: uint,
+}
+
+impl fmt::Show for TooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bytes exceed the column length of {}", self.length, self.capacity)
+    }
+}
+
+macro_rules! fixed_string(
+    ($name:ident, $capacity:expr, $padded:expr) => (
+        pub struct $name {
+            len: u16,
+            data: [u8, ..$capacity]
+        }
+
+        impl $name {
+            pub fn new(s: &str) -> Result<$name, ::chars::TooLong> {
+                if s.len() > $capacity {
+                    return Err(::chars::TooLong { capacity: $capacity, length: s.len() });
+                }
+                let mut result = $name { len: s.len() as u16, data: [0u8, ..$capacity] };
+                for (dst, src) in result.data.iter_mut().zip(s.bytes()) {
+                    *dst = src;
+                }
+                if $padded {
+                    for dst in result.data.iter_mut().skip(s.len()) {
+                        *dst = b' ';
+                    }
+                    result.len = $capacity;
+                }
+                Ok(result)
+            }
+
+            pub fn capacity() -> uint {
+                $capacity
+            }
+
+            pub fn len(&self) -> uint {
+                self.len as uint
+            }
+
+            pub fn as_slice<'a>(&'a self) -> &'a str {
+                // only ever filled from a whole `&str` (plus ASCII padding)
+                unsafe { ::std::str::raw::from_utf8(self.data[..self.len as uint]) }
+            }
+        }
+
+        impl ::std::from_str::FromStr for $name {
+            fn from_str(s: &str) -> Option<$name> {
+                $name::new(s).ok()
+            }
+        }
+
+        impl ::table::FromField for $name {
+            fn from_field(field: &str) -> Result<$name, String> {
+                $name::new(field).map_err(|e| e.to_string())
+            }
+        }
+
+        impl ::dynamic::ToValue for $name {
+            fn to_value(&self) -> ::dynamic::Value {
+                ::dynamic::TextValue(self.as_slice().to_string())
+            }
+        }
+
+        impl Clone for $name {
+            fn clone(&self) -> $name {
+                *self
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                self.as_slice() == other.as_slice()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &$name) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &$name) -> Ordering {
+                self.as_slice().cmp(&other.as_slice())
+            }
+        }
+
+        impl<S: ::std::hash::Writer> ::std::hash::Hash<S> for $name {
+            fn hash(&self, state: &mut S) {
+                state.write(self.as_slice().as_bytes())
+            }
+        }
+
+        impl ::std::fmt::Show for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{}", self.as_slice())
+            }
+        }
+    )
+)
+
+/// `char(n)`: shorter values are padded with spaces.
+macro_rules! char_type(
+    ($name:ident, $capacity:expr) => (fixed_string!($name, $capacity, true))
+)
+
+/// `varchar(n)`
+macro_rules! varchar_type(
+    ($name:ident, $capacity:expr) => (fixed_string!($name, $capacity, false))
+)
+
+
+
+#[cfg(test)]
+mod test {
+    use super::TooLong;
+
+    char_type!(Char9, 9)
+    varchar_type!(Varchar10, 10)
+
+    #[test]
+    fn test_varchar() {
+        let v = Varchar10::new("hello").unwrap();
+        assert_eq!(v.as_slice(), "hello")
+        assert_eq!(v.len(), 5)
+        assert_eq!(v, v.clone())
+        assert!(v < Varchar10::new("world").unwrap())
+        assert_eq!(Varchar10::new("0123456789").unwrap().as_slice(), "0123456789")
+        assert_eq!(Varchar10::new("0123456789a"), Err(TooLong { capacity: 10, length: 11 }))
+    }
+
+    #[test]
+    fn test_char() {
+        assert_eq!(Char9::new("12345").unwrap().as_slice(), "12345    ")
+        assert_eq!(Char9::new("12345").unwrap(), Char9::new("12345    ").unwrap())
+        assert!(Char9::new("1234567890").is_err())
+    }
+}
@@ -0,0 +1,1147 @@
+//! The CH-benCHmark: the 22 TPC-H queries adapted to the TPC-C schema, run
+//! against `Tables` in between NewOrder and Payment transactions.
+//!
+//! `Tables` has no concurrency control, so queries and transactions take
+//! turns on one thread: every query sees the state left by all transactions
+//! run before it. The SQL layer lacks subqueries, `LIKE`, `CASE` and outer
+//! joins, so each query is a hand-written plan of primary key lookups, hash
+//! joins and aggregations, with the SQL in the comment above it.
+//!
+//! Stock row `(s_w_id, s_i_id)` is supplied by supplier
+//! `mod(s_w_id * s_i_id, 10000)`, and nations are keyed by the ASCII codes of
+//! `0-9A-Za-z` so that the first character of `c_state` is a nation key.
+
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+use std::io::File;
+use std::io::fs::PathExtensions;
+use std::num::pow;
+use std::u64;
+use std::rand::{task_rng, Rng};
+
+use time::precise_time_ns;
+
+use super::urand;
+use driver::random_transaction;
+use dynamic::{Row, ToValue, TextValue};
+use numeric::Numeric;
+use query::{rows, Operator, Scan, Sort, SortKey};
+use tables::{Tables, Customer};
+use timestamp::{Date, Timestamp};
+
+
+const NATION_KEYS: &'static str =
+    "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Name and region of the nation with the key `NATION_KEYS[i]`.
+const NATIONS: [(&'static str, i32), ..62] = [
+    ("Australia", 3), ("Belgium", 4), ("Cameroon", 0), ("Canada", 1), ("Chile", 1),
+    ("China", 2), ("Egypt", 0), ("England", 4), ("France", 4), ("Germany", 4),
+    ("Greece", 4), ("India", 2), ("Indonesia", 2), ("Iran", 2), ("Israel", 2),
+    ("Jamaica", 1), ("Japan", 2), ("Kenya", 0), ("Malaysia", 2), ("Mexico", 1),
+    ("Morocco", 0), ("Mozambique", 0), ("New Zealand", 3), ("Nigeria", 0), ("Norway", 4),
+    ("Pakistan", 2), ("Peru", 1), ("Poland", 4), ("Portugal", 4), ("Russia", 4),
+    ("Scotland", 4), ("Singapore", 2), ("South Africa", 0), ("Spain", 4), ("Sweden", 4),
+    ("Syria", 2), ("Taiwan", 2), ("Thailand", 2), ("Tunisia", 0), ("United Kingdom", 4),
+    ("United States", 1), ("Vietnam", 2), ("Zambia", 0), ("Argentina", 1), ("Austria", 4),
+    ("Bangladesh", 2), ("Brazil", 1), ("Cambodia", 2), ("Denmark", 4), ("Finland", 4),
+    ("Hungary", 4), ("Ireland", 4), ("Italy", 4), ("Luxembourg", 4), ("Netherlands", 4),
+    ("Philippines", 2), ("Romania", 4), ("Switzerland", 4), ("Turkey", 2), ("Ukraine", 4),
+    ("Venezuela", 1), ("Fiji", 3),
+];
+
+const REGIONS: [&'static str, ..5] = ["Africa", "America", "Asia", "Australia", "Europe"];
+
+const SUPPLIERS: i32 = 10000;
+
+pub const QUERIES: uint = 22;
+
+/// Writes the `.tbl` files of the supplier, nation and region tables, each
+/// only if it does not exist yet.
+pub fn generate_tables() {
+    let mut rng = task_rng();
+    let regions = REGIONS.iter().enumerate().map(|(key, name)| {
+        format!("{}|{}|{}", key, name, random_text(&mut rng, 31, 115))
+    }).collect();
+    write_tbl("../data/tpcc_region.tbl", regions);
+
+    let nations = NATIONS.iter().zip(NATION_KEYS.bytes()).map(|(&(name, region), key)| {
+        format!("{}|{}|{}|{}", key, name, region, random_text(&mut rng, 31, 114))
+    }).collect();
+    write_tbl("../data/tpcc_nation.tbl", nations);
+
+    let suppliers = range(0, SUPPLIERS).map(|key| {
+        let nation = NATION_KEYS.as_bytes()[rng.gen_range(0, NATION_KEYS.len())];
+        let phone = format!("{}-{}-{}-{}", urand(10, 34, &mut rng), urand(100, 999, &mut rng),
+                            urand(100, 999, &mut rng), urand(1000, 9999, &mut rng));
+        let balance = Numeric::new(urand(-99999, 999999, &mut rng) as i64, 12, 2);
+        // a few suppliers have complaints, see query 16
+        let mut comment = random_text(&mut rng, 25, 90);
+        if urand(1, 100, &mut rng) <= 5 {
+            comment.push_str(" bad");
+        }
+        format!("{}|Supplier#{:09}|{}|{}|{}|{}|{}", key, key, random_text(&mut rng, 10, 40),
+                nation, phone, balance, comment)
+    }).collect();
+    write_tbl("../data/tpcc_supplier.tbl", suppliers);
+}
+
+fn random_text<R: Rng>(rng: &mut R, min_len: uint, max_len: uint) -> String {
+    let alphabet = "abcdefghijklmnopqrstuvwxyz ";
+    range(0, rng.gen_range(min_len, max_len + 1))
+        .map(|_| alphabet.char_at(rng.gen_range(0, alphabet.len())))
+        .collect()
+}
+
+fn write_tbl(filename: &str, lines: Vec<String>) {
+    let path = Path::new(filename);
+    if path.exists() {
+        println!("{} exists, skipped", filename);
+        return;
+    }
+    let mut file = File::create(&path);
+    for line in lines.iter() {
+        if let Err(e) = file.write_line(line.as_slice()) {
+            fail!("{}: {}", filename, e)
+        }
+    }
+    println!("{}: {} rows", filename, lines.len());
+}
+
+/// Runs all queries `rounds` times, each after another `transactions`
+/// NewOrder or Payment transactions, and prints the latency per query.
+pub fn run(tables: &mut Tables, rounds: uint, transactions: uint) {
+    let rng = &mut task_rng();
+    let mut latencies: Vec<Vec<u64>> = Vec::from_fn(QUERIES, |_| Vec::new());
+    let mut result_rows = Vec::from_elem(QUERIES, 0u);
+    let mut transaction_time = 0u64;
+
+    for _ in range(0, rounds) {
+        for q in range(0, QUERIES) {
+            let time = precise_time_ns();
+            for _ in range(0, transactions) {
+                random_transaction(tables, rng);
+            }
+            transaction_time += precise_time_ns() - time;
+
+            let time = precise_time_ns();
+            let result = query(q + 1, tables);
+            latencies.get_mut(q).push(precise_time_ns() - time);
+            *result_rows.get_mut(q) = result.len();
+        }
+    }
+
+    let total = rounds * QUERIES * transactions;
+    println!("{} transactions, {:.0} per second between the queries", total,
+             total as f64 / (transaction_time as f64 / 1e9f64));
+    for (q, times) in latencies.iter().enumerate() {
+        let sum = times.iter().fold(0, |sum, &t| sum + t);
+        let min = times.iter().fold(u64::MAX, |min, &t| if t < min { t } else { min });
+        let max = times.iter().fold(0, |max, &t| if t > max { t } else { max });
+        println!("Q{:<2} {:>8} rows  min {:>10.3}ms  avg {:>10.3}ms  max {:>10.3}ms", q + 1,
+                 result_rows[q], min as f64 / 1e6f64, sum as f64 / times.len() as f64 / 1e6f64,
+                 max as f64 / 1e6f64);
+    }
+}
+
+/// Runs query `n` (1 to 22).
+pub fn query(n: uint, t: &Tables) -> Vec<Row> {
+    match n {
+        1 => q1(t), 2 => q2(t), 3 => q3(t), 4 => q4(t), 5 => q5(t), 6 => q6(t), 7 => q7(t),
+        8 => q8(t), 9 => q9(t), 10 => q10(t), 11 => q11(t), 12 => q12(t), 13 => q13(t),
+        14 => q14(t), 15 => q15(t), 16 => q16(t), 17 => q17(t), 18 => q18(t), 19 => q19(t),
+        20 => q20(t), 21 => q21(t), 22 => q22(t),
+        _ => fail!("there is no query {}", n)
+    }
+}
+
+fn timestamp(year: i64, month: uint, day: uint) -> Timestamp {
+    Date::from_ymd(year, month, day).unwrap().at_midnight()
+}
+
+fn year(t: &Timestamp) -> i64 {
+    let (year, _, _) = t.date().ymd();
+    year
+}
+
+fn supplier_key(s_w_id: i32, s_i_id: i32) -> i32 {
+    (s_w_id * s_i_id) % SUPPLIERS
+}
+
+/// `ascii(substr(c_state, 1, 1))`
+fn customer_nation(customer: &Customer) -> i32 {
+    customer.c_state.as_slice().as_bytes()[0] as i32
+}
+
+fn zero(precision: uint) -> Numeric {
+    Numeric::new(0, 12, precision)
+}
+
+/// `sum / count` with two more digits after the decimal point, NULL if
+/// `count` is zero.
+fn average(sum: Numeric, count: i64) -> Option<Numeric> {
+    if count == 0 {
+        return None;
+    }
+    Some(Numeric::new(sum.raw_value() * 100 / count, 12, sum.precision() + 2))
+}
+
+/// `factor * a / b` with four digits after the decimal point, NULL if `b` is
+/// zero.
+fn divide(factor: i64, a: Numeric, b: Numeric) -> Option<Numeric> {
+    if b.raw_value() == 0 {
+        return None;
+    }
+    let scale = pow(10i64, 4 + b.precision() - a.precision());
+    Some(Numeric::new(factor * a.raw_value() * scale / b.raw_value(), 12, 4))
+}
+
+/// The keys of the nations in the regions `region` holds for.
+fn nations_in(t: &Tables, region: |&str| -> bool) -> HashSet<i32> {
+    let regions: HashSet<i32> = t.region_table.scan()
+        .filter(|r| region(r.r_name.as_slice().trim_right()))
+        .map(|r| r.r_regionkey)
+        .collect();
+    t.nation_table.scan()
+        .filter(|n| regions.contains(&n.n_regionkey))
+        .map(|n| n.n_nationkey)
+        .collect()
+}
+
+fn nation_names(t: &Tables) -> HashMap<i32, &str> {
+    t.nation_table.scan().map(|n| (n.n_nationkey, n.n_name.as_slice().trim_right())).collect()
+}
+
+fn nation_key(t: &Tables, name: &str) -> Option<i32> {
+    t.nation_table.scan()
+        .find(|n| n.n_name.as_slice().trim_right() == name)
+        .map(|n| n.n_nationkey)
+}
+
+fn order_by(unsorted: Vec<Row>, keys: &[(uint, bool)]) -> Vec<Row> {
+    let keys = keys.iter()
+        .map(|&(column, descending)| SortKey { column: column, descending: descending })
+        .collect();
+    let mut sort = Sort::new(box Scan::new(unsorted.iter()), keys);
+    rows(&mut sort as &mut Operator)
+}
+
+// select ol_number, sum(ol_quantity) as sum_qty, sum(ol_amount) as sum_amount,
+//        avg(ol_quantity) as avg_qty, avg(ol_amount) as avg_amount, count(*) as count_order
+// from orderline
+// where ol_delivery_d > '2007-01-02 00:00:00.000000'
+// group by ol_number order by ol_number
+fn q1(t: &Tables) -> Vec<Row> {
+    let since = timestamp(2007, 1, 2);
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        match ol.ol_delivery_d {
+            Some(delivery) if delivery > since => {},
+            _ => continue
+        }
+        let group = groups.find_or_insert_with(ol.ol_number, |_| (zero(0), zero(2), 0i64));
+        let (quantity, amount, count) = *group;
+        *group = (quantity + ol.ol_quantity, amount + ol.ol_amount, count + 1);
+    }
+    let result = groups.into_iter().map(|(number, (quantity, amount, count))| {
+        vec![number.to_value(), quantity.to_value(), amount.to_value(),
+             average(quantity, count).to_value(), average(amount, count).to_value(),
+             count.to_value()]
+    }).collect();
+    order_by(result, [(0, false)])
+}
+
+// select su_suppkey, su_name, n_name, i_id, i_name, su_address, su_phone, su_comment
+// from item, supplier, stock, nation, region,
+//      (select s_i_id as m_i_id, min(s_quantity) as m_s_quantity
+//       from stock, supplier, nation, region
+//       where mod((s_w_id*s_i_id),10000)=su_suppkey and su_nationkey=n_nationkey
+//             and n_regionkey=r_regionkey and r_name like 'Europ%'
+//       group by s_i_id) m
+// where i_id = s_i_id and mod((s_w_id * s_i_id), 10000) = su_suppkey
+//       and su_nationkey = n_nationkey and n_regionkey = r_regionkey
+//       and i_data like '%b' and r_name like 'Europ%'
+//       and i_id = m_i_id and s_quantity = m_s_quantity
+// order by n_name, su_name, i_id
+fn q2(t: &Tables) -> Vec<Row> {
+    let europe = nations_in(t, |name| name.starts_with("Europ"));
+    let european = |s_w_id: i32, s_i_id: i32| {
+        t.supplier_table.find(&supplier_key(s_w_id, s_i_id))
+            .and_then(|su| if europe.contains(&su.su_nationkey) { Some(su) } else { None })
+    };
+    let mut minimum = HashMap::new();
+    for s in t.stock_table.scan() {
+        if european(s.s_w_id, s.s_i_id).is_some() {
+            let quantity = minimum.find_or_insert_with(s.s_i_id, |_| s.s_quantity);
+            *quantity = min(*quantity, s.s_quantity);
+        }
+    }
+
+    let names = nation_names(t);
+    let mut result = Vec::new();
+    for s in t.stock_table.scan() {
+        if minimum.find(&s.s_i_id) != Some(&s.s_quantity) {
+            continue;
+        }
+        let (su, item) = match (european(s.s_w_id, s.s_i_id), t.item_table.find(&s.s_i_id)) {
+            (Some(su), Some(item)) if item.i_data.as_slice().ends_with("b") => (su, item),
+            _ => continue
+        };
+        result.push(vec![su.su_suppkey.to_value(), su.su_name.to_value(),
+                         TextValue(names[su.su_nationkey].to_string()), item.i_id.to_value(),
+                         item.i_name.to_value(), su.su_address.to_value(),
+                         su.su_phone.to_value(), su.su_comment.to_value()]);
+    }
+    order_by(result, [(2, false), (1, false), (3, false)])
+}
+
+// select ol_o_id, ol_w_id, ol_d_id, sum(ol_amount) as revenue, o_entry_d
+// from customer, neworder, "order", orderline
+// where c_state like 'A%' and c_id = o_c_id and c_w_id = o_w_id and c_d_id = o_d_id
+//       and no_w_id = o_w_id and no_d_id = o_d_id and no_o_id = o_id
+//       and ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+//       and o_entry_d > '2007-01-02 00:00:00.000000'
+// group by ol_o_id, ol_w_id, ol_d_id, o_entry_d
+// order by revenue desc, o_entry_d
+fn q3(t: &Tables) -> Vec<Row> {
+    let since = timestamp(2007, 1, 2);
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        let key = (ol.ol_w_id, ol.ol_d_id, ol.ol_o_id);
+        if t.neworder_table.find(&key).is_none() {
+            continue;
+        }
+        let order = match t.order_table.find(&key) {
+            Some(order) if order.o_entry_d > since => order,
+            _ => continue
+        };
+        match t.customer_table.find(&(order.o_w_id, order.o_d_id, order.o_c_id)) {
+            Some(c) if c.c_state.as_slice().starts_with("A") => {},
+            _ => continue
+        }
+        let revenue = groups.find_or_insert_with((key, order.o_entry_d), |_| zero(2));
+        *revenue = *revenue + ol.ol_amount;
+    }
+    let result = groups.into_iter().map(|(((w_id, d_id, o_id), entry_d), revenue)| {
+        vec![o_id.to_value(), w_id.to_value(), d_id.to_value(), revenue.to_value(),
+             entry_d.to_value()]
+    }).collect();
+    order_by(result, [(3, true), (4, false)])
+}
+
+// select o_ol_cnt, count(*) as order_count
+// from "order"
+// where o_entry_d >= '2007-01-02 00:00:00.000000' and o_entry_d < '2012-01-02 00:00:00.000000'
+//       and exists (select * from orderline
+//                   where o_id = ol_o_id and o_w_id = ol_w_id and o_d_id = ol_d_id
+//                         and ol_delivery_d >= o_entry_d)
+// group by o_ol_cnt order by o_ol_cnt
+fn q4(t: &Tables) -> Vec<Row> {
+    let (from, to) = (timestamp(2007, 1, 2), timestamp(2012, 1, 2));
+    let mut orders = HashSet::new();
+    for ol in t.orderline_table.scan() {
+        let key = (ol.ol_w_id, ol.ol_d_id, ol.ol_o_id);
+        match (t.order_table.find(&key), ol.ol_delivery_d) {
+            (Some(order), Some(delivery)) if order.o_entry_d >= from && order.o_entry_d < to
+                                             && delivery >= order.o_entry_d => {
+                orders.insert(key);
+            },
+            _ => {}
+        }
+    }
+    let mut groups = HashMap::new();
+    for key in orders.iter() {
+        let count = groups.find_or_insert_with(t.order_table.lookup(*key).o_ol_cnt, |_| 0i64);
+        *count += 1;
+    }
+    let result = groups.into_iter()
+        .map(|(ol_cnt, count)| vec![ol_cnt.to_value(), count.to_value()])
+        .collect();
+    order_by(result, [(0, false)])
+}
+
+// select n_name, sum(ol_amount) as revenue
+// from customer, "order", orderline, stock, supplier, nation, region
+// where c_id = o_c_id and c_w_id = o_w_id and c_d_id = o_d_id
+//       and ol_o_id = o_id and ol_w_id = o_w_id and ol_d_id = o_d_id
+//       and ol_w_id = s_w_id and ol_i_id = s_i_id
+//       and mod((s_w_id * s_i_id), 10000) = su_suppkey
+//       and ascii(substr(c_state, 1, 1)) = su_nationkey
+//       and su_nationkey = n_nationkey and n_regionkey = r_regionkey
+//       and r_name = 'Europe' and o_entry_d >= '2007-01-02 00:00:00.000000'
+// group by n_name order by revenue desc
+fn q5(t: &Tables) -> Vec<Row> {
+    let since = timestamp(2007, 1, 2);
+    let europe = nations_in(t, |name| name == "Europe");
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        let order = match t.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)) {
+            Some(order) if order.o_entry_d >= since => order,
+            _ => continue
+        };
+        if t.stock_table.find(&(ol.ol_w_id, ol.ol_i_id)).is_none() {
+            continue;
+        }
+        let nation = match t.supplier_table.find(&supplier_key(ol.ol_w_id, ol.ol_i_id)) {
+            Some(su) if europe.contains(&su.su_nationkey) => su.su_nationkey,
+            _ => continue
+        };
+        match t.customer_table.find(&(order.o_w_id, order.o_d_id, order.o_c_id)) {
+            Some(c) if customer_nation(c) == nation => {},
+            _ => continue
+        }
+        let revenue = groups.find_or_insert_with(nation, |_| zero(2));
+        *revenue = *revenue + ol.ol_amount;
+    }
+    let names = nation_names(t);
+    let result = groups.into_iter()
+        .map(|(nation, revenue)| vec![TextValue(names[nation].to_string()), revenue.to_value()])
+        .collect();
+    order_by(result, [(1, true)])
+}
+
+// select sum(ol_amount) as revenue
+// from orderline
+// where ol_delivery_d >= '1999-01-01 00:00:00.000000'
+//       and ol_delivery_d < '2020-01-01 00:00:00.000000'
+//       and ol_quantity between 1 and 100000
+fn q6(t: &Tables) -> Vec<Row> {
+    let (from, to) = (timestamp(1999, 1, 1), timestamp(2020, 1, 1));
+    let (low, high) = (Numeric::new(1, 6, 0), Numeric::new(100000, 6, 0));
+    let mut revenue = None;
+    for ol in t.orderline_table.scan() {
+        match ol.ol_delivery_d {
+            Some(delivery) if delivery >= from && delivery < to => {},
+            _ => continue
+        }
+        if ol.ol_quantity >= low && ol.ol_quantity <= high {
+            revenue = Some(revenue.unwrap_or(zero(2)) + ol.ol_amount);
+        }
+    }
+    vec![vec![revenue.to_value()]]
+}
+
+// select su_nationkey as supp_nation, substr(c_state, 1, 1) as cust_nation,
+//        extract(year from o_entry_d) as l_year, sum(ol_amount) as revenue
+// from supplier, stock, orderline, "order", customer, nation n1, nation n2
+// where ol_supply_w_id = s_w_id and ol_i_id = s_i_id
+//       and mod((s_w_id * s_i_id), 10000) = su_suppkey
+//       and ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+//       and c_id = o_c_id and c_w_id = o_w_id and c_d_id = o_d_id
+//       and su_nationkey = n1.n_nationkey and ascii(substr(c_state, 1, 1)) = n2.n_nationkey
+//       and ((n1.n_name = 'Germany' and n2.n_name = 'Cambodia')
+//            or (n1.n_name = 'Cambodia' and n2.n_name = 'Germany'))
+//       and ol_delivery_d between '2007-01-02 00:00:00.000000' and '2012-01-02 00:00:00.000000'
+// group by su_nationkey, substr(c_state, 1, 1), extract(year from o_entry_d)
+// order by su_nationkey, cust_nation, l_year
+fn q7(t: &Tables) -> Vec<Row> {
+    let (from, to) = (timestamp(2007, 1, 2), timestamp(2012, 1, 2));
+    let (germany, cambodia) = match (nation_key(t, "Germany"), nation_key(t, "Cambodia")) {
+        (Some(germany), Some(cambodia)) => (germany, cambodia),
+        _ => return Vec::new()
+    };
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        match ol.ol_delivery_d {
+            Some(delivery) if delivery >= from && delivery <= to => {},
+            _ => continue
+        }
+        if t.stock_table.find(&(ol.ol_supply_w_id, ol.ol_i_id)).is_none() {
+            continue;
+        }
+        let supplier_nation = match t.supplier_table.find(&supplier_key(ol.ol_supply_w_id,
+                                                                        ol.ol_i_id)) {
+            Some(su) if su.su_nationkey == germany || su.su_nationkey == cambodia =>
+                su.su_nationkey,
+            _ => continue
+        };
+        let order = match t.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)) {
+            Some(order) => order,
+            None => continue
+        };
+        let customer = match t.customer_table.find(&(order.o_w_id, order.o_d_id,
+                                                     order.o_c_id)) {
+            Some(c) if customer_nation(c) == germany + cambodia - supplier_nation => c,
+            _ => continue
+        };
+        let key = (supplier_nation, customer.c_state.as_slice()[..1], year(&order.o_entry_d));
+        let revenue = groups.find_or_insert_with(key, |_| zero(2));
+        *revenue = *revenue + ol.ol_amount;
+    }
+    let result = groups.into_iter().map(|((supplier_nation, customer_nation, year), revenue)| {
+        vec![supplier_nation.to_value(), TextValue(customer_nation.to_string()),
+             year.to_value(), revenue.to_value()]
+    }).collect();
+    order_by(result, [(0, false), (1, false), (2, false)])
+}
+
+// select extract(year from o_entry_d) as l_year,
+//        sum(case when n2.n_name = 'Germany' then ol_amount else 0 end) / sum(ol_amount)
+//            as mkt_share
+// from item, supplier, stock, orderline, "order", customer, nation n1, nation n2, region
+// where i_id = s_i_id and ol_i_id = s_i_id and ol_supply_w_id = s_w_id
+//       and mod((s_w_id * s_i_id), 10000) = su_suppkey
+//       and ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+//       and c_id = o_c_id and c_w_id = o_w_id and c_d_id = o_d_id
+//       and n1.n_nationkey = ascii(substr(c_state, 1, 1)) and n1.n_regionkey = r_regionkey
+//       and ol_i_id < 1000 and r_name = 'Europe' and su_nationkey = n2.n_nationkey
+//       and o_entry_d between '2007-01-02 00:00:00.000000' and '2012-01-02 00:00:00.000000'
+//       and i_data like '%b' and i_id = ol_i_id
+// group by extract(year from o_entry_d) order by l_year
+fn q8(t: &Tables) -> Vec<Row> {
+    let (from, to) = (timestamp(2007, 1, 2), timestamp(2012, 1, 2));
+    let europe = nations_in(t, |name| name == "Europe");
+    let germany = nation_key(t, "Germany");
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        if ol.ol_i_id >= 1000 {
+            continue;
+        }
+        match t.item_table.find(&ol.ol_i_id) {
+            Some(item) if item.i_data.as_slice().ends_with("b") => {},
+            _ => continue
+        }
+        if t.stock_table.find(&(ol.ol_supply_w_id, ol.ol_i_id)).is_none() {
+            continue;
+        }
+        let su = match t.supplier_table.find(&supplier_key(ol.ol_supply_w_id, ol.ol_i_id)) {
+            Some(su) => su,
+            None => continue
+        };
+        let order = match t.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)) {
+            Some(order) if order.o_entry_d >= from && order.o_entry_d <= to => order,
+            _ => continue
+        };
+        match t.customer_table.find(&(order.o_w_id, order.o_d_id, order.o_c_id)) {
+            Some(c) if europe.contains(&customer_nation(c)) => {},
+            _ => continue
+        }
+        let group = groups.find_or_insert_with(year(&order.o_entry_d), |_| (zero(2), zero(2)));
+        let (german, total) = *group;
+        *group = if Some(su.su_nationkey) == germany {
+            (german + ol.ol_amount, total + ol.ol_amount)
+        } else {
+            (german, total + ol.ol_amount)
+        };
+    }
+    let result = groups.into_iter().map(|(year, (german, total))| {
+        vec![year.to_value(), divide(1, german, total).to_value()]
+    }).collect();
+    order_by(result, [(0, false)])
+}
+
+// select n_name, extract(year from o_entry_d) as l_year, sum(ol_amount) as sum_profit
+// from item, stock, supplier, orderline, "order", nation
+// where ol_i_id = s_i_id and ol_supply_w_id = s_w_id
+//       and mod((s_w_id * s_i_id), 10000) = su_suppkey
+//       and ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+//       and ol_i_id = i_id and su_nationkey = n_nationkey and i_data like '%BB'
+// group by n_name, extract(year from o_entry_d)
+// order by n_name, l_year desc
+fn q9(t: &Tables) -> Vec<Row> {
+    let names = nation_names(t);
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        match t.item_table.find(&ol.ol_i_id) {
+            Some(item) if item.i_data.as_slice().ends_with("BB") => {},
+            _ => continue
+        }
+        if t.stock_table.find(&(ol.ol_supply_w_id, ol.ol_i_id)).is_none() {
+            continue;
+        }
+        let nation = match t.supplier_table.find(&supplier_key(ol.ol_supply_w_id, ol.ol_i_id))
+                .and_then(|su| names.find(&su.su_nationkey)) {
+            Some(&name) => name,
+            None => continue
+        };
+        let order = match t.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)) {
+            Some(order) => order,
+            None => continue
+        };
+        let profit = groups.find_or_insert_with((nation, year(&order.o_entry_d)), |_| zero(2));
+        *profit = *profit + ol.ol_amount;
+    }
+    let result = groups.into_iter().map(|((nation, year), profit)| {
+        vec![TextValue(nation.to_string()), year.to_value(), profit.to_value()]
+    }).collect();
+    order_by(result, [(0, false), (1, true)])
+}
+
+// select c_id, c_last, sum(ol_amount) as revenue, c_city, c_phone, n_name
+// from customer, "order", orderline, nation
+// where c_id = o_c_id and c_w_id = o_w_id and c_d_id = o_d_id
+//       and ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+//       and o_entry_d >= '2007-01-02 00:00:00.000000' and o_entry_d <= ol_delivery_d
+//       and n_nationkey = ascii(substr(c_state, 1, 1))
+// group by c_id, c_last, c_city, c_phone, n_name
+// order by revenue desc
+fn q10(t: &Tables) -> Vec<Row> {
+    let since = timestamp(2007, 1, 2);
+    let names = nation_names(t);
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        let order = match (t.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)),
+                           ol.ol_delivery_d) {
+            (Some(order), Some(delivery)) if order.o_entry_d >= since
+                                             && order.o_entry_d <= delivery => order,
+            _ => continue
+        };
+        let c = match t.customer_table.find(&(order.o_w_id, order.o_d_id, order.o_c_id)) {
+            Some(c) => c,
+            None => continue
+        };
+        let nation = match names.find(&customer_nation(c)) {
+            Some(&name) => name,
+            None => continue
+        };
+        let key = (c.c_id, c.c_last, c.c_city, c.c_phone, nation);
+        let revenue = groups.find_or_insert_with(key, |_| zero(2));
+        *revenue = *revenue + ol.ol_amount;
+    }
+    let result = groups.into_iter().map(|((c_id, c_last, c_city, c_phone, nation), revenue)| {
+        vec![c_id.to_value(), c_last.to_value(), revenue.to_value(), c_city.to_value(),
+             c_phone.to_value(), TextValue(nation.to_string())]
+    }).collect();
+    order_by(result, [(2, true)])
+}
+
+// select s_i_id, sum(s_order_cnt) as ordercount
+// from stock, supplier, nation
+// where mod((s_w_id * s_i_id), 10000) = su_suppkey and su_nationkey = n_nationkey
+//       and n_name = 'Germany'
+// group by s_i_id
+// having sum(s_order_cnt) > (select sum(s_order_cnt) * .005
+//                            from stock, supplier, nation
+//                            where mod((s_w_id * s_i_id), 10000) = su_suppkey
+//                                  and su_nationkey = n_nationkey and n_name = 'Germany')
+// order by ordercount desc
+fn q11(t: &Tables) -> Vec<Row> {
+    let germany = nation_key(t, "Germany");
+    let mut groups = HashMap::new();
+    let mut total = zero(0);
+    for s in t.stock_table.scan() {
+        match t.supplier_table.find(&supplier_key(s.s_w_id, s.s_i_id)) {
+            Some(su) if Some(su.su_nationkey) == germany => {},
+            _ => continue
+        }
+        let count = groups.find_or_insert_with(s.s_i_id, |_| zero(0));
+        *count = *count + s.s_order_cnt;
+        total = total + s.s_order_cnt;
+    }
+    // both have the precision of s_order_cnt
+    let result = groups.into_iter()
+        .filter(|&(_, count)| count.raw_value() * 1000 > total.raw_value() * 5)
+        .map(|(i_id, count)| vec![i_id.to_value(), count.to_value()])
+        .collect();
+    order_by(result, [(1, true)])
+}
+
+// select o_ol_cnt,
+//        sum(case when o_carrier_id = 1 or o_carrier_id = 2 then 1 else 0 end)
+//            as high_line_count,
+//        sum(case when o_carrier_id <> 1 and o_carrier_id <> 2 then 1 else 0 end)
+//            as low_line_count
+// from "order", orderline
+// where ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+//       and o_entry_d <= ol_delivery_d and ol_delivery_d < '2020-01-01 00:00:00.000000'
+// group by o_ol_cnt order by o_ol_cnt
+fn q12(t: &Tables) -> Vec<Row> {
+    let to = timestamp(2020, 1, 1);
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        let order = match (t.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)),
+                           ol.ol_delivery_d) {
+            (Some(order), Some(delivery)) if order.o_entry_d <= delivery && delivery < to =>
+                order,
+            _ => continue
+        };
+        let group = groups.find_or_insert_with(order.o_ol_cnt, |_| (0i64, 0i64));
+        let (high, low) = *group;
+        // a NULL carrier counts as neither
+        *group = match order.o_carrier_id {
+            Some(1) | Some(2) => (high + 1, low),
+            Some(_) => (high, low + 1),
+            None => (high, low)
+        };
+    }
+    let result = groups.into_iter().map(|(ol_cnt, (high, low))| {
+        vec![ol_cnt.to_value(), high.to_value(), low.to_value()]
+    }).collect();
+    order_by(result, [(0, false)])
+}
+
+// select c_count, count(*) as custdist
+// from (select c_id, count(o_id)
+//       from customer left outer join "order"
+//            on (c_w_id = o_w_id and c_d_id = o_d_id and c_id = o_c_id and o_carrier_id > 8)
+//       group by c_id) as c_orders (c_id, c_count)
+// group by c_count
+// order by custdist desc, c_count desc
+fn q13(t: &Tables) -> Vec<Row> {
+    let mut orders = HashMap::new();
+    for order in t.order_table.scan() {
+        match order.o_carrier_id {
+            Some(carrier) if carrier > 8 => {},
+            _ => continue
+        }
+        let count = orders.find_or_insert_with((order.o_w_id, order.o_d_id, order.o_c_id),
+                                               |_| 0i64);
+        *count += 1;
+    }
+    let mut c_orders = HashMap::new();
+    for c in t.customer_table.scan() {
+        let count = c_orders.find_or_insert_with(c.c_id, |_| 0i64);
+        *count += orders.find(&(c.c_w_id, c.c_d_id, c.c_id)).map_or(0, |&count| count);
+    }
+    let mut groups = HashMap::new();
+    for (_, c_count) in c_orders.into_iter() {
+        let count = groups.find_or_insert_with(c_count, |_| 0i64);
+        *count += 1;
+    }
+    let result = groups.into_iter()
+        .map(|(c_count, custdist)| vec![c_count.to_value(), custdist.to_value()])
+        .collect();
+    order_by(result, [(1, true), (0, true)])
+}
+
+// select 100.00 * sum(case when i_data like 'PR%' then ol_amount else 0 end)
+//            / (1 + sum(ol_amount)) as promo_revenue
+// from orderline, item
+// where ol_i_id = i_id and ol_delivery_d >= '2007-01-02 00:00:00.000000'
+//       and ol_delivery_d < '2020-01-02 00:00:00.000000'
+fn q14(t: &Tables) -> Vec<Row> {
+    let (from, to) = (timestamp(2007, 1, 2), timestamp(2020, 1, 2));
+    let mut sums = None;
+    for ol in t.orderline_table.scan() {
+        match ol.ol_delivery_d {
+            Some(delivery) if delivery >= from && delivery < to => {},
+            _ => continue
+        }
+        let item = match t.item_table.find(&ol.ol_i_id) {
+            Some(item) => item,
+            None => continue
+        };
+        let (promo, total) = sums.unwrap_or((zero(2), zero(2)));
+        sums = Some(if item.i_data.as_slice().starts_with("PR") {
+            (promo + ol.ol_amount, total + ol.ol_amount)
+        } else {
+            (promo, total + ol.ol_amount)
+        });
+    }
+    let one = Numeric::new(1, 1, 0);
+    let promo_revenue = sums.and_then(|(promo, total)| divide(100, promo, total + one));
+    vec![vec![promo_revenue.to_value()]]
+}
+
+// with revenue (supplier_no, total_revenue) as (
+//      select mod((s_w_id * s_i_id), 10000) as supplier_no, sum(ol_amount) as total_revenue
+//      from orderline, stock
+//      where ol_i_id = s_i_id and ol_supply_w_id = s_w_id
+//            and ol_delivery_d >= '2007-01-02 00:00:00.000000'
+//      group by mod((s_w_id * s_i_id), 10000))
+// select su_suppkey, su_name, su_address, su_phone, total_revenue
+// from supplier, revenue
+// where su_suppkey = supplier_no and total_revenue = (select max(total_revenue) from revenue)
+// order by su_suppkey
+fn q15(t: &Tables) -> Vec<Row> {
+    let since = timestamp(2007, 1, 2);
+    let mut revenue = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        match ol.ol_delivery_d {
+            Some(delivery) if delivery >= since => {},
+            _ => continue
+        }
+        if t.stock_table.find(&(ol.ol_supply_w_id, ol.ol_i_id)).is_none() {
+            continue;
+        }
+        let total = revenue.find_or_insert_with(supplier_key(ol.ol_supply_w_id, ol.ol_i_id),
+                                                |_| zero(2));
+        *total = *total + ol.ol_amount;
+    }
+    let max = match revenue.values().max() {
+        Some(&max) => max,
+        None => return Vec::new()
+    };
+    let result = revenue.iter()
+        .filter(|&(_, total)| *total == max)
+        .filter_map(|(key, total)| t.supplier_table.find(key).map(|su| {
+            vec![su.su_suppkey.to_value(), su.su_name.to_value(), su.su_address.to_value(),
+                 su.su_phone.to_value(), total.to_value()]
+        }))
+        .collect();
+    order_by(result, [(0, false)])
+}
+
+// select i_name, substr(i_data, 1, 3) as brand, i_price,
+//        count(distinct (mod((s_w_id * s_i_id), 10000))) as supplier_cnt
+// from stock, item
+// where i_id = s_i_id and i_data not like 'zz%'
+//       and (mod((s_w_id * s_i_id), 10000) not in
+//            (select su_suppkey from supplier where su_comment like '%bad%'))
+// group by i_name, substr(i_data, 1, 3), i_price
+// order by supplier_cnt desc
+fn q16(t: &Tables) -> Vec<Row> {
+    let bad: HashSet<i32> = t.supplier_table.scan()
+        .filter(|su| su.su_comment.as_slice().contains("bad"))
+        .map(|su| su.su_suppkey)
+        .collect();
+    let mut groups = HashMap::new();
+    for s in t.stock_table.scan() {
+        let supplier = supplier_key(s.s_w_id, s.s_i_id);
+        if bad.contains(&supplier) {
+            continue;
+        }
+        let item = match t.item_table.find(&s.s_i_id) {
+            Some(item) if !item.i_data.as_slice().starts_with("zz") => item,
+            _ => continue
+        };
+        let data = item.i_data.as_slice();
+        let brand = data[..min(3, data.len())];
+        groups.find_or_insert_with((item.i_name, brand, item.i_price), |_| HashSet::new())
+            .insert(supplier);
+    }
+    let result = groups.into_iter().map(|((name, brand, price), suppliers)| {
+        vec![name.to_value(), TextValue(brand.to_string()), price.to_value(),
+             (suppliers.len() as i64).to_value()]
+    }).collect();
+    order_by(result, [(3, true)])
+}
+
+// select sum(ol_amount) / 2.0 as avg_yearly
+// from orderline,
+//      (select i_id, avg(ol_quantity) as a
+//       from item, orderline
+//       where i_data like '%b' and ol_i_id = i_id
+//       group by i_id) t
+// where ol_i_id = t.i_id and ol_quantity < t.a
+fn q17(t: &Tables) -> Vec<Row> {
+    let mut quantities = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        match t.item_table.find(&ol.ol_i_id) {
+            Some(item) if item.i_data.as_slice().ends_with("b") => {},
+            _ => continue
+        }
+        let group = quantities.find_or_insert_with(ol.ol_i_id, |_| (zero(0), 0i64));
+        let (sum, count) = *group;
+        *group = (sum + ol.ol_quantity, count + 1);
+    }
+    let mut amount = None;
+    for ol in t.orderline_table.scan() {
+        // ol_quantity < sum / count, all of precision 0
+        match quantities.find(&ol.ol_i_id) {
+            Some(&(sum, count)) if ol.ol_quantity.raw_value() * count < sum.raw_value() => {},
+            _ => continue
+        }
+        amount = Some(amount.unwrap_or(zero(2)) + ol.ol_amount);
+    }
+    let avg_yearly = amount.map(|amount| {
+        Numeric::new(amount.raw_value() * 10 / 2, 12, amount.precision() + 1)
+    });
+    vec![vec![avg_yearly.to_value()]]
+}
+
+// select c_last, c_id, o_id, o_entry_d, o_ol_cnt, sum(ol_amount) as amount_sum
+// from customer, "order", orderline
+// where c_id = o_c_id and c_w_id = o_w_id and c_d_id = o_d_id
+//       and ol_w_id = o_w_id and ol_d_id = o_d_id and ol_o_id = o_id
+// group by o_id, o_w_id, o_d_id, c_id, c_last, o_entry_d, o_ol_cnt
+// having sum(ol_amount) > 200
+// order by amount_sum desc, o_entry_d
+fn q18(t: &Tables) -> Vec<Row> {
+    let mut amounts = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        let amount = amounts.find_or_insert_with((ol.ol_w_id, ol.ol_d_id, ol.ol_o_id),
+                                                 |_| zero(2));
+        *amount = *amount + ol.ol_amount;
+    }
+    let threshold = Numeric::new(20000, 12, 2);
+    let mut result = Vec::new();
+    for (key, amount) in amounts.into_iter() {
+        if amount <= threshold {
+            continue;
+        }
+        let order = match t.order_table.find(&key) {
+            Some(order) => order,
+            None => continue
+        };
+        if let Some(c) = t.customer_table.find(&(order.o_w_id, order.o_d_id, order.o_c_id)) {
+            result.push(vec![c.c_last.to_value(), c.c_id.to_value(), order.o_id.to_value(),
+                             order.o_entry_d.to_value(), order.o_ol_cnt.to_value(),
+                             amount.to_value()]);
+        }
+    }
+    order_by(result, [(5, true), (3, false)])
+}
+
+// select sum(ol_amount) as revenue
+// from orderline, item
+// where (ol_i_id = i_id and i_data like '%a' and ol_quantity >= 1 and ol_quantity <= 10
+//        and i_price between 1 and 400000 and ol_w_id in (1, 2, 3))
+//    or (ol_i_id = i_id and i_data like '%b' and ol_quantity >= 1 and ol_quantity <= 10
+//        and i_price between 1 and 400000 and ol_w_id in (1, 2, 4))
+//    or (ol_i_id = i_id and i_data like '%c' and ol_quantity >= 1 and ol_quantity <= 10
+//        and i_price between 1 and 400000 and ol_w_id in (1, 5, 3))
+fn q19(t: &Tables) -> Vec<Row> {
+    let (low_quantity, high_quantity) = (Numeric::new(1, 2, 0), Numeric::new(10, 2, 0));
+    let (low_price, high_price) = (Numeric::new(1, 6, 0), Numeric::new(400000, 6, 0));
+    let mut revenue = None;
+    for ol in t.orderline_table.scan() {
+        if ol.ol_quantity < low_quantity || ol.ol_quantity > high_quantity {
+            continue;
+        }
+        let item = match t.item_table.find(&ol.ol_i_id) {
+            Some(item) if item.i_price >= low_price && item.i_price <= high_price => item,
+            _ => continue
+        };
+        let data = item.i_data.as_slice();
+        let w_id = ol.ol_w_id;
+        if (data.ends_with("a") && (w_id == 1 || w_id == 2 || w_id == 3))
+                || (data.ends_with("b") && (w_id == 1 || w_id == 2 || w_id == 4))
+                || (data.ends_with("c") && (w_id == 1 || w_id == 5 || w_id == 3)) {
+            revenue = Some(revenue.unwrap_or(zero(2)) + ol.ol_amount);
+        }
+    }
+    vec![vec![revenue.to_value()]]
+}
+
+// select su_name, su_address
+// from supplier, nation
+// where su_suppkey in
+//           (select mod(s_i_id * s_w_id, 10000)
+//            from stock, orderline
+//            where s_i_id in (select i_id from item where i_data like 'co%')
+//                  and ol_i_id = s_i_id and ol_delivery_d > '2010-05-23 12:00:00'
+//            group by s_i_id, s_w_id, s_quantity
+//            having 2 * s_quantity > sum(ol_quantity))
+//       and su_nationkey = n_nationkey and n_name = 'Germany'
+// order by su_name
+fn q20(t: &Tables) -> Vec<Row> {
+    let since = timestamp(2010, 5, 23) + ::std::time::Duration::hours(12);
+    let items: HashSet<i32> = t.item_table.scan()
+        .filter(|item| item.i_data.as_slice().starts_with("co"))
+        .map(|item| item.i_id)
+        .collect();
+    // the join is on the item only, so every stock row of an item has the same sum
+    let mut quantities = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        match ol.ol_delivery_d {
+            Some(delivery) if delivery > since && items.contains(&ol.ol_i_id) => {},
+            _ => continue
+        }
+        let quantity = quantities.find_or_insert_with(ol.ol_i_id, |_| zero(0));
+        *quantity = *quantity + ol.ol_quantity;
+    }
+    let mut suppliers = HashSet::new();
+    for s in t.stock_table.scan() {
+        match quantities.find(&s.s_i_id) {
+            Some(quantity) if s.s_quantity + s.s_quantity > *quantity => {
+                suppliers.insert(supplier_key(s.s_w_id, s.s_i_id));
+            },
+            _ => {}
+        }
+    }
+    let germany = nation_key(t, "Germany");
+    let result = t.supplier_table.scan()
+        .filter(|su| suppliers.contains(&su.su_suppkey) && Some(su.su_nationkey) == germany)
+        .map(|su| vec![su.su_name.to_value(), su.su_address.to_value()])
+        .collect();
+    order_by(result, [(0, false)])
+}
+
+// select su_name, count(*) as numwait
+// from supplier, orderline l1, "order", stock, nation
+// where ol_o_id = o_id and ol_w_id = o_w_id and ol_d_id = o_d_id
+//       and ol_w_id = s_w_id and ol_i_id = s_i_id
+//       and mod((s_w_id * s_i_id), 10000) = su_suppkey
+//       and l1.ol_delivery_d > o_entry_d
+//       and not exists (select * from orderline l2
+//                       where l2.ol_o_id = l1.ol_o_id and l2.ol_w_id = l1.ol_w_id
+//                             and l2.ol_d_id = l1.ol_d_id
+//                             and l2.ol_delivery_d > l1.ol_delivery_d)
+//       and su_nationkey = n_nationkey and n_name = 'Germany'
+// group by su_name
+// order by numwait desc, su_name
+fn q21(t: &Tables) -> Vec<Row> {
+    // an orderline has no later one in its order if it was delivered last
+    let mut last_delivery = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        if let Some(delivery) = ol.ol_delivery_d {
+            let last = last_delivery.find_or_insert_with((ol.ol_w_id, ol.ol_d_id, ol.ol_o_id),
+                                                         |_| delivery);
+            if delivery > *last {
+                *last = delivery;
+            }
+        }
+    }
+    let germany = nation_key(t, "Germany");
+    let mut groups = HashMap::new();
+    for ol in t.orderline_table.scan() {
+        let key = (ol.ol_w_id, ol.ol_d_id, ol.ol_o_id);
+        let delivery = match ol.ol_delivery_d {
+            Some(delivery) if last_delivery.find(&key) == Some(&delivery) => delivery,
+            _ => continue
+        };
+        match t.order_table.find(&key) {
+            Some(order) if delivery > order.o_entry_d => {},
+            _ => continue
+        }
+        if t.stock_table.find(&(ol.ol_w_id, ol.ol_i_id)).is_none() {
+            continue;
+        }
+        let su = match t.supplier_table.find(&supplier_key(ol.ol_w_id, ol.ol_i_id)) {
+            Some(su) if Some(su.su_nationkey) == germany => su,
+            _ => continue
+        };
+        let count = groups.find_or_insert_with(su.su_name, |_| 0i64);
+        *count += 1;
+    }
+    let result = groups.into_iter()
+        .map(|(name, numwait)| vec![name.to_value(), numwait.to_value()])
+        .collect();
+    order_by(result, [(1, true), (0, false)])
+}
+
+// select substr(c_state, 1, 1) as country, count(*) as numcust, sum(c_balance) as totacctbal
+// from customer
+// where substr(c_phone, 1, 1) in ('1', '2', '3', '4', '5', '6', '7')
+//       and c_balance > (select avg(c_balance) from customer
+//                        where c_balance > 0.00
+//                              and substr(c_phone, 1, 1) in ('1', '2', '3', '4', '5', '6', '7'))
+//       and not exists (select * from "order"
+//                       where o_c_id = c_id and o_w_id = c_w_id and o_d_id = c_d_id)
+// group by substr(c_state, 1, 1)
+// order by substr(c_state, 1, 1)
+fn q22(t: &Tables) -> Vec<Row> {
+    let selected = |c: &Customer| {
+        let phone = c.c_phone.as_slice();
+        phone.len() > 0 && phone.char_at(0) >= '1' && phone.char_at(0) <= '7'
+    };
+    let (mut sum, mut count) = (zero(2), 0i64);
+    for c in t.customer_table.scan() {
+        if selected(c) && c.c_balance > zero(2) {
+            sum = sum + c.c_balance;
+            count += 1;
+        }
+    }
+    let ordered: HashSet<(i32, i32, i32)> = t.order_table.scan()
+        .map(|order| (order.o_w_id, order.o_d_id, order.o_c_id))
+        .collect();
+    let mut groups = HashMap::new();
+    for c in t.customer_table.scan() {
+        // c_balance > sum / count, both of the precision of c_balance
+        if !selected(c) || c.c_balance.raw_value() * count <= sum.raw_value()
+                || ordered.contains(&(c.c_w_id, c.c_d_id, c.c_id)) {
+            continue;
+        }
+        let group = groups.find_or_insert_with(c.c_state.as_slice()[..1], |_| (0i64, zero(2)));
+        let (numcust, balance) = *group;
+        *group = (numcust + 1, balance + c.c_balance);
+    }
+    let result = groups.into_iter().map(|(country, (numcust, balance))| {
+        vec![TextValue(country.to_string()), numcust.to_value(), balance.to_value()]
+    }).collect();
+    order_by(result, [(0, false)])
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use dynamic::{Value, IntValue, NumericValue, TextValue};
+    use numeric::Numeric;
+    use table::row;
+    use tables::Tables;
+
+    use super::{query, average, divide};
+
+    fn numeric(value: i64, precision: uint) -> Value {
+        NumericValue(Numeric::new(value, 12, precision))
+    }
+
+    fn customer(c_id: i32, state: &str, phone: &str, balance: &str) -> String {
+        format!("{}|1|1|first|OE|LAST|street|street|city|{}|123456789|{}|2010-05-01|GC|\
+                 50000.00|0.1000|{}|10.00|1|0|data", c_id, state, phone, balance)
+    }
+
+    fn stock(s_i_id: i32, s_w_id: i32, order_cnt: i32) -> String {
+        format!("{}|{}|50|d|d|d|d|d|d|d|d|d|d|0|{}|0|data", s_i_id, s_w_id, order_cnt)
+    }
+
+    fn tables() -> Tables {
+        let mut t = Tables::new();
+        t.item_table.insert(row("1|1|item one|10.00|ends with b"));
+        t.item_table.insert(row("2|2|item two|20.00|plain"));
+        for line in ["1|1|1|1|1|1|2010-05-01|1|10.00|d", "1|1|1|2|1|1|2010-05-01|5|50.00|d",
+                     "2|1|1|1|2|1|2010-05-01|3|60.00|d", "2|1|1|2|1|1||9|90.00|d"].iter() {
+            t.orderline_table.insert(row(*line));
+        }
+        t.order_table.insert(row("1|1|1|1|2010-05-01||2|1"));
+
+        t.nation_table.insert(row("68|Germany|4|comment"));
+        t.nation_table.insert(row("70|France|4|comment"));
+        // stock (w_id, i_id) is supplied by w_id * i_id
+        for line in ["1|Supplier#1|address|68|phone|0.00|comment",
+                     "2|Supplier#2|address|70|phone|0.00|comment",
+                     "3|Supplier#3|address|68|phone|0.00|comment"].iter() {
+            t.supplier_table.insert(row(*line));
+        }
+        for line in [stock(1, 1, 10), stock(2, 1, 50), stock(3, 1, 0)].iter() {
+            t.stock_table.insert(row(line[]));
+        }
+
+        for line in [customer(1, "AB", "1000000000000000", "100.00"),
+                     customer(2, "AX", "2000000000000000", "300.00"),
+                     customer(3, "BC", "8000000000000000", "500.00"),
+                     customer(4, "BD", "3000000000000000", "-50.00"),
+                     customer(5, "C", "4000000000000000", "250.00")].iter() {
+            t.customer_table.insert(row(line[]));
+        }
+        t
+    }
+
+    #[test]
+    fn test_q1() {
+        // the undelivered line of order 2 is left out
+        assert_eq!(query(1, &tables()),
+                   vec![vec![IntValue(1), numeric(4, 0), numeric(7000, 2), numeric(200, 2),
+                             numeric(350000, 4), IntValue(2)],
+                        vec![IntValue(2), numeric(5, 0), numeric(5000, 2), numeric(500, 2),
+                             numeric(500000, 4), IntValue(1)]])
+    }
+
+    #[test]
+    fn test_divide_by_zero() {
+        let zero = Numeric::new(0, 12, 2);
+        assert_eq!(divide(1, zero, zero), None)
+        assert_eq!(divide(100, Numeric::new(150, 12, 2), Numeric::new(3, 1, 0)),
+                   Some(Numeric::new(500000, 12, 4)))
+        assert_eq!(average(zero, 0), None)
+    }
+
+    #[test]
+    fn test_q6() {
+        // including the line with a quantity of 1
+        assert_eq!(query(6, &tables()), vec![vec![numeric(12000, 2)]])
+    }
+
+    #[test]
+    fn test_q11() {
+        // item 3 of the German supplier 3 has no share in the orders
+        assert_eq!(query(11, &tables()), vec![vec![IntValue(1), numeric(10, 0)]])
+    }
+
+    #[test]
+    fn test_q17() {
+        // the item ending in b is ordered 1, 5 and 9 times, only the first line is below
+        // the average
+        assert_eq!(query(17, &tables()), vec![vec![numeric(5000, 3)]])
+    }
+
+    #[test]
+    fn test_q22() {
+        // the positive balances average 216.67, customer 1 has an order
+        assert_eq!(query(22, &tables()),
+                   vec![vec![TextValue("A".to_string()), IntValue(1), numeric(30000, 2)],
+                        vec![TextValue("C".to_string()), IntValue(1), numeric(25000, 2)]])
+    }
+}
@@ -0,0 +1,211 @@
+//! Generates the typed table module (`tables.rs`) from a schema catalog:
+//!
+//!     rust-code codegen schema.sql > src/tables.rs
+
+use std::collections::TreeSet;
+
+use super::catalog::{Catalog, Column, TableSchema, IntegerType, NumericType, CharType,
+                     VarcharType, TimestampType};
+
+
+pub fn generate(catalog: &Catalog, schema_file: &str) -> Result<String, String> {
+    for table in catalog.tables().iter() {
+        if table.primary_key.is_empty() {
+            return Err(format!("table {} has no primary key", table.name));
+        }
+    }
+
+    let columns: Vec<&Column> = catalog.tables().iter()
+        .flat_map(|table| table.columns.iter())
+        .collect();
+    let mut chars = TreeSet::new();
+    let mut varchars = TreeSet::new();
+    for column in columns.iter() {
+        match column.ty {
+            CharType(len) => { chars.insert(len); },
+            VarcharType(len) => { varchars.insert(len); },
+            _ => {}
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(format!("// Generated by `rust-code codegen {}`, do not edit.\n\n",
+                         schema_file)[]);
+    out.push_str("use super::column::ColumnTable;\n");
+    out.push_str("use super::format::Format;\n");
+    if columns.iter().any(|column| match column.ty { NumericType(..) => true, _ => false }) {
+        out.push_str("use super::numeric::Numeric;\n");
+    }
+    out.push_str("use super::query::{Database, Relation};\n");
+    out.push_str("use super::table::{bulk_load_if_exists, Table};\n");
+    if columns.iter().any(|column| column.ty == TimestampType) {
+        out.push_str("use super::timestamp::Timestamp;\n");
+    }
+    out.push_str("\n\n");
+
+    for len in chars.iter() {
+        out.push_str(format!("char_type!(Char{}, {})\n", len, len)[]);
+    }
+    if !chars.is_empty() && !varchars.is_empty() {
+        out.push_str("\n");
+    }
+    for len in varchars.iter() {
+        out.push_str(format!("varchar_type!(Varchar{}, {})\n", len, len)[]);
+    }
+    out.push_str("\n\n");
+
+    for table in catalog.tables().iter() {
+        row_struct(&mut out, table);
+    }
+    tables_struct(&mut out, catalog);
+    Ok(out)
+}
+
+fn row_struct(out: &mut String, table: &TableSchema) {
+    let name = struct_name(table);
+    let key: Vec<&str> = table.primary_key.iter().map(|&i| table.columns[i].name[]).collect();
+
+    out.push_str("#[deriving(Clone, Show)]\n");
+    out.push_str("#[deriving_from_row]\n");
+    out.push_str("#[deriving_to_row]\n");
+    out.push_str(format!("#[deriving_primary_key({})]\n", key.connect(", "))[]);
+    out.push_str("#[deriving_columns]\n");
+    out.push_str(format!("pub struct {} {{\n", name)[]);
+    for column in table.columns.iter() {
+        if let NumericType(len, precision) = column.ty {
+            out.push_str(format!("    #[numeric = \"{},{}\"]\n", len, precision)[]);
+        }
+        out.push_str(format!("    pub {}: {},\n", column.name, field_type(column))[]);
+    }
+    out.push_str("}\n\n");
+
+    let key_types: Vec<String> = table.primary_key.iter()
+        .map(|&i| field_type(&table.columns[i]))
+        .collect();
+    let key_type = if key_types.len() == 1 {
+        key_types[0].clone()
+    } else {
+        format!("({})", key_types.connect(", "))
+    };
+    out.push_str(format!("pub type  {}Table = Table<{}, {}>;\n", name, name, key_type)[]);
+    out.push_str(format!("pub type  {}ColumnTable = ColumnTable<{}, {}Columns, {}>;\n\n\n",
+                         name, name, name, key_type)[]);
+}
+
+fn tables_struct(out: &mut String, catalog: &Catalog) {
+    out.push_str("pub struct Tables {\n");
+    for table in catalog.tables().iter() {
+        out.push_str(format!("    pub {}_table: {}Table,\n", table.name, struct_name(table))[]);
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("impl Tables {\n");
+    out.push_str("    pub fn new() -> Tables {\n");
+    out.push_str("        Tables {\n");
+    for table in catalog.tables().iter() {
+        out.push_str(format!("            {}_table: Table::new(),\n", table.name)[]);
+    }
+    out.push_str("        }\n");
+    out.push_str("    }\n\n");
+    out.push_str("    /// Number of rows per table, in schema order.\n");
+    out.push_str("    pub fn sizes(&self) -> Vec<(&'static str, uint)> {\n");
+    out.push_str("        vec![\n");
+    for table in catalog.tables().iter() {
+        out.push_str(format!("            (\"{}\", self.{}_table.size()),\n", struct_name(table),
+                             table.name)[]);
+    }
+    out.push_str("        ]\n");
+    out.push_str("    }\n");
+    out.push_str("}\n\n");
+
+    out.push_str("impl Database for Tables {\n");
+    out.push_str("    fn relation(&self, name: &str) -> Option<&Relation> {\n");
+    out.push_str("        match name {\n");
+    for table in catalog.tables().iter() {
+        out.push_str(format!("            \"{}\" => Some(&self.{}_table as &Relation),\n",
+                             table.name, table.name)[]);
+    }
+    out.push_str("            _ => None\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n\n");
+
+    out.push_str("pub fn read_tables(tables: &mut Tables) {\n");
+    out.push_str("    read_tables_with(tables, &Format::tbl(), \"tbl\");\n");
+    out.push_str("}\n\n");
+
+    out.push_str("/// Like `read_tables`, for files in `format` ending in `.extension`. A\n");
+    out.push_str("/// missing file leaves its table empty.\n");
+    out.push_str("pub fn read_tables_with(tables: &mut Tables, format: &Format, \
+                  extension: &str) {\n");
+    for table in catalog.tables().iter() {
+        out.push_str(format!("    bulk_load_if_exists(format!(\"../data/tpcc_{}.{{}}\", \
+                              extension)[], format,\n", table.name)[]);
+        out.push_str(format!("                        &mut tables.{}_table);\n", table.name)[]);
+    }
+    out.push_str("}\n");
+}
+
+/// `order_line` becomes `OrderLine`.
+fn struct_name(table: &TableSchema) -> String {
+    let mut name = String::new();
+    for part in table.name[].split('_') {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_uppercase());
+            name.extend(chars);
+        }
+    }
+    name
+}
+
+fn field_type(column: &Column) -> String {
+    let ty = match column.ty {
+        IntegerType => "i32".to_string(),
+        NumericType(..) => "Numeric".to_string(),
+        CharType(len) => format!("Char{}", len),
+        VarcharType(len) => format!("Varchar{}", len),
+        TimestampType => "Timestamp".to_string(),
+    };
+    if column.nullable {
+        format!("Option<{}>", ty)
+    } else {
+        ty
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use ddl;
+
+    use super::generate;
+
+    #[test]
+    fn test_tables_rs_is_up_to_date() {
+        let catalog = ddl::parse(include_str!("../schema.sql")).unwrap();
+        assert_eq!(generate(&catalog, "schema.sql").unwrap()[], include_str!("tables.rs"))
+    }
+
+    #[test]
+    fn test_generate() {
+        let catalog = ddl::parse("create table new_order (no_id int primary key, \
+                                                        no_note char(4))").unwrap();
+        let code = generate(&catalog, "x.sql").unwrap();
+        assert!(code[].contains("char_type!(Char4, 4)\n"))
+        assert!(code[].contains("pub struct NewOrder {\n"))
+        assert!(code[].contains("    pub no_note: Option<Char4>,\n"))
+        assert!(code[].contains("pub type  NewOrderTable = Table<NewOrder, i32>;\n"))
+        assert!(code[].contains("    bulk_load_if_exists(format!(\"../data/tpcc_new_order.{}\", \
+                                 extension)[], format,\n                        \
+                                 &mut tables.new_order_table);\n"))
+        assert!(!code[].contains("Numeric"))
+    }
+
+    #[test]
+    fn test_no_primary_key() {
+        let catalog = ddl::parse("create table history (h_c_id int)").unwrap();
+        assert!(generate(&catalog, "x.sql").is_err())
+    }
+}
@@ -0,0 +1,210 @@
+//! Column store layout: one vector per attribute instead of one vector of
+//! rows, with dictionary encoding for strings.
+//!
+//! The per-table column structs and their `Columns` impls are generated by
+//! `#[deriving_columns]`, see the `table_derive` crate.
+
+use std::collections::HashMap;
+
+use collections::hash::Hash;
+
+use super::table::{read_tbl, FromRow, PrimaryKey};
+
+
+/// Storage of a single attribute.
+pub trait Column<T> {
+    fn new() -> Self;
+    fn push_value(&mut self, value: &T);
+    fn value(&self, pos: uint) -> T;
+    fn set_value(&mut self, pos: uint, value: &T);
+}
+
+impl<T: Clone> Column<T> for Vec<T> {
+    fn new() -> Vec<T> {
+        Vec::new()
+    }
+
+    fn push_value(&mut self, value: &T) {
+        self.push(value.clone());
+    }
+
+    fn value(&self, pos: uint) -> T {
+        self[pos].clone()
+    }
+
+    fn set_value(&mut self, pos: uint, value: &T) {
+        *self.get_mut(pos) = value.clone();
+    }
+}
+
+/// A column storing a code per row, each distinct value is stored once.
+pub struct Dictionary<T> {
+    values: Vec<T>,
+    codes: HashMap<T, u32>,
+    column: Vec<u32>,
+}
+
+impl<T: Hash + Eq + Clone> Dictionary<T> {
+    /// The code per row.
+    pub fn codes(&self) -> &[u32] {
+        self.column[]
+    }
+
+    pub fn decode(&self, code: u32) -> &T {
+        &self.values[code as uint]
+    }
+
+    /// The code of `value`, `None` if no row has that value (and so a scan for
+    /// it can stop right away).
+    pub fn encode(&self, value: &T) -> Option<u32> {
+        self.codes.find(value).map(|&code| code)
+    }
+
+    pub fn len(&self) -> uint {
+        self.column.len()
+    }
+
+    pub fn distinct_values(&self) -> uint {
+        self.values.len()
+    }
+
+    fn code_for(&mut self, value: &T) -> u32 {
+        if let Some(&code) = self.codes.find(value) {
+            return code;
+        }
+        let code = self.values.len() as u32;
+        self.values.push(value.clone());
+        self.codes.insert(value.clone(), code);
+        code
+    }
+}
+
+impl<T: Hash + Eq + Clone> Column<T> for Dictionary<T> {
+    fn new() -> Dictionary<T> {
+        Dictionary {
+            values: Vec::new(),
+            codes: HashMap::new(),
+            column: Vec::new(),
+        }
+    }
+
+    fn push_value(&mut self, value: &T) {
+        let code = self.code_for(value);
+        self.column.push(code);
+    }
+
+    fn value(&self, pos: uint) -> T {
+        self.decode(self.column[pos]).clone()
+    }
+
+    fn set_value(&mut self, pos: uint, value: &T) {
+        // values no row refers to any more stay in the dictionary
+        let code = self.code_for(value);
+        *self.column.get_mut(pos) = code;
+    }
+}
+
+/// All columns of a row type.
+pub trait Columns<Data> {
+    fn new() -> Self;
+    fn push(&mut self, row: &Data);
+    /// Reassembles the row at `pos`.
+    fn get(&self, pos: uint) -> Data;
+    fn set(&mut self, pos: uint, row: &Data);
+    fn len(&self) -> uint;
+}
+
+/// Column store counterpart of `table::Table`. Rows do not exist as such, so
+/// lookups return a copy of the row and updates go through `update`.
+pub struct ColumnTable<Data, Cols, Index> {
+    columns: Cols,
+    index: HashMap<Index, uint>,
+}
+
+impl<Data: PrimaryKey<Index>, Cols: Columns<Data>, Index: Eq + Hash>
+        ColumnTable<Data, Cols, Index> {
+    pub fn new() -> ColumnTable<Data, Cols, Index> {
+        ColumnTable {
+            columns: Columns::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, data: Data) {
+        self.columns.push(&data);
+        self.index.insert(data.primary_key(), self.columns.len() - 1);
+    }
+
+    pub fn lookup(&self, index_elem: Index) -> Data {
+        self.columns.get(self.index[index_elem])
+    }
+
+    /// Applies `f` to the row with the key `index_elem` and writes it back.
+    pub fn update(&mut self, index_elem: Index, f: |&mut Data|) {
+        let pos = self.index[index_elem];
+        let mut row = self.columns.get(pos);
+        f(&mut row);
+        self.columns.set(pos, &row);
+    }
+
+    pub fn size(&self) -> uint {
+        self.columns.len()
+    }
+
+    /// The individual columns, for scans touching only some attributes.
+    pub fn columns(&self) -> &Cols {
+        &self.columns
+    }
+
+    /// Scan reassembling whole rows.
+    pub fn scan<'a>(&'a self) -> Rows<'a, Data, Cols> {
+        Rows { columns: &self.columns, pos: 0 }
+    }
+}
+
+pub struct Rows<'a, Data, Cols: 'a> {
+    columns: &'a Cols,
+    pos: uint,
+}
+
+impl<'a, Data, Cols: Columns<Data>> Iterator<Data> for Rows<'a, Data, Cols> {
+    fn next(&mut self) -> Option<Data> {
+        if self.pos == self.columns.len() {
+            return None;
+        }
+        self.pos += 1;
+        Some(self.columns.get(self.pos - 1))
+    }
+}
+
+pub fn read_column_table<Data: FromRow + PrimaryKey<Index>, Cols: Columns<Data>,
+                         Index: Eq + Hash>
+        (filename: &str, table: &mut ColumnTable<Data, Cols, Index>) {
+    read_tbl(filename, |row| {
+        table.insert(try!(FromRow::from_row(row)));
+        Ok(())
+    });
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::{Column, Dictionary};
+
+    #[test]
+    fn test_dictionary() {
+        let mut column: Dictionary<String> = Column::new();
+        for s in ["a", "b", "a", "c", "a"].iter() {
+            column.push_value(&s.to_string());
+        }
+        assert_eq!(column.distinct_values(), 3)
+        assert_eq!(column.codes(), [0, 1, 0, 2, 0][])
+        assert_eq!(column.value(3), "c".to_string())
+        assert_eq!(column.encode(&"d".to_string()), None)
+
+        column.set_value(0, &"d".to_string());
+        assert_eq!(column.value(0), "d".to_string())
+        assert_eq!(column.value(2), "a".to_string())
+    }
+}
@@ -0,0 +1,312 @@
+//! The consistency conditions of TPC-C (clause 3.3.2), checked on `Tables`
+//! after loading and after running transactions.
+//!
+//! Conditions 8 to 11 involve the history table, which is not loaded, so
+//! they are not checked.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use numeric::Numeric;
+use tables::Tables;
+
+
+const MAX_SHOWN: uint = 10;
+
+/// A row or group of rows that violates a condition.
+pub struct Violation {
+    pub condition: uint,
+    pub key: String,
+    pub detail: String,
+}
+
+impl fmt::Show for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "condition {} violated for {}: {}", self.condition, self.key, self.detail)
+    }
+}
+
+/// Checks all conditions and returns the violations ordered by condition.
+pub fn check(tables: &Tables) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    warehouse_ytd(tables, &mut violations);
+    district_orders(tables, &mut violations);
+    order_lines(tables, &mut violations);
+    customer_balance(tables, &mut violations);
+    violations.sort_by(|a, b| a.condition.cmp(&b.condition));
+    violations
+}
+
+/// Prints the violations, at most `MAX_SHOWN` per condition.
+pub fn report(tables: &Tables, when: &str) {
+    let violations = check(tables);
+    println!("consistency {}: {} violations", when, violations.len());
+    let mut shown = HashMap::new();
+    for violation in violations.iter() {
+        let count = shown.find_or_insert_with(violation.condition, |_| 0u);
+        *count += 1;
+        if *count <= MAX_SHOWN {
+            println!("  {}", violation);
+        } else if *count == MAX_SHOWN + 1 {
+            println!("  ... more violations of condition {}", violation.condition);
+        }
+    }
+}
+
+fn equal(a: Numeric, b: Numeric) -> bool {
+    (a - b).raw_value() == 0
+}
+
+fn violation(violations: &mut Vec<Violation>, condition: uint, key: String, detail: String) {
+    violations.push(Violation { condition: condition, key: key, detail: detail });
+}
+
+/// 1: W_YTD = sum(D_YTD) of the districts of the warehouse.
+fn warehouse_ytd(tables: &Tables, violations: &mut Vec<Violation>) {
+    let mut sums = HashMap::new();
+    for district in tables.district_table.scan() {
+        let sum = sums.find_or_insert_with(district.d_w_id, |_| Numeric::new(0, 12, 2));
+        *sum = *sum + district.d_ytd;
+    }
+    for warehouse in tables.warehouse_table.scan() {
+        let sum = sums.find(&warehouse.w_id).map_or(Numeric::new(0, 12, 2), |&sum| sum);
+        if !equal(warehouse.w_ytd, sum) {
+            violation(violations, 1, format!("w_id {}", warehouse.w_id),
+                      format!("w_ytd {} != sum(d_ytd) {}", warehouse.w_ytd, sum));
+        }
+    }
+}
+
+/// The orders and new orders of a district.
+struct DistrictOrders {
+    max_o_id: Option<i32>,
+    ol_cnt_sum: i64,
+    ol_count: i64,
+    min_no_o_id: Option<i32>,
+    max_no_o_id: Option<i32>,
+    no_count: i64,
+}
+
+fn no_orders(_: &(i32, i32)) -> DistrictOrders {
+    DistrictOrders {
+        max_o_id: None, ol_cnt_sum: 0, ol_count: 0, min_no_o_id: None, max_no_o_id: None,
+        no_count: 0
+    }
+}
+
+/// 2: D_NEXT_O_ID - 1 = max(O_ID) = max(NO_O_ID),
+/// 3: max(NO_O_ID) - min(NO_O_ID) + 1 = count(*) of NEW-ORDER,
+/// 4: sum(O_OL_CNT) = count(*) of ORDER-LINE, all per district. A district
+/// without orders has to have D_NEXT_O_ID = 1.
+fn district_orders(tables: &Tables, violations: &mut Vec<Violation>) {
+    let mut districts = HashMap::new();
+    for order in tables.order_table.scan() {
+        let d = districts.find_or_insert_with((order.o_w_id, order.o_d_id), no_orders);
+        d.max_o_id = Some(d.max_o_id.map_or(order.o_id, |o_id| if order.o_id > o_id {
+            order.o_id
+        } else {
+            o_id
+        }));
+        d.ol_cnt_sum += order.o_ol_cnt.raw_value();
+    }
+    for no in tables.neworder_table.scan() {
+        let d = districts.find_or_insert_with((no.no_w_id, no.no_d_id), no_orders);
+        d.min_no_o_id = Some(d.min_no_o_id.map_or(no.no_o_id, |o_id| if no.no_o_id < o_id {
+            no.no_o_id
+        } else {
+            o_id
+        }));
+        d.max_no_o_id = Some(d.max_no_o_id.map_or(no.no_o_id, |o_id| if no.no_o_id > o_id {
+            no.no_o_id
+        } else {
+            o_id
+        }));
+        d.no_count += 1;
+    }
+    for ol in tables.orderline_table.scan() {
+        districts.find_or_insert_with((ol.ol_w_id, ol.ol_d_id), no_orders).ol_count += 1;
+    }
+
+    let none = no_orders(&(0, 0));
+    for district in tables.district_table.scan() {
+        let key = (district.d_w_id, district.d_id);
+        let d = districts.find(&key).unwrap_or(&none);
+        let name = format!("w_id {} d_id {}", district.d_w_id, district.d_id);
+        // order ids start at 1
+        let last = district.d_next_o_id - 1;
+        if d.max_o_id.unwrap_or(0) != last
+                || (d.max_no_o_id.is_some() && d.max_no_o_id != Some(last)) {
+            violation(violations, 2, name.clone(),
+                      format!("d_next_o_id - 1 = {}, max(o_id) = {}, max(no_o_id) = {}",
+                              district.d_next_o_id - 1, d.max_o_id, d.max_no_o_id));
+        }
+        if let (Some(min), Some(max)) = (d.min_no_o_id, d.max_no_o_id) {
+            if (max - min + 1) as i64 != d.no_count {
+                violation(violations, 3, name.clone(),
+                          format!("max(no_o_id) - min(no_o_id) + 1 = {}, count(*) = {}",
+                                  max - min + 1, d.no_count));
+            }
+        }
+        if d.ol_cnt_sum != d.ol_count {
+            violation(violations, 4, name,
+                      format!("sum(o_ol_cnt) = {}, count(*) of orderline = {}", d.ol_cnt_sum,
+                              d.ol_count));
+        }
+    }
+}
+
+/// 5: O_CARRIER_ID is null exactly for the orders with a NEW-ORDER row,
+/// 6: O_OL_CNT = count(*) of the ORDER-LINE rows of the order,
+/// 7: OL_DELIVERY_D is null exactly if O_CARRIER_ID of the order is null.
+fn order_lines(tables: &Tables, violations: &mut Vec<Violation>) {
+    let mut line_counts = HashMap::new();
+    let mut misdelivered = Vec::new();
+    for ol in tables.orderline_table.scan() {
+        let key = (ol.ol_w_id, ol.ol_d_id, ol.ol_o_id);
+        *line_counts.find_or_insert_with(key, |_| 0i64) += 1;
+        let carrier = tables.order_table.find(&key).and_then(|order| order.o_carrier_id);
+        if carrier.is_none() != ol.ol_delivery_d.is_none() {
+            misdelivered.push((key, ol.ol_number, carrier, ol.ol_delivery_d));
+        }
+    }
+
+    let mut orders: Vec<(i32, i32, i32)> = tables.order_table.scan()
+        .map(|order| (order.o_w_id, order.o_d_id, order.o_id))
+        .collect();
+    orders.sort();
+    for key in orders.iter() {
+        let order = tables.order_table.lookup(*key);
+        let (w_id, d_id, o_id) = *key;
+        let name = format!("w_id {} d_id {} o_id {}", w_id, d_id, o_id);
+        let new = tables.neworder_table.find(key).is_some();
+        if order.o_carrier_id.is_none() != new {
+            violation(violations, 5, name.clone(),
+                      format!("o_carrier_id {}, {} new order", order.o_carrier_id,
+                              if new { "a" } else { "no" }));
+        }
+        let lines = line_counts.find(key).map_or(0, |&count| count);
+        if order.o_ol_cnt.raw_value() != lines {
+            violation(violations, 6, name,
+                      format!("o_ol_cnt {}, {} orderlines", order.o_ol_cnt, lines));
+        }
+    }
+
+    misdelivered.sort();
+    for &((w_id, d_id, o_id), number, carrier, delivery) in misdelivered.iter() {
+        violation(violations, 7,
+                  format!("w_id {} d_id {} o_id {} ol_number {}", w_id, d_id, o_id, number),
+                  format!("ol_delivery_d {}, o_carrier_id {}", delivery, carrier));
+    }
+}
+
+/// 12: C_BALANCE + C_YTD_PAYMENT = sum(OL_AMOUNT) of the delivered ORDER-LINE
+/// rows of the customer's orders.
+fn customer_balance(tables: &Tables, violations: &mut Vec<Violation>) {
+    let mut delivered = HashMap::new();
+    for ol in tables.orderline_table.scan() {
+        if ol.ol_delivery_d.is_none() {
+            continue;
+        }
+        if let Some(order) = tables.order_table.find(&(ol.ol_w_id, ol.ol_d_id, ol.ol_o_id)) {
+            let sum = delivered.find_or_insert_with((order.o_w_id, order.o_d_id, order.o_c_id),
+                                                    |_| Numeric::new(0, 12, 2));
+            *sum = *sum + ol.ol_amount;
+        }
+    }
+
+    let mut customers: Vec<(i32, i32, i32)> = tables.customer_table.scan()
+        .map(|c| (c.c_w_id, c.c_d_id, c.c_id))
+        .collect();
+    customers.sort();
+    for key in customers.iter() {
+        let c = tables.customer_table.lookup(*key);
+        let sum = delivered.find(key).map_or(Numeric::new(0, 12, 2), |&sum| sum);
+        if !equal(c.c_balance + c.c_ytd_payment, sum) {
+            violation(violations, 12,
+                      format!("w_id {} d_id {} c_id {}", c.c_w_id, c.c_d_id, c.c_id),
+                      format!("c_balance {} + c_ytd_payment {} != sum(ol_amount) {}",
+                              c.c_balance, c.c_ytd_payment, sum));
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use numeric::Numeric;
+    use table::{row, fixture};
+    use tables::Tables;
+
+    use super::check;
+
+    fn violations(t: &Tables) -> Vec<(uint, String)> {
+        check(t).into_iter().map(|v| (v.condition, v.key)).collect()
+    }
+
+    fn keys(expected: &[(uint, &str)]) -> Vec<(uint, String)> {
+        expected.iter().map(|&(condition, key)| (condition, key.to_string())).collect()
+    }
+
+    #[test]
+    fn test_consistent() {
+        assert_eq!(violations(&fixture()), Vec::new())
+    }
+
+    #[test]
+    fn test_warehouse_ytd() {
+        let mut t = fixture();
+        t.warehouse_table.lookup_mut(1).w_ytd = Numeric::new(30100, 12, 2);
+        assert_eq!(violations(&t), keys([(1, "w_id 1")]))
+    }
+
+    #[test]
+    fn test_district_orders() {
+        let mut t = fixture();
+        t.district_table.lookup_mut((1, 1)).d_next_o_id = 4;
+        assert_eq!(violations(&t), keys([(2, "w_id 1 d_id 1")]))
+
+        // district 2 has no orders
+        let mut t = fixture();
+        t.district_table.lookup_mut((1, 2)).d_next_o_id = 3;
+        assert_eq!(violations(&t), keys([(2, "w_id 1 d_id 2")]))
+
+        // new orders 0 and 2 without 1
+        let mut t = fixture();
+        t.neworder_table.insert(row("0|1|1"));
+        assert_eq!(violations(&t), keys([(3, "w_id 1 d_id 1")]))
+
+        let mut t = fixture();
+        t.orderline_table.insert(row("1|1|1|2|1|1|2010-05-03|1|0.00|info"));
+        assert_eq!(violations(&t), keys([(4, "w_id 1 d_id 1"), (6, "w_id 1 d_id 1 o_id 1")]))
+    }
+
+    #[test]
+    fn test_order_lines() {
+        // a delivered order that is also new
+        let mut t = fixture();
+        t.neworder_table.insert(row("1|1|1"));
+        assert_eq!(violations(&t), keys([(5, "w_id 1 d_id 1 o_id 1")]))
+
+        let mut t = fixture();
+        t.order_table.lookup_mut((1, 1, 2)).o_ol_cnt = Numeric::new(2, 2, 0);
+        assert_eq!(violations(&t), keys([(4, "w_id 1 d_id 1"), (6, "w_id 1 d_id 1 o_id 2")]))
+
+        // a delivered line of the new order, with no amount to keep the balance
+        let mut t = fixture();
+        let delivery = t.orderline_table.lookup((1, 1, 1, 1)).ol_delivery_d;
+        {
+            let ol = t.orderline_table.lookup_mut((1, 1, 2, 1));
+            ol.ol_delivery_d = delivery;
+            ol.ol_amount = Numeric::new(0, 6, 2);
+        }
+        assert_eq!(violations(&t), keys([(7, "w_id 1 d_id 1 o_id 2 ol_number 1")]))
+    }
+
+    #[test]
+    fn test_customer_balance() {
+        let mut t = fixture();
+        t.customer_table.lookup_mut((1, 1, 1)).c_balance = Numeric::new(2500, 12, 2);
+        assert_eq!(violations(&t), keys([(12, "w_id 1 d_id 1 c_id 1")]))
+    }
+}
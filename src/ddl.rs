@@ -0,0 +1,190 @@
+//! Parser for the `CREATE TABLE`/`CREATE INDEX` subset of SQL needed to
+//! describe the TPC-C schema.
+
+use super::catalog::{Catalog, TableSchema, ColumnType, IntegerType, NumericType, CharType,
+                     VarcharType, TimestampType};
+use super::lexer::{Tokens, Comma, LParen, RParen, Semicolon};
+
+
+/// Parses a schema file into a new catalog.
+pub fn parse(input: &str) -> Result<Catalog, String> {
+    let mut catalog = Catalog::new();
+    try!(execute(&mut catalog, input));
+    Ok(catalog)
+}
+
+/// Applies the statements of `input` to `catalog`.
+pub fn execute(catalog: &mut Catalog, input: &str) -> Result<(), String> {
+    let mut tokens = try!(Tokens::new(input));
+    while !tokens.at_end() {
+        if tokens.eat(&Semicolon) {
+            continue;
+        }
+        try!(tokens.expect_keyword("create"));
+        if tokens.eat_keyword("table") {
+            try!(catalog.add_table(try!(create_table(&mut tokens))));
+        } else {
+            try!(create_index(catalog, &mut tokens));
+        }
+        if !tokens.at_end() {
+            try!(tokens.expect(&Semicolon));
+        }
+    }
+    Ok(())
+}
+
+/// `CREATE TABLE name (column type [NOT NULL] [PRIMARY KEY], ..., PRIMARY KEY (...))`
+fn create_table(tokens: &mut Tokens) -> Result<TableSchema, String> {
+    let mut table = TableSchema::new(try!(tokens.ident())[]);
+    try!(tokens.expect(&LParen));
+    loop {
+        if tokens.eat_keyword("primary") {
+            try!(tokens.expect_keyword("key"));
+            let columns = try!(tokens.ident_list());
+            let columns: Vec<&str> = columns.iter().map(|c| c[]).collect();
+            try!(table.set_primary_key(columns[]));
+        } else {
+            let name = try!(tokens.ident());
+            let ty = try!(column_type(tokens));
+            let mut nullable = true;
+            let mut primary_key = false;
+            loop {
+                if tokens.eat_keyword("not") {
+                    try!(tokens.expect_keyword("null"));
+                    nullable = false;
+                } else if tokens.eat_keyword("null") {
+                    nullable = true;
+                } else if tokens.eat_keyword("primary") {
+                    try!(tokens.expect_keyword("key"));
+                    nullable = false;
+                    primary_key = true;
+                } else {
+                    break;
+                }
+            }
+            try!(table.add_column(name[], ty, nullable));
+            if primary_key {
+                try!(table.set_primary_key([name[]]));
+            }
+        }
+
+        if !tokens.eat(&Comma) {
+            break;
+        }
+    }
+    try!(tokens.expect(&RParen));
+
+    // key columns are implicitly NOT NULL
+    for &i in table.primary_key.clone().iter() {
+        table.columns.get_mut(i).nullable = false;
+    }
+    Ok(table)
+}
+
+/// `CREATE [UNIQUE] INDEX name ON table (column, ...)`, the `CREATE` already
+/// being consumed.
+fn create_index(catalog: &mut Catalog, tokens: &mut Tokens) -> Result<(), String> {
+    let unique = tokens.eat_keyword("unique");
+    try!(tokens.expect_keyword("index"));
+    let name = try!(tokens.ident());
+    try!(tokens.expect_keyword("on"));
+    let table_name = try!(tokens.ident());
+    let columns = try!(tokens.ident_list());
+
+    let columns: Vec<&str> = columns.iter().map(|c| c[]).collect();
+    match catalog.table_mut(table_name[]) {
+        Some(table) => table.add_index(name[], columns[], unique),
+        None => Err(format!("relation {} does not exist", table_name))
+    }
+}
+
+fn column_type(tokens: &mut Tokens) -> Result<ColumnType, String> {
+    let name = try!(tokens.ident());
+    match name[] {
+        "integer" | "int" => Ok(IntegerType),
+        "timestamp" => Ok(TimestampType),
+        "numeric" | "decimal" => {
+            try!(tokens.expect(&LParen));
+            let len = try!(tokens.uint());
+            let precision = if tokens.eat(&Comma) { try!(tokens.uint()) } else { 0 };
+            try!(tokens.expect(&RParen));
+            if precision > len || len > 18 {
+                return Err(format!("unsupported precision numeric({},{})", len, precision));
+            }
+            Ok(NumericType(len, precision))
+        },
+        "char" | "varchar" => {
+            try!(tokens.expect(&LParen));
+            let len = try!(tokens.uint());
+            try!(tokens.expect(&RParen));
+            Ok(if name[] == "char" { CharType(len) } else { VarcharType(len) })
+        },
+        _ => Err(format!("type {} does not exist", name))
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use catalog::{ColumnType, IntegerType, NumericType, CharType, VarcharType, TimestampType};
+
+    use super::parse;
+
+    #[test]
+    fn test_create_table() {
+        let catalog = parse("
+            -- a comment
+            CREATE TABLE \"order\" (
+                o_id integer NOT NULL,
+                o_w_id INTEGER not null,
+                o_state char(2),
+                o_name varchar(16) NOT NULL,
+                o_total numeric(12, 2) NOT NULL,
+                o_entry_d timestamp,
+                PRIMARY KEY (o_w_id, o_id)
+            );
+            create unique index order_name on \"order\"(o_w_id, o_name);
+        ").unwrap();
+
+        let table = catalog.table("order").unwrap();
+        let types: Vec<(ColumnType, bool)> = table.columns.iter()
+            .map(|c| (c.ty.clone(), c.nullable))
+            .collect();
+        assert_eq!(types, vec![(IntegerType, false), (IntegerType, false), (CharType(2), true),
+                               (VarcharType(16), false), (NumericType(12, 2), false),
+                               (TimestampType, true)])
+        assert_eq!(table.primary_key, vec![1, 0])
+        assert_eq!(table.indexes[0].columns, vec![1, 3])
+        assert!(table.indexes[0].unique)
+    }
+
+    #[test]
+    fn test_inline_primary_key() {
+        let catalog = parse("create table item (i_id int primary key, i_name varchar(24))").unwrap();
+        let table = catalog.table("item").unwrap();
+        assert_eq!(table.primary_key, vec![0])
+        assert!(!table.columns[0].nullable)
+    }
+
+    #[test]
+    fn test_tpcc_schema() {
+        let catalog = parse(include_str!("../schema.sql")).unwrap();
+        assert_eq!(catalog.tables().len(), 11)
+        let orderline = catalog.table("orderline").unwrap();
+        assert_eq!(orderline.columns.len(), 10)
+        assert!(orderline.column("ol_delivery_d").unwrap().nullable)
+        assert_eq!(catalog.table("order").unwrap().indexes[0].name, "order_wdc".to_string())
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("create table t (a blob)").is_err())
+        assert!(parse("create table t (a int, a int)").is_err())
+        assert!(parse("create table t (a int, primary key (b))").is_err())
+        assert!(parse("create table t (a int) create table u (b int)").is_err())
+        assert!(parse("create index i on t (a)").is_err())
+        assert!(parse("create table t (a int); create table t (b int)").is_err())
+        assert!(parse("create table t (a numeric(2,4))").is_err())
+    }
+}
@@ -0,0 +1,158 @@
+//! Runs the NewOrder/Payment mix of TPC-C and records the latency of every
+//! transaction in a histogram per transaction type. While running it prints
+//! the throughput and latencies of every interval, which shows the warm-up,
+//! and at the end the percentiles and abort rate of the whole run.
+
+use std::rand::Rng;
+
+use time::precise_time_ns;
+
+use super::{urand, new_order_random, payment_random, NUM_WAREHOUSES};
+use histogram::Histogram;
+use tables::Tables;
+use timestamp::Timestamp;
+
+
+pub const NEW_ORDER: uint = 0;
+pub const PAYMENT: uint = 1;
+const NAMES: [&'static str, ..2] = ["neworder", "payment"];
+pub const PERCENTILES: [(f64, &'static str), ..4] =
+    [(50f64, "p50"), (90f64, "p90"), (99f64, "p99"), (99.9f64, "p99.9")];
+
+/// Runs a NewOrder or a Payment for a random warehouse, in their TPC-C ratio
+/// of 45:43, and returns which one it ran and whether it committed.
+pub fn random_transaction<R: Rng>(tables: &mut Tables, rng: &mut R) -> (uint, bool) {
+    let w_id = urand(1, NUM_WAREHOUSES, rng);
+    if urand(1, 88, rng) <= 45 {
+        (NEW_ORDER, new_order_random(tables, Timestamp::now(), w_id, rng))
+    } else {
+        payment_random(tables, w_id, rng);
+        (PAYMENT, true)
+    }
+}
+
+/// The latencies and aborts of one transaction type.
+pub struct Stats {
+    pub name: &'static str,
+    pub latencies: Histogram,
+    interval: Histogram,
+    pub aborts: u64,
+}
+
+impl Stats {
+    pub fn new(name: &'static str) -> Stats {
+        Stats { name: name, latencies: Histogram::new(), interval: Histogram::new(), aborts: 0 }
+    }
+
+    /// Records a transaction of the current interval.
+    pub fn record(&mut self, latency: u64, committed: bool) {
+        self.interval.record(latency);
+        if !committed {
+            self.aborts += 1;
+        }
+    }
+
+    /// The fraction of the transactions that rolled back, 0 if there were none.
+    pub fn abort_rate(&self) -> f64 {
+        let count = self.latencies.count();
+        if count == 0 { 0f64 } else { self.aborts as f64 / count as f64 }
+    }
+}
+
+/// What `run` measured.
+pub struct RunStats {
+    pub seconds: f64,
+    pub transactions: Vec<Stats>,
+}
+
+impl RunStats {
+    /// The number of transactions, committed or not.
+    pub fn count(&self) -> u64 {
+        self.transactions.iter().fold(0, |count, s| count + s.latencies.count())
+    }
+
+    /// Transactions per second, committed or not.
+    pub fn throughput(&self) -> f64 {
+        self.count() as f64 / self.seconds
+    }
+}
+
+/// Runs `transactions` transactions, printing a report every `interval`
+/// seconds.
+pub fn run<R: Rng>(tables: &mut Tables, rng: &mut R, transactions: uint, interval: f64)
+        -> RunStats {
+    let mut stats = new_stats();
+    let interval_ns = (interval * 1e9f64) as u64;
+    let start = precise_time_ns();
+    let mut interval_start = start;
+
+    print_interval_header();
+    for _ in range(0, transactions) {
+        let time = precise_time_ns();
+        let (kind, committed) = random_transaction(tables, rng);
+        let now = precise_time_ns();
+        stats.get_mut(kind).record(now - time, committed);
+        if now - interval_start >= interval_ns {
+            print_interval(stats.as_mut_slice(), now - start, now - interval_start);
+            interval_start = now;
+        }
+    }
+    let now = precise_time_ns();
+    print_interval(stats.as_mut_slice(), now - start, now - interval_start);
+
+    let result = RunStats { seconds: (now - start) as f64 / 1e9f64, transactions: stats };
+    print_summary(&result);
+    result
+}
+
+/// The statistics of each transaction type, indexed by `NEW_ORDER` and
+/// `PAYMENT`.
+pub fn new_stats() -> Vec<Stats> {
+    NAMES.iter().map(|&name| Stats::new(name)).collect()
+}
+
+/// Prints the throughput, and the latency percentiles and abort rate of
+/// each transaction type.
+pub fn print_summary(result: &RunStats) {
+    println!("{} transactions in {:.3}s, {:.0} per second", result.count(), result.seconds,
+             result.throughput());
+    let names: Vec<String> = PERCENTILES.iter().map(|&(_, name)| format!("{:>8}", name)).collect();
+    println!("{:<10} {:>9} {:>7}  {:>8} {} {:>8}  (us)", "", "count", "aborts", "mean",
+             names.connect(" "), "max");
+    for s in result.transactions.iter() {
+        let latencies = &s.latencies;
+        let percentiles: Vec<String> = PERCENTILES.iter()
+            .map(|&(p, _)| format!("{:>8.1}", micros(latencies.percentile(p))))
+            .collect();
+        println!("{:<10} {:>9} {:>6.2}%  {:>8.1} {} {:>8.1}", s.name, latencies.count(),
+                 100f64 * s.abort_rate(), latencies.mean() / 1e3f64, percentiles.connect(" "),
+                 micros(latencies.max()));
+    }
+}
+
+pub fn print_interval_header() {
+    println!("{:>8} {:>10}  latency in us (p50 / p99 / max)", "time", "txn/s");
+}
+
+/// Prints the throughput and latencies of the last interval and adds them
+/// to the totals.
+pub fn print_interval(stats: &mut [Stats], elapsed: u64, length: u64) {
+    let count = stats.iter().fold(0, |count, s| count + s.interval.count());
+    if count == 0 {
+        return;
+    }
+    let latencies: Vec<String> = stats.iter().map(|s| {
+        format!("{} {:.1} / {:.1} / {:.1}", s.name, micros(s.interval.percentile(50f64)),
+                micros(s.interval.percentile(99f64)), micros(s.interval.max()))
+    }).collect();
+    println!("{:>7.1}s {:>10.0}  {}", elapsed as f64 / 1e9f64,
+             count as f64 / (length as f64 / 1e9f64), latencies.connect("  "));
+    for s in stats.iter_mut() {
+        s.latencies.add(&s.interval);
+        s.interval.clear();
+    }
+}
+
+pub fn micros(nanos: u64) -> f64 {
+    nanos as f64 / 1e3f64
+}
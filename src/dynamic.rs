@@ -0,0 +1,266 @@
+//! Dynamically typed rows and tables, laid out according to a `TableSchema`
+//! known only at run time.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::slice::Items;
+
+use super::catalog::{TableSchema, Column, IntegerType, NumericType, CharType, VarcharType,
+                     TimestampType};
+use super::format::Format;
+use super::numeric::Numeric;
+use super::table::{read_records, RowError, MissingField, InvalidField};
+use super::timestamp::Timestamp;
+
+
+#[deriving(Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    NullValue,
+    IntValue(i64),
+    NumericValue(Numeric),
+    TextValue(String),
+    TimestampValue(Timestamp),
+}
+
+impl Value {
+    /// Parses a `.tbl` field for `column`. The empty field is NULL for nullable
+    /// columns and the empty string for text columns.
+    pub fn parse(column: &Column, field: &str) -> Result<Value, String> {
+        if field.is_empty() && column.nullable {
+            return Ok(NullValue);
+        }
+        match column.ty {
+            IntegerType => from_str(field).map(IntValue).ok_or("not an integer".to_string()),
+            NumericType(len, precision) => Numeric::from_str(field, len, precision)
+                .map(NumericValue)
+                .map_err(|e| e.to_string()),
+            CharType(len) | VarcharType(len) if field.len() > len =>
+                Err(format!("{} bytes exceed the column length of {}", field.len(), len)),
+            CharType(len) => {
+                let mut text = field.to_string();
+                text.grow(len - field.len(), ' ');
+                Ok(TextValue(text))
+            },
+            VarcharType(_) => Ok(TextValue(field.to_string())),
+            TimestampType => from_str(field).map(TimestampValue).ok_or("not a timestamp".to_string()),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == NullValue
+    }
+}
+
+impl fmt::Show for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NullValue => write!(f, "NULL"),
+            IntValue(i) => write!(f, "{}", i),
+            NumericValue(ref n) => write!(f, "{}", n),
+            TextValue(ref s) => write!(f, "{}", s),
+            TimestampValue(ref t) => write!(f, "{}", t),
+        }
+    }
+}
+
+pub type Row = Vec<Value>;
+
+/// Conversion of a typed field to a `Value`.
+pub trait ToValue {
+    fn to_value(&self) -> Value;
+}
+
+impl ToValue for i32 {
+    fn to_value(&self) -> Value {
+        IntValue(*self as i64)
+    }
+}
+
+impl ToValue for i64 {
+    fn to_value(&self) -> Value {
+        IntValue(*self)
+    }
+}
+
+impl ToValue for Numeric {
+    fn to_value(&self) -> Value {
+        NumericValue(*self)
+    }
+}
+
+impl ToValue for Timestamp {
+    fn to_value(&self) -> Value {
+        TimestampValue(*self)
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(&self) -> Value {
+        match *self {
+            Some(ref value) => value.to_value(),
+            None => NullValue
+        }
+    }
+}
+
+/// Conversion of a `Value` back to a typed field, `None` if the value has a
+/// different type or does not fit.
+pub trait FromValue {
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> Option<i32> {
+        match *value {
+            IntValue(i) => i.to_i32(),
+            _ => None
+        }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Option<i64> {
+        match *value {
+            IntValue(i) => Some(i),
+            _ => None
+        }
+    }
+}
+
+/// Conversion of a typed row to a dynamic one, with the fields in declaration
+/// (that is, schema) order. Implemented by `#[deriving_to_row]`, see the
+/// `table_derive` crate.
+pub trait ToRow {
+    fn to_row(&self) -> Row;
+}
+
+/// A row store like `table::Table`, with a hash index on the primary key
+/// (if the schema has one).
+pub struct DynTable {
+    schema: TableSchema,
+    rows: Vec<Row>,
+    index: HashMap<Row, uint>,
+}
+
+impl DynTable {
+    pub fn new(schema: TableSchema) -> DynTable {
+        DynTable {
+            schema: schema,
+            rows: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn schema(&self) -> &TableSchema {
+        &self.schema
+    }
+
+    pub fn parse_row<'a, I: Iterator<&'a str>>(&self, mut row: I) -> Result<Row, RowError> {
+        let mut result = Vec::with_capacity(self.schema.columns.len());
+        for column in self.schema.columns.iter() {
+            let field = match row.next() {
+                Some(field) => field,
+                None => return Err(MissingField(column.name.clone()))
+            };
+            result.push(try!(Value::parse(column, field).map_err(|reason| {
+                InvalidField(column.name.clone(), field.to_string(), reason)
+            })));
+        }
+        Ok(result)
+    }
+
+    pub fn load(&mut self, filename: &str) {
+        self.load_with(filename, &Format::tbl());
+    }
+
+    /// Like `load`, for a file in `format`, whose header names the columns of
+    /// the schema if it has one.
+    pub fn load_with(&mut self, filename: &str, format: &Format) {
+        let columns: Vec<String> = self.schema.columns.iter()
+            .map(|column| column.name.clone())
+            .collect();
+        let columns: Vec<&str> = columns.iter().map(|name| name[]).collect();
+        read_records(filename, format, columns[], |row| {
+            let row = try!(self.parse_row(row));
+            self.insert(row);
+            Ok(())
+        });
+    }
+
+    pub fn insert(&mut self, row: Row) {
+        assert_eq!(row.len(), self.schema.columns.len());
+        if !self.schema.primary_key.is_empty() {
+            self.index.insert(self.key_of(&row), self.rows.len());
+        }
+        self.rows.push(row);
+    }
+
+    /// `key` holds the primary key columns in key order.
+    pub fn lookup(&self, key: Row) -> &Row {
+        &self.rows[self.index[key]]
+    }
+
+    pub fn lookup_mut(&mut self, key: Row) -> &mut Row {
+        self.rows.get_mut(self.index[key])
+    }
+
+    /// Like `lookup`, but `None` for a key that does not exist.
+    pub fn find(&self, key: &Row) -> Option<&Row> {
+        self.index.find(key).map(|&pos| &self.rows[pos])
+    }
+
+    pub fn size(&self) -> uint {
+        self.rows.len()
+    }
+
+    pub fn scan(&self) -> Items<Row> {
+        self.rows.iter()
+    }
+
+    fn key_of(&self, row: &Row) -> Row {
+        self.schema.primary_key.iter().map(|&i| row[i].clone()).collect()
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use catalog::{TableSchema, IntegerType, NumericType, CharType, TimestampType};
+    use numeric::Numeric;
+    use table::InvalidField;
+
+    use super::{DynTable, IntValue, NumericValue, TextValue, NullValue};
+
+    fn orders() -> DynTable {
+        let mut schema = TableSchema::new("orders");
+        schema.add_column("o_id", IntegerType, false).unwrap();
+        schema.add_column("o_w_id", IntegerType, false).unwrap();
+        schema.add_column("o_state", CharType(2), false).unwrap();
+        schema.add_column("o_total", NumericType(6, 2), false).unwrap();
+        schema.add_column("o_delivery_d", TimestampType, true).unwrap();
+        schema.set_primary_key(["o_w_id", "o_id"]).unwrap();
+        DynTable::new(schema)
+    }
+
+    #[test]
+    fn test_insert_lookup() {
+        let mut table = orders();
+        let row = table.parse_row("1|5|A|12.5|".split('|')).unwrap();
+        assert_eq!(row, vec![IntValue(1), IntValue(5), TextValue("A ".to_string()),
+                             NumericValue(Numeric::new(1250, 6, 2)), NullValue])
+        table.insert(row.clone());
+        assert_eq!(table.size(), 1)
+        assert_eq!(table.lookup(vec![IntValue(5), IntValue(1)]), &row)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let table = orders();
+        assert_eq!(table.parse_row("1|5|ABC|12.5|".split('|')),
+                   Err(InvalidField("o_state".to_string(), "ABC".to_string(),
+                                    "3 bytes exceed the column length of 2".to_string())))
+        assert!(table.parse_row("1|5|A||".split('|')).is_err())
+        assert!(table.parse_row("1|5".split('|')).is_err())
+    }
+}
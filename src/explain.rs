@@ -0,0 +1,76 @@
+//! Description of a physical plan for `EXPLAIN`: an indented tree with an
+//! operator per line.
+//!
+//!     Sort on total  (rows=2)
+//!       ->  Hash Join on c.c_d_id = d.d_id  (rows=300)
+//!             ->  Seq Scan on district d  (rows=10)
+//!             ->  Seq Scan on customer c  (rows=30000)
+
+use std::rc::Rc;
+
+use super::query::Stats;
+
+
+pub struct PlanNode {
+    pub label: String,
+    /// Estimated number of output rows.
+    pub estimate: uint,
+    /// Actual rows and time, for `EXPLAIN ANALYZE`.
+    pub stats: Option<Rc<Stats>>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    /// The tree, a line per operator.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.format(0, &mut lines);
+        lines
+    }
+
+    fn format(&self, depth: uint, lines: &mut Vec<String>) {
+        let mut line = String::new();
+        if depth > 0 {
+            line.grow(6 * depth - 6, ' ');
+            line.push_str("  ->  ");
+        }
+        line.push_str(format!("{}  (rows={})", self.label, self.estimate)[]);
+        if let Some(ref stats) = self.stats {
+            line.push_str(format!(" (actual rows={} time={:.3}ms)", stats.rows.get(),
+                                  stats.nanos.get() as f64 / 1e6f64)[]);
+        }
+        lines.push(line);
+        for child in self.children.iter() {
+            child.format(depth + 1, lines);
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use query::Stats;
+
+    use super::PlanNode;
+
+    fn node(label: &str, estimate: uint, children: Vec<PlanNode>) -> PlanNode {
+        PlanNode { label: label.to_string(), estimate: estimate, stats: None, children: children }
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut plan = node("Hash Join on a = b", 10, vec![node("Seq Scan on x", 10, Vec::new()),
+                                                          node("Seq Scan on y", 5, Vec::new())]);
+        let stats = Rc::new(Stats::new());
+        stats.rows.set(7);
+        stats.nanos.set(1500000);
+        plan.stats = Some(stats);
+        assert_eq!(plan.lines(), vec![
+            "Hash Join on a = b  (rows=10) (actual rows=7 time=1.500ms)".to_string(),
+            "  ->  Seq Scan on x  (rows=10)".to_string(),
+            "  ->  Seq Scan on y  (rows=5)".to_string()])
+    }
+}
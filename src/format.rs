@@ -0,0 +1,261 @@
+//! Delimited text formats for loading tables: the `.tbl` files of dbgen, CSV
+//! with quoting as in RFC 4180, TSV or any other delimiter, optionally with a
+//! header naming the columns, comment lines and a delimiter ending every
+//! record.
+//!
+//! A `Parser` is given the lines of a file one at a time and splits them
+//! into records, whose `Fields` are passed to `FromRow::from_row` or
+//! `DynTable::parse_row` like the fields of a `.tbl` line.
+
+use std::slice::Items;
+
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct Format {
+    pub delimiter: char,
+    /// Quoted fields may contain the delimiter, line breaks and the quote
+    /// itself, doubled. Text after the closing quote is kept.
+    pub quote: Option<char>,
+    /// Whether the first record names the columns, which may then come in
+    /// any order and include columns that are not loaded.
+    pub header: bool,
+    /// Whether every record ends with a delimiter, as dbgen writes them.
+    pub trailing_delimiter: bool,
+    /// Lines starting with it are skipped, unless in a quoted field.
+    pub comment: Option<char>,
+}
+
+impl Format {
+    pub fn tbl() -> Format {
+        Format { delimiter: '|', quote: None, header: false, trailing_delimiter: false,
+                 comment: None }
+    }
+
+    pub fn csv() -> Format {
+        Format { delimiter: ',', quote: Some('"'), header: false, trailing_delimiter: false,
+                 comment: None }
+    }
+
+    pub fn tsv() -> Format {
+        Format { delimiter: '\t', quote: None, header: false, trailing_delimiter: false,
+                 comment: None }
+    }
+
+    /// `tbl`, `csv` or `tsv`.
+    pub fn named(name: &str) -> Option<Format> {
+        match name {
+            "tbl" => Some(Format::tbl()),
+            "csv" => Some(Format::csv()),
+            "tsv" => Some(Format::tsv()),
+            _ => None
+        }
+    }
+}
+
+#[deriving(PartialEq)]
+enum State {
+    /// No record has been started.
+    Between,
+    FieldStart,
+    Unquoted,
+    Quoted,
+    /// A quote in a quoted field, which ends it unless another one follows.
+    QuoteInQuoted,
+}
+
+/// Splits lines into the records of a `Format`.
+pub struct Parser {
+    format: Format,
+    state: State,
+    /// The unquoted fields of the current record and where each of them
+    /// starts and ends in `text`.
+    text: String,
+    bounds: Vec<(uint, uint)>,
+    /// The field of each column once the header was read.
+    order: Option<Vec<uint>>,
+}
+
+impl Parser {
+    pub fn new(format: Format) -> Parser {
+        Parser { format: format, state: Between, text: String::new(), bounds: Vec::new(),
+                 order: None }
+    }
+
+    /// Adds a line without its line break and returns whether it completes
+    /// a record. It does not if it is empty, a comment or ends in a quoted
+    /// field, which continues on the next line.
+    pub fn push(&mut self, line: &str) -> bool {
+        if self.state == Between {
+            if line.is_empty() {
+                return false;
+            }
+            if self.format.comment.is_some() && line.chars().next() == self.format.comment {
+                return false;
+            }
+            self.text.clear();
+            self.bounds.clear();
+            self.state = FieldStart;
+        } else {
+            self.text.push('\n');
+        }
+
+        let (delimiter, quote) = (self.format.delimiter, self.format.quote);
+        for c in line.chars() {
+            let state = self.state;
+            match state {
+                FieldStart | Unquoted | QuoteInQuoted if c == delimiter => self.end_field(),
+                FieldStart if Some(c) == quote => self.state = Quoted,
+                Quoted if Some(c) == quote => self.state = QuoteInQuoted,
+                QuoteInQuoted if Some(c) == quote => {
+                    self.text.push(c);
+                    self.state = Quoted;
+                },
+                Quoted => self.text.push(c),
+                _ => {
+                    self.text.push(c);
+                    self.state = Unquoted;
+                }
+            }
+        }
+        if self.state == Quoted {
+            return false;
+        }
+        self.end_field();
+        if self.format.trailing_delimiter && self.bounds.len() > 1 {
+            if let Some(&(start, end)) = self.bounds.last() {
+                if start == end {
+                    self.bounds.pop();
+                }
+            }
+        }
+        self.state = Between;
+        true
+    }
+
+    /// Whether a quoted field of the last line continues.
+    pub fn pending(&self) -> bool {
+        self.state != Between
+    }
+
+    /// Takes the last record as the header, after which `fields` returns
+    /// the fields of `columns` in that order. Names are compared ignoring
+    /// case.
+    pub fn set_header(&mut self, columns: &[&str]) -> Result<(), String> {
+        self.order = None;
+        let names: Vec<String> = self.fields().map(|name| lowercase(name)).collect();
+        let mut order = Vec::with_capacity(columns.len());
+        for column in columns.iter() {
+            let column = lowercase(*column);
+            match names.iter().position(|name| *name == column) {
+                Some(i) => order.push(i),
+                None => return Err(format!("the header has no column {}", column))
+            }
+        }
+        self.order = Some(order);
+        Ok(())
+    }
+
+    /// The fields of the last record.
+    pub fn fields(&self) -> Fields {
+        Fields {
+            text: self.text[], bounds: self.bounds[],
+            order: self.order.as_ref().map(|order| order.iter()), pos: 0
+        }
+    }
+
+    fn end_field(&mut self) {
+        let start = self.bounds.last().map_or(0, |&(_, end)| end);
+        self.bounds.push((start, self.text.len()));
+        self.state = FieldStart;
+    }
+}
+
+fn lowercase(name: &str) -> String {
+    name.trim().chars().map(|c| c.to_lowercase()).collect()
+}
+
+pub struct Fields<'a> {
+    text: &'a str,
+    bounds: &'a [(uint, uint)],
+    order: Option<Items<'a, uint>>,
+    pos: uint,
+}
+
+impl<'a> Iterator<&'a str> for Fields<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let i = match self.order {
+            Some(ref mut order) => match order.next() {
+                Some(&i) => i,
+                None => return None
+            },
+            None => {
+                self.pos += 1;
+                self.pos - 1
+            }
+        };
+        let text = self.text;
+        self.bounds.get(i).map(|&(start, end)| text[start..end])
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::{Format, Parser};
+
+    fn records(format: Format, lines: &[&str]) -> Vec<Vec<String>> {
+        let mut parser = Parser::new(format);
+        let mut records = Vec::new();
+        for line in lines.iter() {
+            if parser.push(*line) {
+                records.push(parser.fields().map(|field| field.to_string()).collect());
+            }
+        }
+        assert!(!parser.pending())
+        records
+    }
+
+    fn strings(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tbl() {
+        // the empty line is skipped
+        assert_eq!(records(Format::tbl(), ["1||a\"b|", ""]),
+                   vec![strings(["1", "", "a\"b", ""])])
+        let format = Format { trailing_delimiter: true, ..Format::tbl() };
+        assert_eq!(records(format, ["1||x|", "|"]), vec![strings(["1", "", "x"]), strings([""])])
+    }
+
+    #[test]
+    fn test_csv() {
+        let lines = ["1,\"a, \"\"b\"\"\",c", "\"multi", "line\",\"\"", "# not a comment"];
+        assert_eq!(records(Format::csv(), lines[]),
+                   vec![strings(["1", "a, \"b\"", "c"]), strings(["multi\nline", ""]),
+                        strings(["# not a comment"])])
+        let format = Format { comment: Some('#'), ..Format::csv() };
+        assert_eq!(records(format, lines[]).len(), 2)
+
+        let mut parser = Parser::new(Format::csv());
+        assert!(!parser.push("\"open"))
+        assert!(parser.pending())
+    }
+
+    #[test]
+    fn test_tsv() {
+        assert_eq!(records(Format::tsv(), ["a\t\"b\"\t"]), vec![strings(["a", "\"b\"", ""])])
+    }
+
+    #[test]
+    fn test_header() {
+        let mut parser = Parser::new(Format::csv());
+        assert!(parser.push("C_ID, c_name,c_extra"))
+        assert!(parser.set_header(["c_name", "c_id"]).is_ok())
+        assert!(parser.push("1,Smith,x"))
+        assert_eq!(parser.fields().collect::<Vec<&str>>(), vec!["Smith", "1"])
+        assert_eq!(parser.set_header(["c_w_id"]),
+                   Err("the header has no column c_w_id".to_string()))
+    }
+}
@@ -0,0 +1,148 @@
+//! A latency histogram in the style of HdrHistogram: values below
+//! `SUB_BUCKETS` are counted exactly, larger ones in buckets whose width
+//! doubles with every power of two, so every recorded value is off by less
+//! than 1/64 of itself while the whole `u64` range fits in a few thousand
+//! counters.
+
+use std::cmp::{max, min};
+use std::u64;
+
+
+const SUB_BUCKET_BITS: uint = 7;
+const SUB_BUCKETS: uint = 1 << SUB_BUCKET_BITS;
+const HALF: uint = SUB_BUCKETS / 2;
+const BUCKETS: uint = SUB_BUCKETS + (64 - SUB_BUCKET_BITS) * HALF;
+
+#[deriving(Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram { counts: Vec::from_elem(BUCKETS, 0), count: 0, sum: 0, min: u64::MAX, max: 0 }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        *self.counts.get_mut(index(value)) += 1;
+        self.count += 1;
+        self.sum += value;
+        self.min = min(self.min, value);
+        self.max = max(self.max, value);
+    }
+
+    /// Adds the values recorded in `other`.
+    pub fn add(&mut self, other: &Histogram) {
+        for (count, &other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = min(self.min, other.min);
+        self.max = max(self.max, other.max);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Histogram::new();
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 { 0f64 } else { self.sum as f64 / self.count as f64 }
+    }
+
+    /// The smallest value that `percentile` percent of the recorded values
+    /// are less than or equal to, up to the bucket width; 0 if there are none.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = max(1, (percentile / 100f64 * self.count as f64).ceil() as u64);
+        let mut seen = 0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return min(highest_value(i), self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// The bucket counting `value`: below `SUB_BUCKETS` the value itself, above
+/// it the top `SUB_BUCKET_BITS - 1` bits after the leading one, following
+/// the buckets of all smaller powers of two.
+fn index(value: u64) -> uint {
+    if value < SUB_BUCKETS as u64 {
+        return value as uint;
+    }
+    let bits = 64 - value.leading_zeros() as uint;
+    let shift = bits - SUB_BUCKET_BITS;
+    SUB_BUCKETS + (shift - 1) * HALF + (value >> shift) as uint - HALF
+}
+
+/// The largest value counted by bucket `index`.
+fn highest_value(index: uint) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = (index - SUB_BUCKETS) / HALF + 1;
+    let sub_bucket = ((index - SUB_BUCKETS) % HALF + HALF) as u64;
+    ((sub_bucket + 1) << shift) - 1
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::u64;
+
+    use super::{Histogram, index, highest_value, BUCKETS};
+
+    #[test]
+    fn test_index() {
+        assert_eq!(index(127), 127)
+        assert_eq!(index(128), 128)
+        assert_eq!(index(129), 128)
+        assert_eq!(index(255), 191)
+        assert_eq!(index(256), 192)
+        assert_eq!(index(u64::MAX), BUCKETS - 1)
+        for &value in [0u64, 100, 128, 1000, 123456789, 1 << 40].iter() {
+            assert!(highest_value(index(value)) >= value)
+            assert!(highest_value(index(value)) - value <= value / 64)
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50f64), 0)
+        for value in range(1u64, 10001) {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.count(), 10000)
+        assert_eq!(histogram.max(), 10000)
+        assert_eq!(histogram.percentile(100f64), 10000)
+        for &(percentile, exact) in [(50f64, 5000u64), (90f64, 9000), (99.9f64, 9990)].iter() {
+            let value = histogram.percentile(percentile);
+            assert!(value >= exact && value - exact <= exact / 64)
+        }
+
+        let mut other = Histogram::new();
+        other.record(1000000);
+        histogram.add(&other);
+        assert_eq!(histogram.max(), 1000000)
+        assert_eq!(histogram.percentile(100f64), 1000000)
+    }
+}
@@ -0,0 +1,102 @@
+//! Compares the row store (`table::Table`) with the column store
+//! (`column::ColumnTable`) on point accesses and on scans.
+
+use std::fmt::Show;
+use std::rand::{task_rng, Rng};
+
+use time::precise_time_ns;
+
+use column::{read_column_table, ColumnTable};
+use numeric::Numeric;
+use table::{read_table, PrimaryKey, Table};
+use tables::{OrderlineTable, OrderlineColumnTable, StockTable, StockColumnTable};
+
+
+const ORDERLINE_FILE: &'static str = "../data/tpcc_orderline.tbl";
+const STOCK_FILE: &'static str = "../data/tpcc_stock.tbl";
+const POINT_ACCESSES: uint = 1000000;
+
+pub fn compare_layouts() {
+    let mut orderline_rows: OrderlineTable = Table::new();
+    let mut orderline_columns: OrderlineColumnTable = ColumnTable::new();
+    let mut stock_rows: StockTable = Table::new();
+    let mut stock_columns: StockColumnTable = ColumnTable::new();
+
+    measure("load orderline", || read_table(ORDERLINE_FILE, &mut orderline_rows),
+            || read_column_table(ORDERLINE_FILE, &mut orderline_columns));
+    measure("load stock", || read_table(STOCK_FILE, &mut stock_rows),
+            || read_column_table(STOCK_FILE, &mut stock_columns));
+
+    let mut rng = task_rng();
+    let orderline_keys: Vec<(i32, i32, i32, i32)> =
+        orderline_rows.scan().map(|ol| ol.primary_key()).collect();
+    let orderline_sample: Vec<(i32, i32, i32, i32)> = range(0, POINT_ACCESSES)
+        .map(|_| orderline_keys[rng.gen_range(0, orderline_keys.len())])
+        .collect();
+    let stock_keys: Vec<(i32, i32)> = stock_rows.scan().map(|s| s.primary_key()).collect();
+    let stock_sample: Vec<(i32, i32)> = range(0, POINT_ACCESSES)
+        .map(|_| stock_keys[rng.gen_range(0, stock_keys.len())])
+        .collect();
+
+    // OLTP: random accesses to whole rows
+    let zero = Numeric::new(0, 12, 2);
+    measure("orderline lookups", || {
+        orderline_sample.iter().fold(zero, |sum, &key| sum + orderline_rows.lookup(key).ol_amount)
+    }, || {
+        orderline_sample.iter().fold(zero, |sum, &key| sum + orderline_columns.lookup(key).ol_amount)
+    });
+
+    let one = Numeric::new(1, 4, 0);
+    measure("stock updates", || {
+        for &key in stock_sample.iter() {
+            let stock = stock_rows.lookup_mut(key);
+            stock.s_order_cnt = stock.s_order_cnt + one;
+        }
+    }, || {
+        for &key in stock_sample.iter() {
+            stock_columns.update(key, |stock| stock.s_order_cnt = stock.s_order_cnt + one);
+        }
+    });
+
+    // OLAP: scans touching single attributes
+    measure("sum(ol_amount)", || {
+        orderline_rows.scan().fold(zero, |sum, ol| sum + ol.ol_amount)
+    }, || {
+        orderline_columns.columns().ol_amount.iter().fold(zero, |sum, amount| sum + *amount)
+    });
+
+    let needle = orderline_rows.scan().next().map(|ol| ol.ol_dist_info);
+    measure("count(ol_dist_info = x)", || {
+        orderline_rows.scan().filter(|ol| Some(ol.ol_dist_info) == needle).count()
+    }, || {
+        let dist_info = &orderline_columns.columns().ol_dist_info;
+        // compare codes instead of strings, a value not in the dictionary matches nothing
+        match needle.and_then(|needle| dist_info.encode(&needle)) {
+            Some(code) => dist_info.codes().iter().filter(|&&c| c == code).count(),
+            None => 0
+        }
+    });
+
+    let threshold = Numeric::new(20, 4, 0);
+    measure("count(s_quantity < 20)", || {
+        stock_rows.scan().filter(|s| s.s_quantity < threshold).count()
+    }, || {
+        stock_columns.columns().s_quantity.iter().filter(|q| **q < threshold).count()
+    });
+}
+
+/// Runs the row and the column store version of an operation, checks that
+/// they agree and prints how long each took.
+fn measure<T: PartialEq + Show>(name: &str, row: || -> T, column: || -> T) {
+    let time = precise_time_ns();
+    let row_result = row();
+    let row_time = precise_time_ns() - time;
+
+    let time = precise_time_ns();
+    let column_result = column();
+    let column_time = precise_time_ns() - time;
+
+    assert_eq!(row_result, column_result);
+    println!("{:<24} row {:>8.3}s  column {:>8.3}s", name, row_time as f64 / 1e9f64,
+             column_time as f64 / 1e9f64);
+}
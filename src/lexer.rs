@@ -0,0 +1,262 @@
+//! Tokenizer for the SQL dialect understood by the DDL parser, and a cursor
+//! over the resulting tokens for recursive descent parsers.
+
+use std::fmt;
+
+
+#[deriving(PartialEq, Clone)]
+pub enum Token {
+    /// Identifier or keyword, lower-cased unless it was double-quoted.
+    Ident(String),
+    /// A double-quoted identifier, which is never a keyword.
+    QuotedIdent(String),
+    /// The literal's text, parsed later according to where it is used.
+    Number(String),
+    StringLit(String),
+    Comma,
+    Dot,
+    Semicolon,
+    LParen,
+    RParen,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Equals,
+    NotEquals,
+    LessThan,
+    LessEquals,
+    GreaterThan,
+    GreaterEquals,
+}
+
+impl fmt::Show for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Ident(ref s) => write!(f, "{}", s),
+            QuotedIdent(ref s) => write!(f, "\"{}\"", s),
+            Number(ref s) => write!(f, "{}", s),
+            StringLit(ref s) => write!(f, "'{}'", s),
+            Comma => write!(f, ","),
+            Dot => write!(f, "."),
+            Semicolon => write!(f, ";"),
+            LParen => write!(f, "("),
+            RParen => write!(f, ")"),
+            Star => write!(f, "*"),
+            Plus => write!(f, "+"),
+            Minus => write!(f, "-"),
+            Slash => write!(f, "/"),
+            Equals => write!(f, "="),
+            NotEquals => write!(f, "<>"),
+            LessThan => write!(f, "<"),
+            LessEquals => write!(f, "<="),
+            GreaterThan => write!(f, ">"),
+            GreaterEquals => write!(f, ">="),
+        }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0u;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = if i + 1 < chars.len() { Some(chars[i + 1]) } else { None };
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                i += 1;
+            }
+            if i + 1 >= chars.len() {
+                return Err("unterminated comment".to_string());
+            }
+            i += 2;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().map(|c| c.to_lowercase()).collect();
+            tokens.push(Ident(word));
+        } else if c.is_digit() || (c == '.' && next.map_or(false, |c| c.is_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Number(String::from_chars(chars[start..i])));
+        } else if c == '\'' || c == '"' {
+            // both quote styles escape the quote character by doubling it
+            let mut text = String::new();
+            i += 1;
+            loop {
+                if i >= chars.len() {
+                    return Err(format!("unterminated quoted string starting with {}", c));
+                }
+                if chars[i] == c {
+                    if i + 1 < chars.len() && chars[i + 1] == c {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                text.push(chars[i]);
+                i += 1;
+            }
+            i += 1;
+            tokens.push(if c == '\'' { StringLit(text) } else { QuotedIdent(text) });
+        } else {
+            let (token, len) = match (c, next) {
+                ('<', Some('=')) => (LessEquals, 2),
+                ('<', Some('>')) => (NotEquals, 2),
+                ('!', Some('=')) => (NotEquals, 2),
+                ('>', Some('=')) => (GreaterEquals, 2),
+                ('<', _) => (LessThan, 1),
+                ('>', _) => (GreaterThan, 1),
+                ('=', _) => (Equals, 1),
+                (',', _) => (Comma, 1),
+                ('.', _) => (Dot, 1),
+                (';', _) => (Semicolon, 1),
+                ('(', _) => (LParen, 1),
+                (')', _) => (RParen, 1),
+                ('*', _) => (Star, 1),
+                ('+', _) => (Plus, 1),
+                ('-', _) => (Minus, 1),
+                ('/', _) => (Slash, 1),
+                _ => return Err(format!("unexpected character {}", c))
+            };
+            tokens.push(token);
+            i += len;
+        }
+    }
+
+    Ok(tokens)
+}
+
+
+/// Cursor over a token list with the usual helpers of a hand-written parser.
+/// Keywords are matched case-insensitively since identifiers are lower-cased.
+pub struct Tokens {
+    tokens: Vec<Token>,
+    pos: uint,
+}
+
+impl Tokens {
+    pub fn new(input: &str) -> Result<Tokens, String> {
+        Ok(Tokens { tokens: try!(tokenize(input)), pos: 0 })
+    }
+
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens[].get(self.pos)
+    }
+
+    pub fn next(&mut self) -> Option<Token> {
+        let token = self.tokens[].get(self.pos).map(|t| t.clone());
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// Consumes `token` if it comes next.
+    pub fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expect(&mut self, token: &Token) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(format!("{}", token)[]))
+        }
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(&Ident(ref word)) => word[] == keyword,
+            _ => false
+        }
+    }
+
+    /// Consumes `keyword` if it comes next.
+    pub fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    pub fn ident(&mut self) -> Result<String, String> {
+        let word = match self.peek() {
+            Some(&Ident(ref word)) | Some(&QuotedIdent(ref word)) => Some(word.clone()),
+            _ => None
+        };
+        match word {
+            Some(word) => {
+                self.pos += 1;
+                Ok(word)
+            },
+            None => Err(self.unexpected("identifier"))
+        }
+    }
+
+    /// `ident (',' ident)*` in parentheses.
+    pub fn ident_list(&mut self) -> Result<Vec<String>, String> {
+        try!(self.expect(&LParen));
+        let mut idents = vec![try!(self.ident())];
+        while self.eat(&Comma) {
+            idents.push(try!(self.ident()));
+        }
+        try!(self.expect(&RParen));
+        Ok(idents)
+    }
+
+    pub fn uint(&mut self) -> Result<uint, String> {
+        let value = match self.peek() {
+            Some(&Number(ref text)) => from_str(text[]),
+            _ => None
+        };
+        match value {
+            Some(value) => {
+                self.pos += 1;
+                Ok(value)
+            },
+            None => Err(self.unexpected("unsigned integer"))
+        }
+    }
+
+    /// Error message for when `expected` was expected but something else came.
+    pub fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(token) => format!("syntax error at {}: expected {}", token, expected),
+            None => format!("syntax error at end of input: expected {}", expected)
+        }
+    }
+}
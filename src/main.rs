@@ -5,27 +5,64 @@
 
 
 extern crate collections;
+extern crate libc;
 extern crate rand;
 extern crate time;
 
 #[phase(plugin, link)]
 extern crate log;
+#[phase(plugin)]
+extern crate table_derive;
 
 
-use std::io::{BufferedReader, File};
+use std::cmp::min;
+use std::io::{stdin, File};
 use std::num::abs;
+use std::rand::{SeedableRng, StdRng};
 
-use collections::hash::Hash;
 use time::precise_time_ns;
 
+use catalog::Catalog;
+use dynamic::DynTable;
+use format::Format;
 use numeric::Numeric;
+use report::BenchmarkResult;
 use tables::*;
-use table::{FromRow, PrimaryKey, Table};
-
-
+use timestamp::Timestamp;
+
+
+mod backends;
+mod catalog;
+mod chbench;
+#[macro_escape]
+mod chars;
+mod codegen;
+mod column;
+mod consistency;
+mod ddl;
+mod driver;
+mod dynamic;
+mod explain;
+mod format;
+mod histogram;
+mod layout;
+mod lexer;
+mod null;
 mod numeric;
+mod pgwire;
+mod pipeline;
+mod planner;
+mod query;
+mod repl;
+mod report;
+mod rpc;
+mod rte;
+mod snapshot;
+mod sql;
 mod table;
 mod tables;
+mod timestamp;
+mod vector;
 
 
 fn urand<Rng: std::rand::Rng>(min: i32, max: i32, rng: &mut Rng) -> i32 {
@@ -50,60 +87,18 @@ fn urandexcept<Rng: std::rand::Rng>(min: i32, max: i32, v: i32, rng: &mut Rng) -
 }
 
 
-struct Tables {
-    warehouse_table: WarehouseTable,
-    district_table: DistrictTable,
-    customer_table: CustomerTable,
-    neworder_table: NeworderTable,
-    order_table: OrderTable,
-    orderline_table: OrderlineTable,
-    item_table: ItemTable,
-    stock_table: StockTable,
-}
+const NUM_WAREHOUSES: i32 = 5;
 
-impl Tables {
-    fn new() -> Tables {
-        Tables {
-            warehouse_table: Table::new(),
-            district_table: Table::new(),
-            customer_table: Table::new(),
-            neworder_table: Table::new(),
-            order_table: Table::new(),
-            orderline_table: Table::new(),
-            item_table: Table::new(),
-            stock_table: Table::new(),
+/// Returns whether the transaction committed: an unused item id rolls it
+/// back, which is checked before changing anything.
+fn new_order(tables: &mut Tables, w_id: i32, d_id: i32, c_id: i32, ol_cnt: i32,
+             supware: &[i32], itemid: &[i32], qty: &[i32], now: Timestamp) -> bool {
+    for i in range(0, ol_cnt as uint) {
+        if tables.item_table.find(&itemid[i]).is_none() {
+            return false;
         }
     }
-}
-
-
-fn read_table<Data: Clone + FromRow + PrimaryKey<Index>, Index: Eq + Hash>
-        (filename: &str, table: &mut Table<Data, Index>) {
-    let path = Path::new(filename);
-    let mut file = BufferedReader::new(File::open(&path));
-    for line in file.lines() {
-        let row = FromRow::from_row(line.unwrap()[].trim_chars('\n').split('|'));
-        table.insert(row);
-    }
-}
-
-fn read_tables(tables: &mut Tables) {
-    read_table::<Warehouse, i32>("../data/tpcc_warehouse.tbl", &mut tables.warehouse_table);
-    read_table::<District, (i32, i32)>("../data/tpcc_district.tbl", &mut tables.district_table);
-    read_table::<Customer, (i32, i32, i32)>("../data/tpcc_customer.tbl", &mut tables.customer_table);
-    read_table::<Neworder, (i32, i32, i32)>("../data/tpcc_neworder.tbl", &mut tables.neworder_table);
-    read_table::<Order, (i32, i32, i32)>("../data/tpcc_order.tbl", &mut tables.order_table);
-    read_table::<Orderline, (i32, i32, i32, i32)>("../data/tpcc_orderline.tbl", &mut tables.orderline_table);
-    read_table::<Item, i32>("../data/tpcc_item.tbl", &mut tables.item_table);
-    read_table::<Stock, (i32, i32)>("../data/tpcc_stock.tbl", &mut tables.stock_table);
-}
 
-
-
-const NUM_WAREHOUSES: i32 = 5;
-
-fn new_order(tables: &mut Tables, w_id: i32, d_id: i32, c_id: i32, ol_cnt: i32,
-             supware: &[i32], itemid: &[i32], qty: &[i32], now: i64) {
     let w_tax = tables.warehouse_table.lookup(w_id).w_tax;
     let c_discount = tables.customer_table.lookup((w_id, d_id, c_id)).c_discount;
     let district = tables.district_table.lookup_mut((w_id, d_id));
@@ -121,7 +116,7 @@ fn new_order(tables: &mut Tables, w_id: i32, d_id: i32, c_id: i32, ol_cnt: i32,
 
     tables.order_table.insert(Order {
         o_id: o_id, o_d_id: d_id, o_w_id: w_id, o_c_id: c_id, o_entry_d: now,
-        o_carrier_id: 0, o_ol_cnt: Numeric::new(ol_cnt as i64, 1, 0),
+        o_carrier_id: None, o_ol_cnt: Numeric::new(ol_cnt as i64, 1, 0),
         o_all_local: Numeric::new(all_local, 1, 0)
     });
     tables.neworder_table.insert(Neworder { no_o_id: o_id, no_d_id: d_id, no_w_id: w_id });
@@ -160,66 +155,393 @@ fn new_order(tables: &mut Tables, w_id: i32, d_id: i32, c_id: i32, ol_cnt: i32,
             stock.s_order_cnt = s_order_cnt + Numeric::new(1, 4, 0);
         }
 
-        let ol_amount = qty * i_price * (Numeric::new(1, 1, 0) + w_tax + d_tax) *
-            (Numeric::new(1, 1, 0) - c_discount);
+        let ol_amount = (qty * i_price * (Numeric::new(1, 1, 0) + w_tax + d_tax) *
+            (Numeric::new(1, 1, 0) - c_discount)).round(6, 2);
         tables.orderline_table.insert(Orderline {
             ol_o_id: o_id, ol_d_id: d_id, ol_w_id: w_id, ol_number: i as i32 + 1, ol_i_id: itemid[i],
-            ol_supply_w_id: supware[i], ol_delivery_d: 0, ol_quantity: qty, ol_amount: ol_amount,
-            ol_dist_info: s_dist.clone()
+            ol_supply_w_id: supware[i], ol_delivery_d: None, ol_quantity: qty,
+            ol_amount: ol_amount, ol_dist_info: s_dist.clone()
         })
     }
+    true
 }
 
-fn new_order_random<Rng: std::rand::Rng>(tables: &mut Tables, now: i64, w_id: i32, rng: &mut Rng) {
+fn new_order_random<Rng: std::rand::Rng>(tables: &mut Tables, now: Timestamp, w_id: i32,
+                                         rng: &mut Rng) -> bool {
     let d_id = urand(1, 1, rng);
-    let c_id = nurand(1023, 1, 3000, rng);
-    let ol_cnt = urand(5, 15, rng);
-
-    let mut supware = [0i32, ..15];
-    let mut itemid = [0i32, ..15];
-    let mut qty = [0i32, ..15];
+    let input = rpc::random_new_order(w_id, d_id, rng);
+    new_order(tables, w_id, d_id, input.c_id, input.itemid.len() as i32, input.supware[],
+              input.itemid[], input.qty[], now)
+}
 
-    for i in range(0, ol_cnt as uint) {
-        supware[i] = if urand(1, 100, rng) > 1 {
-            w_id
-        } else {
-            urandexcept(1, NUM_WAREHOUSES, w_id, rng)
-        };
-        itemid[i] = nurand(8191, 1, 100000, rng);
-        qty[i] = urand(1, 10, rng);
+fn payment(tables: &mut Tables, w_id: i32, d_id: i32, c_w_id: i32, c_d_id: i32, c_id: i32,
+           h_amount: Numeric) {
+    let warehouse = tables.warehouse_table.lookup_mut(w_id);
+    warehouse.w_ytd = warehouse.w_ytd + h_amount;
+    let district = tables.district_table.lookup_mut((w_id, d_id));
+    district.d_ytd = district.d_ytd + h_amount;
+
+    let customer = tables.customer_table.lookup_mut((c_w_id, c_d_id, c_id));
+    customer.c_balance = customer.c_balance - h_amount;
+    customer.c_ytd_payment = customer.c_ytd_payment + h_amount;
+    customer.c_payment_cnt = customer.c_payment_cnt + Numeric::new(1, 4, 0);
+    if customer.c_credit.as_slice() == "BC" {
+        let c_data = format!("{} {} {} {} {} {}|{}", c_id, c_d_id, c_w_id, d_id, w_id, h_amount,
+                             customer.c_data);
+        // at most 500 bytes, without splitting a character
+        let mut end = min(c_data.len(), 500);
+        while !c_data.as_slice().is_char_boundary(end) {
+            end -= 1;
+        }
+        customer.c_data = Varchar500::new(c_data[..end]).unwrap();
     }
+    // there is no history table to insert into
+}
 
-    new_order(tables, w_id, d_id, c_id, ol_cnt, supware, itemid, qty, now)
+fn payment_random<Rng: std::rand::Rng>(tables: &mut Tables, w_id: i32, rng: &mut Rng) {
+    let d_id = urand(1, 1, rng);
+    let input = rpc::random_payment(w_id, d_id, rng);
+    payment(tables, w_id, d_id, input.c_w_id, input.c_d_id, input.c_id,
+            Numeric::new(input.h_amount, 6, 2))
 }
 
 fn print_tables(tables: &Tables) {
-    println!("Warehouse: {}", tables.warehouse_table.size());
-    println!("District: {}", tables.district_table.size());
-    println!("Customer: {}", tables.customer_table.size());
-    println!("Neworder: {}", tables.neworder_table.size());
-    println!("Order: {}", tables.order_table.size());
-    println!("Orderline: {}", tables.orderline_table.size());
-    println!("Item: {}", tables.item_table.size());
-    println!("Stock: {}\n", tables.stock_table.size());
+    for &(name, size) in tables.sizes().iter() {
+        println!("{}: {}", name, size);
+    }
+    println!("");
 }
 
-fn main() {
+fn read_schema(schema_file: &str) -> Catalog {
+    let schema = match File::open(&Path::new(schema_file)).read_to_string() {
+        Ok(schema) => schema,
+        Err(e) => fail!("{}: {}", schema_file, e)
+    };
+    match ddl::parse(schema[]) {
+        Ok(catalog) => catalog,
+        Err(e) => fail!("{}: {}", schema_file, e)
+    }
+}
+
+/// Loads all tables of `schema_file` as dynamically typed tables from the
+/// files with `extension`, skipping the missing ones.
+fn load_dynamic(schema_file: &str, format: &Format, extension: &str) {
+    let catalog = read_schema(schema_file);
     let time = precise_time_ns();
+    for schema in catalog.tables().iter() {
+        let filename = format!("../data/tpcc_{}.{}", schema.name, extension);
+        if !Path::new(filename[]).exists() {
+            println!("{}: not found, the table stays empty", filename);
+            continue;
+        }
+        let mut table = DynTable::new(schema.clone());
+        table.load_with(filename[], format);
+        println!("{}: {}", schema.name, table.size());
+    }
+    println!("insert {}s", ((precise_time_ns() - time) as f64) / 1e9f64);
+}
 
-    let tables: &mut Tables =  &mut Tables::new();
-    read_tables(tables);
+/// Prints the `tables.rs` for `schema_file`.
+fn generate_tables(schema_file: &str) {
+    match codegen::generate(&read_schema(schema_file), schema_file) {
+        Ok(code) => print!("{}", code),
+        Err(e) => fail!("{}: {}", schema_file, e)
+    }
+}
 
-    println!("insert {}s", ((precise_time_ns() - time) as f64) / 1e9f64);
-    print_tables(tables);
+/// Loads the tables and answers the `SELECT` statements read from stdin, one
+/// per line.
+fn run_queries(schema_file: &str) {
+    let catalog = read_schema(schema_file);
+    let mut tables = Tables::new();
+    read_tables(&mut tables);
+
+    for line in stdin().lines() {
+        let line = line.unwrap();
+        if line[].trim().is_empty() {
+            continue;
+        }
+        let time = precise_time_ns();
+        match planner::query(&catalog, &tables, line[]) {
+            Ok((columns, rows)) => {
+                println!("{}", columns.connect("|"));
+                for row in rows.iter() {
+                    let fields: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+                    println!("{}", fields.connect("|"));
+                }
+                println!("({} rows, {}s)\n", rows.len(),
+                         ((precise_time_ns() - time) as f64) / 1e9f64);
+            },
+            Err(e) => println!("ERROR: {}\n", e)
+        }
+    }
+}
+
+/// The options of the benchmark run, given as `--name value` pairs.
+struct Options {
+    transactions: uint,
+    /// Whether to check the consistency conditions after loading and after
+    /// the run, the only option without a value.
+    check: bool,
+    /// Whether to run the terminals of `rte` for `duration` seconds instead of
+    /// `transactions` transactions back to back, without a value as well.
+    rte: bool,
+    duration: uint,
+    speedup: uint,
+    seed: uint,
+    /// Files to write the result to.
+    json: Option<String>,
+    csv: Option<String>,
+    /// The format of the files to load and their extension, given as for
+    /// `parse_format`.
+    format: Format,
+    extension: String,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        transactions: 1000000, check: false, rte: false, duration: 7200, speedup: 1,
+        seed: std::rand::random(), json: None, csv: None, format: Format::tbl(),
+        extension: "tbl".to_string()
+    };
+    let mut format_args = Vec::new();
+    let mut args = args.iter();
+    loop {
+        let name = match args.next() {
+            Some(name) => name[],
+            None => {
+                let (format, extension) = try!(parse_format(format_args[]));
+                options.format = format;
+                options.extension = extension;
+                return Ok(options);
+            }
+        };
+        if name == "--check" {
+            options.check = true;
+            continue;
+        }
+        if name == "--rte" {
+            options.rte = true;
+            continue;
+        }
+        if name == "--header" || name == "--trailing-delimiter" {
+            format_args.push(name.to_string());
+            continue;
+        }
+        let value = match args.next() {
+            Some(value) => value[],
+            None => return Err(format!("{} needs a value", name))
+        };
+        let number = from_str::<uint>(value);
+        match (name, number) {
+            ("--transactions", Some(number)) => options.transactions = number,
+            ("--seed", Some(number)) => options.seed = number,
+            ("--duration", Some(number)) => options.duration = number,
+            ("--speedup", Some(number)) if number > 0 => options.speedup = number,
+            ("--transactions", _) | ("--seed", _) | ("--duration", _) | ("--speedup", _) =>
+                return Err(format!("{} needs a number, not {}", name, value)),
+            ("--json", _) => options.json = Some(value.to_string()),
+            ("--csv", _) => options.csv = Some(value.to_string()),
+            ("--format", _) | ("--delimiter", _) | ("--comment", _) => {
+                format_args.push(name.to_string());
+                format_args.push(value.to_string());
+            },
+            _ => return Err(format!("unknown option {}", name))
+        }
+    }
+}
+
+/// The format of the files to load and their extension, given as
+/// `--format tbl|csv|tsv`, which is also the extension, `--delimiter c`,
+/// `--comment c`, `--header` and `--trailing-delimiter`.
+fn parse_format(args: &[String]) -> Result<(Format, String), String> {
+    let mut format = Format::tbl();
+    let mut extension = "tbl".to_string();
+    let mut args = args.iter();
+    loop {
+        let name = match args.next() {
+            Some(name) => name[],
+            None => return Ok((format, extension))
+        };
+        match name {
+            "--header" => {
+                format.header = true;
+                continue;
+            },
+            "--trailing-delimiter" => {
+                format.trailing_delimiter = true;
+                continue;
+            },
+            _ => {}
+        }
+        let value = match args.next() {
+            Some(value) => value[],
+            None => return Err(format!("{} needs a value", name))
+        };
+        let character = match value {
+            "\\t" => Some('\t'),
+            _ if value.char_len() == 1 => value.chars().next(),
+            _ => None
+        };
+        match (name, character) {
+            ("--format", _) => {
+                let named = match Format::named(value) {
+                    Some(named) => named,
+                    None => return Err(format!("unknown format {}, use tbl, csv or tsv", value))
+                };
+                format = Format { header: format.header,
+                                  trailing_delimiter: format.trailing_delimiter, ..named };
+                extension = value.to_string();
+            },
+            ("--delimiter", Some(c)) => format.delimiter = c,
+            ("--comment", Some(c)) => format.comment = Some(c),
+            ("--delimiter", None) | ("--comment", None) =>
+                return Err(format!("{} needs a single character, not {}", name, value)),
+            _ => return Err(format!("unknown option {}", name))
+        }
+    }
+}
+
+fn main() {
+    let args = std::os::args();
+    if args.len() >= 3 && args[1][] == "dynamic" {
+        let (format, extension) = match parse_format(args[3..]) {
+            Ok(format) => format,
+            Err(e) => fail!("{}", e)
+        };
+        load_dynamic(args[2][], &format, extension[]);
+        return;
+    }
+    if args.len() == 3 && args[1][] == "codegen" {
+        generate_tables(args[2][]);
+        return;
+    }
+    if args.len() == 3 && args[1][] == "sql" {
+        run_queries(args[2][]);
+        return;
+    }
+    if (args.len() == 3 || args.len() == 4) && args[1][] == "server" {
+        let port = if args.len() == 4 {
+            match from_str(args[3][]) {
+                Some(port) => port,
+                None => fail!("not a port: {}", args[3])
+            }
+        } else {
+            5433
+        };
+        let catalog = read_schema(args[2][]);
+        let mut tables = Tables::new();
+        read_tables(&mut tables);
+        pgwire::serve(catalog, tables, port);
+        return;
+    }
+    if args.len() == 3 && args[1][] == "rpcserver" {
+        let address = match rpc::Address::parse(args[2][]) {
+            Ok(address) => address,
+            Err(e) => fail!("{}", e)
+        };
+        let mut tables = Tables::new();
+        read_tables(&mut tables);
+        rpc::serve(tables, &address);
+        return;
+    }
+    if args.len() >= 4 && args[1][] == "rpcload" {
+        let address = match rpc::Address::parse(args[2][]) {
+            Ok(address) => address,
+            Err(e) => fail!("{}", e)
+        };
+        let terminals: uint = match from_str(args[3][]) {
+            Some(terminals) if terminals > 0 => terminals,
+            _ => fail!("not a number of terminals: {}", args[3])
+        };
+        let options = match parse_options(args[4..]) {
+            Ok(options) => options,
+            Err(e) => fail!("{}", e)
+        };
+        let per_terminal = options.transactions / terminals;
+        let run = rpc::load(&address, terminals, per_terminal, options.seed);
+        let result = BenchmarkResult {
+            warehouses: NUM_WAREHOUSES, transactions: options.transactions, seed: options.seed,
+            load_seconds: 0f64, run: &run, table_sizes: Vec::new()
+        };
+        if let Some(filename) = options.json {
+            report::write(filename[], result.to_json());
+        }
+        if let Some(filename) = options.csv {
+            report::write(filename[], result.to_csv());
+        }
+        return;
+    }
+    if args.len() == 3 && args[1][] == "repl" {
+        let catalog = read_schema(args[2][]);
+        let tables: &mut Tables = &mut Tables::new();
+        read_tables(tables);
+        repl::run(&catalog, tables);
+        return;
+    }
+    if args.len() == 2 && args[1][] == "layout" {
+        layout::compare_layouts();
+        return;
+    }
+    if args.len() == 3 && args[1][] == "backends" {
+        backends::compare_backends(&read_schema(args[2][]));
+        return;
+    }
+    if args.len() == 2 && args[1][] == "snapshot" {
+        snapshot::measure_snapshots();
+        return;
+    }
+    if args.len() == 2 && args[1][] == "chgen" {
+        chbench::generate_tables();
+        return;
+    }
+    if args.len() == 2 && args[1][] == "ch" {
+        let tables: &mut Tables = &mut Tables::new();
+        read_tables(tables);
+        if tables.supplier_table.size() == 0 || tables.nation_table.size() == 0
+                || tables.region_table.size() == 0 {
+            fail!("no supplier, nation or region rows, run chgen first");
+        }
+        chbench::run(tables, 3, 1000);
+        return;
+    }
+
+    let options = match parse_options(args[1..]) {
+        Ok(options) => options,
+        Err(e) => fail!("{}", e)
+    };
 
     let time = precise_time_ns();
 
-    let rng = &mut std::rand::task_rng();
-    for _ in range(0u, 1000000) {
-        new_order_random(tables, time::get_time().sec, urand(1, NUM_WAREHOUSES, rng), rng)
+    let tables: &mut Tables =  &mut Tables::new();
+    read_tables_with(tables, &options.format, options.extension[]);
+
+    let load_seconds = ((precise_time_ns() - time) as f64) / 1e9f64;
+    println!("insert {}s", load_seconds);
+    print_tables(tables);
+    if options.check {
+        consistency::report(tables, "after load");
     }
 
-    println!("1000000 neworder iterations {}s",
-             ((precise_time_ns() - time) as f64) / 1e9f64);
+    let rng: &mut StdRng = &mut SeedableRng::from_seed([options.seed][]);
+    let run = if options.rte {
+        rte::run(tables, options.duration as f64, options.speedup as f64, options.seed)
+    } else {
+        driver::run(tables, rng, options.transactions, 1f64)
+    };
     print_tables(tables);
+    if options.check {
+        consistency::report(tables, "after the run");
+    }
+
+    let result = BenchmarkResult {
+        warehouses: NUM_WAREHOUSES, transactions: run.count() as uint, seed: options.seed,
+        load_seconds: load_seconds, run: &run, table_sizes: tables.sizes()
+    };
+    if let Some(filename) = options.json {
+        report::write(filename[], result.to_json());
+    }
+    if let Some(filename) = options.csv {
+        report::write(filename[], result.to_csv());
+    }
 }
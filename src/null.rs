@@ -0,0 +1,77 @@
+/// Truth value of SQL's three-valued logic: comparing against NULL is
+/// neither true nor false.
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub enum Truth {
+    True,
+    False,
+    Unknown,
+}
+
+impl Truth {
+    pub fn from_bool(b: bool) -> Truth {
+        if b { True } else { False }
+    }
+
+    pub fn and(self, other: Truth) -> Truth {
+        match (self, other) {
+            (False, _) | (_, False) => False,
+            (True, True) => True,
+            _ => Unknown
+        }
+    }
+
+    pub fn or(self, other: Truth) -> Truth {
+        match (self, other) {
+            (True, _) | (_, True) => True,
+            (False, False) => False,
+            _ => Unknown
+        }
+    }
+
+    pub fn not(self) -> Truth {
+        match self {
+            True => False,
+            False => True,
+            Unknown => Unknown
+        }
+    }
+
+    /// Whether a row passes a `WHERE` clause evaluating to this value.
+    pub fn is_true(self) -> bool {
+        self == True
+    }
+}
+
+/// Compares two nullable values, `pred` decides which orderings count as a
+/// match. Yields `Unknown` if either side is NULL.
+pub fn compare<T: Ord>(a: &Option<T>, b: &Option<T>, pred: |Ordering| -> bool) -> Truth {
+    match (a, b) {
+        (&Some(ref a), &Some(ref b)) => Truth::from_bool(pred(a.cmp(b))),
+        _ => Unknown
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::{compare, True, False, Unknown};
+
+    #[test]
+    fn test_logic() {
+        assert_eq!(Unknown.and(False), False)
+        assert_eq!(Unknown.and(True), Unknown)
+        assert_eq!(Unknown.or(True), True)
+        assert_eq!(Unknown.or(False), Unknown)
+        assert_eq!(Unknown.not(), Unknown)
+        assert!(!Unknown.is_true())
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare(&Some(1i), &Some(2), |o| o == Less), True)
+        assert_eq!(compare(&Some(1i), &Some(2), |o| o == Equal), False)
+        assert_eq!(compare(&None, &Some(2i), |o| o == Less), Unknown)
+        assert_eq!(compare::<int>(&None, &None, |o| o == Equal), Unknown)
+    }
+}
@@ -1,8 +1,12 @@
 use std::char::{is_whitespace, to_digit};
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::fmt;
+use std::hash;
+use std::hash::Hash;
+use std::iter::Peekable;
 use std::num::pow;
 
-#[deriving(PartialOrd, Clone, Show, Eq)]
+#[deriving(Clone, Eq)]
 pub struct Numeric {
     value: i64,
     len: uint,
@@ -19,58 +23,194 @@ impl Numeric {
         }
     }
 
-    pub fn from_str(s: &str, len: uint, precision: uint) -> Option<Numeric> {
-        let mut s = s.trim_chars(is_whitespace);
-        let mut value = 0i64;
-        let mut negative = false;
-        let mut fraction = false;
-        if s[0..1] == "-" {
-            negative = true;
-            s = s[1..];
-        }
-
-        if s.contains_char('.') {
-            while s.chars().last() == Some('0') {
-                s = s[..s.len() - 1]
-            }
-        }
+    pub fn from_str(s: &str, len: uint, precision: uint) -> Result<Numeric, ParseError> {
+        let s = s.trim_chars(is_whitespace);
+        let mut chars = s.chars().peekable();
+        let negative = match chars.peek() {
+            Some(&'-') => { chars.next(); true },
+            Some(&'+') => { chars.next(); false },
+            Some(_) => false,
+            None => return Err(Empty)
+        };
 
-        let mut digits_seen = 0u;
-        let mut digits_seen_fraction = 0u;
-        for c in s.chars() {
+        // all digits in order, the decimal point sits after `int_len` of them
+        let mut digits: Vec<u8> = Vec::new();
+        let mut int_len = 0u;
+        let mut fraction = false;
+        let mut exponent = 0i64;
+        loop {
+            let c = match chars.next() {
+                Some(c) => c,
+                None => break
+            };
             if let Some(n) = to_digit(c, 10) {
-                value = value * 10 + n as i64;
-                if fraction {
-                    digits_seen_fraction += 1;
-                } else {
-                    digits_seen += 1;
+                digits.push(n as u8);
+                if !fraction {
+                    int_len += 1;
                 }
             } else if c == '.' {
-                fraction = match fraction {
-                    true => return None,
-                    false => true
-                };
+                if fraction {
+                    return Err(InvalidChar(c));
+                }
+                fraction = true;
+            } else if (c == 'e' || c == 'E') && !digits.is_empty() {
+                exponent = try!(parse_exponent(&mut chars));
+                break;
             } else {
-                return None;
+                return Err(InvalidChar(c));
             }
         }
 
+        if digits.is_empty() {
+            return Err(Empty);
+        }
+
+        // strip insignificant zeros, keeping track of where the decimal point is
+        let mut point = int_len as i64 + exponent;
+        let mut start = 0u;
+        while start < digits.len() && digits[start] == 0 {
+            start += 1;
+            point -= 1;
+        }
+        let mut end = digits.len();
+        while end > start && digits[end - 1] == 0 {
+            end -= 1;
+        }
+        let significant = digits[start..end];
+
+        if significant.is_empty() {
+            return Ok(Numeric::new(0, len, precision));
+        }
+
+        let int_digits = max(point, 0);
+        let fraction_digits = max(significant.len() as i64 - point, 0);
+        if int_digits > len as i64 - precision as i64 {
+            return Err(TooManyIntegerDigits);
+        }
+        if fraction_digits > precision as i64 {
+            return Err(TooManyFractionDigits);
+        }
+
+        let mut value = 0i64;
+        for &d in significant.iter() {
+            value = value * 10 + d as i64;
+        }
+        value *= pow(10, (precision as i64 + point - significant.len() as i64) as uint);
+
         if negative {
             value *= -1;
         }
 
-        if digits_seen > len - precision || digits_seen_fraction > precision {
-            None
+        Ok(Numeric::new(value, len, precision))
+    }
+
+    /// The digits without the decimal point, for primitives working on many
+    /// values of the same precision at once (see `vector`).
+    pub fn raw_value(&self) -> i64 {
+        self.value
+    }
+
+    pub fn precision(&self) -> uint {
+        self.precision
+    }
+
+    /// The raw value and precision without trailing zeros after the decimal
+    /// point, the same for all numerics that compare equal.
+    fn normalized(&self) -> (i64, uint) {
+        let (mut value, mut precision) = (self.value, self.precision);
+        while precision > 0 && value % 10 == 0 {
+            value /= 10;
+            precision -= 1;
+        }
+        (value, precision)
+    }
+
+    /// The value with `precision` digits after the decimal point and at
+    /// most `len` digits, rounding half away from zero.
+    pub fn round(&self, len: uint, precision: uint) -> Numeric {
+        let value = if precision >= self.precision {
+            self.value * pow(10, precision - self.precision)
         } else {
-            Some(Numeric::new(value * pow(10, precision - digits_seen_fraction), len, precision))
+            let divisor = pow(10i64, self.precision - precision);
+            let half = if self.value < 0 { -divisor / 2 } else { divisor / 2 };
+            (self.value + half) / divisor
+        };
+        Numeric::new(value, len, precision)
+    }
+}
+
+/// Why a string could not be parsed by `Numeric::from_str`.
+#[deriving(PartialEq, Eq, Clone, Show)]
+pub enum ParseError {
+    /// The input contains no digits.
+    Empty,
+    InvalidChar(char),
+    /// More digits before the decimal point than `len - precision`.
+    TooManyIntegerDigits,
+    /// More (non-zero) digits after the decimal point than `precision`.
+    TooManyFractionDigits,
+}
+
+/// Parses the part following an `e`: an optional sign and at least one digit.
+/// Absurdly large exponents are clamped, they fail the digit checks anyway.
+fn parse_exponent<I: Iterator<char>>(chars: &mut Peekable<char, I>) -> Result<i64, ParseError> {
+    let negative = match chars.peek() {
+        Some(&'-') => { chars.next(); true },
+        Some(&'+') => { chars.next(); false },
+        _ => false
+    };
+
+    let mut exponent = 0i64;
+    let mut digits_seen = false;
+    for c in chars.by_ref() {
+        match to_digit(c, 10) {
+            Some(n) => {
+                exponent = min(exponent * 10 + n as i64, 1_000_000);
+                digits_seen = true;
+            },
+            None => return Err(InvalidChar(c))
         }
     }
+
+    if !digits_seen {
+        return Err(Empty);
+    }
+
+    Ok(if negative { -exponent } else { exponent })
 }
 
 impl PartialEq for Numeric {
     fn eq(&self, other: &Numeric) -> bool {
-        self.value == other.value
-            && self.precision == other.precision
+        if self.precision == other.precision {
+            self.value == other.value
+        } else {
+            self.normalized() == other.normalized()
+        }
+    }
+}
+
+impl<S: hash::Writer> Hash<S> for Numeric {
+    fn hash(&self, state: &mut S) {
+        // consistent with `eq`, which ignores `len` and trailing zeros
+        self.normalized().hash(state);
+    }
+}
+
+impl fmt::Show for Numeric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.precision == 0 {
+            return write!(f, "{}", self.value);
+        }
+        let divisor = pow(10i64, self.precision);
+        write!(f, "{}{}.{:0>width$}", if self.value < 0 { "-" } else { "" },
+               (self.value / divisor).abs(), (self.value % divisor).abs(), width = self.precision)
+    }
+}
+
+/// Compares the values like `cmp`, whatever the lengths and precisions.
+impl PartialOrd for Numeric {
+    fn partial_cmp(&self, other: &Numeric) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -79,7 +219,7 @@ impl Ord for Numeric {
         match self.precision.cmp(&other.precision) {
             Equal => self.value.cmp(&other.value),
             Less => (self.value * pow(10, other.precision - self.precision)).cmp(&other.value),
-            Greater => (other.value * pow(10, self.precision - other.precision)).cmp(&self.value),
+            Greater => self.value.cmp(&(other.value * pow(10, self.precision - other.precision))),
         }
     }
 }
@@ -114,14 +254,11 @@ impl Sub<Numeric, Numeric> for Numeric {
 
 impl Mul<Numeric, Numeric> for Numeric {
     fn mul(&self, rhs: &Numeric) -> Numeric {
+        // the fraction digits of both factors add up, like the integer digits
         Numeric {
-            value: match self.precision.cmp(&rhs.precision) {
-                Equal => self.value * rhs.value,
-                Less => self.value * pow(10, rhs.precision - self.precision) * rhs.value,
-                Greater => self.value * rhs.value * pow(10, self.precision * rhs.precision),
-            },
-            precision: max(self.precision, rhs.precision),
-            len: max(self.len, rhs.len)
+            value: self.value * rhs.value,
+            precision: self.precision + rhs.precision,
+            len: self.len + rhs.len
         }
     }
 }
@@ -130,17 +267,143 @@ impl Mul<Numeric, Numeric> for Numeric {
 
 #[cfg(test)]
 mod test {
-    use super::Numeric;
+    use std::hash::hash;
+    use std::rand::{task_rng, Rng};
+
+    use super::{Numeric, Empty, InvalidChar, TooManyIntegerDigits, TooManyFractionDigits};
 
     #[test]
     fn test_from_str() {
-        assert_eq!(Numeric::from_str("50", 2, 0), Some(Numeric {value: 50, len: 2, precision: 0}))
-        assert_eq!(Numeric::from_str("-50", 2, 0), Some(Numeric {value: -50, len: 2, precision: 0}))
-        assert_eq!(Numeric::from_str("50.25", 4, 2), Some(Numeric {value: 5025, len: 4, precision: 2}))
-        assert_eq!(Numeric::from_str("-50.25", 4, 2), Some(Numeric {value: -5025, len: 4, precision: 2}))
-        assert_eq!(Numeric::from_str("-50.250", 4, 2), Some(Numeric {value: -5025, len: 4, precision: 2}))
-        assert_eq!(Numeric::from_str("-50.25", 5, 3), Some(Numeric {value: -50250, len: 5, precision: 3}))
-        assert_eq!(Numeric::from_str("10.2.1", 4, 0), None)
-        assert_eq!(Numeric::from_str("abc", 4, 0), None)
+        assert_eq!(Numeric::from_str("50", 2, 0), Ok(Numeric {value: 50, len: 2, precision: 0}))
+        assert_eq!(Numeric::from_str("-50", 2, 0), Ok(Numeric {value: -50, len: 2, precision: 0}))
+        assert_eq!(Numeric::from_str("50.25", 4, 2), Ok(Numeric {value: 5025, len: 4, precision: 2}))
+        assert_eq!(Numeric::from_str("-50.25", 4, 2), Ok(Numeric {value: -5025, len: 4, precision: 2}))
+        assert_eq!(Numeric::from_str("-50.250", 4, 2), Ok(Numeric {value: -5025, len: 4, precision: 2}))
+        assert_eq!(Numeric::from_str("-50.25", 5, 3), Ok(Numeric {value: -50250, len: 5, precision: 3}))
+        assert_eq!(Numeric::from_str("10.2.1", 4, 0), Err(InvalidChar('.')))
+        assert_eq!(Numeric::from_str("abc", 4, 0), Err(InvalidChar('a')))
+    }
+
+    #[test]
+    fn test_from_str_forms() {
+        assert_eq!(Numeric::from_str("+50", 2, 0), Ok(Numeric {value: 50, len: 2, precision: 0}))
+        assert_eq!(Numeric::from_str(".5", 2, 1), Ok(Numeric {value: 5, len: 2, precision: 1}))
+        assert_eq!(Numeric::from_str("-.5", 2, 1), Ok(Numeric {value: -5, len: 2, precision: 1}))
+        assert_eq!(Numeric::from_str("5.", 2, 1), Ok(Numeric {value: 50, len: 2, precision: 1}))
+        assert_eq!(Numeric::from_str("1e3", 4, 0), Ok(Numeric {value: 1000, len: 4, precision: 0}))
+        assert_eq!(Numeric::from_str("1.5E+2", 4, 1), Ok(Numeric {value: 1500, len: 4, precision: 1}))
+        assert_eq!(Numeric::from_str("25e-2", 4, 2), Ok(Numeric {value: 25, len: 4, precision: 2}))
+        assert_eq!(Numeric::from_str("0050", 2, 0), Ok(Numeric {value: 50, len: 2, precision: 0}))
+        assert_eq!(Numeric::from_str("500", 3, 0), Ok(Numeric {value: 500, len: 3, precision: 0}))
+        assert_eq!(Numeric::from_str("0.000", 1, 0), Ok(Numeric {value: 0, len: 1, precision: 0}))
+        assert_eq!(Numeric::from_str(" 7 ", 1, 0), Ok(Numeric {value: 7, len: 1, precision: 0}))
+    }
+
+    #[test]
+    fn test_from_str_errors() {
+        assert_eq!(Numeric::from_str("", 2, 0), Err(Empty))
+        assert_eq!(Numeric::from_str("   ", 2, 0), Err(Empty))
+        assert_eq!(Numeric::from_str("-", 2, 0), Err(Empty))
+        assert_eq!(Numeric::from_str(".", 2, 0), Err(Empty))
+        assert_eq!(Numeric::from_str("1e", 2, 0), Err(Empty))
+        assert_eq!(Numeric::from_str("e3", 2, 0), Err(InvalidChar('e')))
+        assert_eq!(Numeric::from_str("1e3.0", 4, 0), Err(InvalidChar('.')))
+        assert_eq!(Numeric::from_str("--1", 2, 0), Err(InvalidChar('-')))
+        assert_eq!(Numeric::from_str("123", 2, 0), Err(TooManyIntegerDigits))
+        assert_eq!(Numeric::from_str("1e2", 2, 0), Err(TooManyIntegerDigits))
+        assert_eq!(Numeric::from_str("1", 2, 4), Err(TooManyIntegerDigits))
+        assert_eq!(Numeric::from_str("0.125", 4, 2), Err(TooManyFractionDigits))
+        assert_eq!(Numeric::from_str("1e-3", 4, 2), Err(TooManyFractionDigits))
+        assert_eq!(Numeric::from_str("1e99999999999999999999", 4, 0), Err(TooManyIntegerDigits))
+    }
+
+    #[test]
+    fn test_show() {
+        assert_eq!(Numeric::new(5025, 4, 2).to_string(), "50.25".to_string())
+        assert_eq!(Numeric::new(-5, 4, 2).to_string(), "-0.05".to_string())
+        assert_eq!(Numeric::new(42, 2, 0).to_string(), "42".to_string())
+    }
+
+    #[test]
+    fn test_cmp_mixed_precision() {
+        let amount = Numeric::new(1000, 6, 2);
+        assert_eq!(amount.cmp(&Numeric::new(0, 1, 0)), Greater)
+        assert_eq!(Numeric::new(0, 1, 0).cmp(&amount), Less)
+        assert_eq!(amount.cmp(&Numeric::new(10, 2, 0)), Equal)
+        assert_eq!(amount.cmp(&Numeric::new(11, 2, 0)), Less)
+        assert_eq!(Numeric::new(10001, 6, 3).cmp(&amount), Greater)
+    }
+
+    #[test]
+    fn test_eq_hash_mixed_precision() {
+        let one = Numeric::new(10, 2, 1);
+        assert_eq!(one, Numeric::new(100, 3, 2))
+        assert_eq!(hash(&one), hash(&Numeric::new(100, 3, 2)))
+        assert_eq!(hash(&one), hash(&Numeric::new(1, 1, 0)))
+        assert_eq!(hash(&Numeric::new(0, 1, 0)), hash(&Numeric::new(0, 4, 2)))
+        assert!(one != Numeric::new(101, 3, 2))
+        assert!(Numeric::new(10, 2, 0) != Numeric::new(1, 1, 0))
+    }
+
+    #[test]
+    fn test_round() {
+        assert_eq!(Numeric::new(157500, 6, 4).round(6, 2), Numeric::new(1575, 6, 2))
+        assert_eq!(Numeric::new(157450, 6, 4).round(6, 1), Numeric::new(157, 6, 1))
+        assert_eq!(Numeric::new(-157450, 6, 4).round(6, 2), Numeric::new(-1575, 6, 2))
+        assert_eq!(Numeric::new(-15, 4, 1).round(4, 3), Numeric::new(-1500, 4, 3))
+    }
+
+    #[test]
+    fn test_operators_ignore_len() {
+        let quantity = Numeric::new(1, 2, 0);
+        assert!(quantity >= Numeric::new(1, 6, 0))
+        assert!(quantity <= Numeric::new(1, 1, 0))
+        assert!(Numeric::new(1000, 6, 2) > Numeric::new(0, 1, 0))
+        assert!(!(Numeric::new(1000, 6, 2) < Numeric::new(10, 2, 0)))
+    }
+
+    #[test]
+    fn test_mul_mixed_precision() {
+        let amount = Numeric::new(1050, 6, 2);
+        let two = Numeric::new(2, 1, 0);
+        assert_eq!((two * amount).to_string(), "21.00".to_string())
+        assert_eq!((amount * two).to_string(), "21.00".to_string())
+        assert_eq!((amount * Numeric::new(150, 5, 2)).to_string(), "15.7500".to_string())
+        assert_eq!((Numeric::new(5, 2, 1) * amount).to_string(), "5.250".to_string())
+    }
+
+    #[test]
+    fn fuzz_from_str() {
+        let alphabet = ['0', '1', '5', '9', '.', '-', '+', 'e', 'E', ' ', 'x'];
+        let mut rng = task_rng();
+        for _ in range(0u, 100000) {
+            let s: String = range(0, rng.gen_range(0u, 12))
+                .map(|_| *rng.choose(alphabet).unwrap())
+                .collect();
+            let len = rng.gen_range(0u, 19);
+            let precision = rng.gen_range(0u, 19);
+            // must never panic, whatever the input
+            let _ = Numeric::from_str(s[], len, precision);
+        }
+    }
+
+    #[test]
+    fn fuzz_from_str_roundtrip() {
+        let mut rng = task_rng();
+        for _ in range(0u, 100000) {
+            let len = rng.gen_range(1u, 19);
+            let precision = rng.gen_range(0u, len + 1);
+            let value = rng.gen_range(0i64, pow10(len)) * if rng.gen() { 1 } else { -1 };
+
+            let divisor = pow10(precision);
+            let s = format!("{}{}.{:0>width$}", if value < 0 { "-" } else { "" },
+                            (value / divisor).abs(), (value % divisor).abs(), width = precision);
+            assert_eq!(Numeric::from_str(s[], len, precision),
+                       Ok(Numeric {value: value, len: len, precision: precision}))
+        }
+    }
+
+    fn pow10(exp: uint) -> i64 {
+        range(0, exp).fold(1i64, |acc, _| acc * 10)
     }
 }
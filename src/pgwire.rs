@@ -0,0 +1,425 @@
+//! A server for the part of the PostgreSQL v3 wire protocol that standard
+//! clients need to run queries: the startup handshake without
+//! authentication, the simple query protocol with row descriptions and data
+//! rows in text format, and error responses. Queries are answered by
+//! `planner::query`, one connection per task.
+//!
+//! ```text
+//! psql -h 127.0.0.1 -p 5433
+//! ```
+//!
+//! The extended query protocol (which JDBC uses unless connected with
+//! `preferQueryMode=simple`) is answered with an error, after which its
+//! messages are skipped until Sync. Other unsupported messages get an error
+//! followed by ReadyForQuery.
+
+use std::io::{Acceptor, BufReader, EndOfFile, IoResult, Listener, MemWriter};
+use std::io::net::tcp::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use catalog::Catalog;
+use dynamic::{Row, NullValue, IntValue, NumericValue, TextValue, TimestampValue};
+use planner;
+use query::Database;
+
+
+const PROTOCOL_VERSION: i32 = 3 << 16;
+const SSL_REQUEST: i32 = 80877103;
+const CANCEL_REQUEST: i32 = 80877102;
+const MAX_STARTUP_LENGTH: i32 = 10000;
+const MAX_MESSAGE_LENGTH: i32 = 1 << 20;
+
+// oids of the column types, from pg_type
+const INT8_OID: i32 = 20;
+const TEXT_OID: i32 = 25;
+const TIMESTAMP_OID: i32 = 1114;
+const NUMERIC_OID: i32 = 1700;
+
+/// Sent after the startup, clients like JDBC check some of them.
+const PARAMETERS: [(&'static str, &'static str), ..6] = [
+    ("server_version", "9.0.0"),
+    ("server_encoding", "UTF8"),
+    ("client_encoding", "UTF8"),
+    ("DateStyle", "ISO, MDY"),
+    ("integer_datetimes", "on"),
+    ("standard_conforming_strings", "on"),
+];
+
+/// Accepts connections on `port` of the loopback interface, forever.
+pub fn serve<D: Database + Send + Sync>(catalog: Catalog, db: D, port: u16) {
+    let mut acceptor = match TcpListener::bind("127.0.0.1", port).listen() {
+        Ok(acceptor) => acceptor,
+        Err(e) => fail!("cannot listen on port {}: {}", port, e)
+    };
+    println!("listening on 127.0.0.1:{}", port);
+    let shared = Arc::new((catalog, db));
+    for stream in acceptor.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("accept failed: {}", e);
+                continue;
+            }
+        };
+        let shared = shared.clone();
+        spawn(proc() {
+            let (ref catalog, ref db) = *shared;
+            if let Err(e) = handle(stream, catalog, db) {
+                println!("connection failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Talks to one client until it terminates the connection.
+pub fn handle(mut stream: TcpStream, catalog: &Catalog, db: &Database) -> IoResult<()> {
+    if !try!(startup(&mut stream)) {
+        return Ok(());
+    }
+    // AuthenticationOk
+    try!(send(&mut stream, b'R', [0, 0, 0, 0]));
+    for &(name, value) in PARAMETERS.iter() {
+        let mut body = MemWriter::new();
+        try!(write_cstring(&mut body, name));
+        try!(write_cstring(&mut body, value));
+        try!(send(&mut stream, b'S', body.unwrap()[]));
+    }
+    try!(ready(&mut stream));
+
+    // after an error in the extended protocol, messages are skipped until Sync
+    let mut skipping = false;
+    loop {
+        let tag = match stream.read_u8() {
+            Ok(tag) => tag,
+            Err(ref e) if e.kind == EndOfFile => return Ok(()),
+            Err(e) => return Err(e)
+        };
+        let len = try!(stream.read_be_i32());
+        if len < 4 || len > MAX_MESSAGE_LENGTH {
+            return error(&mut stream, "08P01", format!("invalid message length {}", len)[]);
+        }
+        let body = try!(stream.read_exact(len as uint - 4));
+        match tag {
+            b'Q' => try!(simple_query(&mut stream, catalog, db, cstring(body[]))),
+            b'X' => return Ok(()),
+            b'S' => {
+                skipping = false;
+                try!(ready(&mut stream));
+            },
+            b'P' | b'B' | b'E' | b'D' | b'C' | b'H' if skipping => {},
+            _ => {
+                let message = format!("unsupported message type '{}', only simple queries are \
+                                       supported", tag as char);
+                try!(error(&mut stream, "0A000", message[]));
+                // the client of the extended protocol sends Sync for ReadyForQuery
+                match tag {
+                    b'P' | b'B' | b'E' | b'D' | b'C' | b'H' => skipping = true,
+                    _ => try!(ready(&mut stream))
+                }
+            }
+        }
+    }
+}
+
+/// Reads the startup message, declining SSL, and returns whether the client
+/// wants to run queries (and not to cancel one).
+fn startup(stream: &mut TcpStream) -> IoResult<bool> {
+    loop {
+        let len = try!(stream.read_be_i32());
+        if len < 8 || len > MAX_STARTUP_LENGTH {
+            try!(error(stream, "08P01", format!("invalid startup message length {}", len)[]));
+            return Ok(false);
+        }
+        // the parameters (user, database, ...) that follow are ignored
+        let body = try!(stream.read_exact(len as uint - 4));
+        match try!(BufReader::new(body[]).read_be_i32()) {
+            SSL_REQUEST => try!(stream.write_u8(b'N')),
+            PROTOCOL_VERSION => return Ok(true),
+            CANCEL_REQUEST => return Ok(false),
+            version => {
+                let message = format!("unsupported protocol {}.{}", version >> 16,
+                                      version & 0xffff);
+                try!(error(stream, "0A000", message[]));
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Runs the statements of `query` until one fails, answering each with its
+/// rows or an error.
+fn simple_query(stream: &mut TcpStream, catalog: &Catalog, db: &Database, query: &str)
+        -> IoResult<()> {
+    let statements = statements(query);
+    if statements.is_empty() {
+        try!(send(stream, b'I', []));
+    }
+    for statement in statements.iter() {
+        let (columns, rows) = match planner::query(catalog, db, *statement) {
+            Ok(result) => result,
+            Err(e) => {
+                try!(error(stream, error_code(e[]), e[]));
+                break;
+            }
+        };
+        try!(send(stream, b'T', row_description(columns[], rows[])[]));
+        for row in rows.iter() {
+            try!(send(stream, b'D', data_row(row)[]));
+        }
+        let explain = statement.words().next().map_or(false, |word| {
+            let word: String = word.chars().map(|c| c.to_lowercase()).collect();
+            word[] == "explain"
+        });
+        let tag = if explain { "EXPLAIN".to_string() } else { format!("SELECT {}", rows.len()) };
+        let mut body = MemWriter::new();
+        try!(write_cstring(&mut body, tag[]));
+        try!(send(stream, b'C', body.unwrap()[]));
+    }
+    ready(stream)
+}
+
+/// The SQLSTATE of an error of `planner::query`.
+fn error_code(message: &str) -> &'static str {
+    if message.starts_with("relation ") && message.ends_with(" does not exist") {
+        "42P01"
+    } else {
+        "42601"
+    }
+}
+
+/// The statements of a query string, split at the semicolons outside of
+/// string literals.
+fn statements(query: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in query.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ';' if !quoted => {
+                statements.push(query[start..i]);
+                start = i + 1;
+            },
+            _ => {}
+        }
+    }
+    statements.push(query[start..]);
+    statements.into_iter()
+        .map(|statement| statement.trim())
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// The type of a column is taken from its first value that is not NULL,
+/// text if there is none.
+fn row_description(columns: &[String], rows: &[Row]) -> Vec<u8> {
+    let mut body = MemWriter::new();
+    body.write_be_i16(columns.len() as i16).unwrap();
+    for (i, name) in columns.iter().enumerate() {
+        let oid = rows.iter()
+            .filter_map(|row| match row[i] {
+                NullValue => None,
+                IntValue(_) => Some(INT8_OID),
+                NumericValue(_) => Some(NUMERIC_OID),
+                TextValue(_) => Some(TEXT_OID),
+                TimestampValue(_) => Some(TIMESTAMP_OID),
+            })
+            .next()
+            .unwrap_or(TEXT_OID);
+        write_cstring(&mut body, name[]).unwrap();
+        // table oid, column number, type oid, type size, type modifier, text format
+        body.write_be_i32(0).unwrap();
+        body.write_be_i16(0).unwrap();
+        body.write_be_i32(oid).unwrap();
+        body.write_be_i16(-1).unwrap();
+        body.write_be_i32(-1).unwrap();
+        body.write_be_i16(0).unwrap();
+    }
+    body.unwrap()
+}
+
+fn data_row(row: &Row) -> Vec<u8> {
+    let mut body = MemWriter::new();
+    body.write_be_i16(row.len() as i16).unwrap();
+    for value in row.iter() {
+        match *value {
+            NullValue => body.write_be_i32(-1).unwrap(),
+            ref value => {
+                let text = value.to_string();
+                body.write_be_i32(text.len() as i32).unwrap();
+                body.write_str(text[]).unwrap();
+            }
+        }
+    }
+    body.unwrap()
+}
+
+fn error(stream: &mut TcpStream, code: &str, message: &str) -> IoResult<()> {
+    let mut body = MemWriter::new();
+    for &(field, value) in [(b'S', "ERROR"), (b'C', code), (b'M', message)].iter() {
+        try!(body.write_u8(field));
+        try!(write_cstring(&mut body, value));
+    }
+    try!(body.write_u8(0));
+    send(stream, b'E', body.unwrap()[])
+}
+
+/// ReadyForQuery, not in a transaction block.
+fn ready(stream: &mut TcpStream) -> IoResult<()> {
+    send(stream, b'Z', [b'I'])
+}
+
+fn send(stream: &mut TcpStream, tag: u8, body: &[u8]) -> IoResult<()> {
+    try!(stream.write_u8(tag));
+    try!(stream.write_be_i32(body.len() as i32 + 4));
+    stream.write(body)
+}
+
+fn write_cstring(writer: &mut Writer, s: &str) -> IoResult<()> {
+    try!(writer.write_str(s));
+    writer.write_u8(0)
+}
+
+/// The string up to the first NUL byte.
+fn cstring(bytes: &[u8]) -> &str {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    ::std::str::from_utf8(bytes[..end]).unwrap_or("")
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{Acceptor, Listener, MemWriter};
+    use std::io::net::tcp::{TcpListener, TcpStream};
+
+    use ddl;
+    use dynamic::DynTable;
+    use query::{Database, Relation};
+
+    use super::{handle, statements, PROTOCOL_VERSION};
+
+    struct Districts {
+        table: DynTable,
+    }
+
+    impl Database for Districts {
+        fn relation(&self, name: &str) -> Option<&Relation> {
+            if name == "district" { Some(&self.table as &Relation) } else { None }
+        }
+    }
+
+    /// Reads messages up to and including ReadyForQuery.
+    fn read_messages(client: &mut TcpStream) -> Vec<(char, Vec<u8>)> {
+        let mut messages = Vec::new();
+        loop {
+            let tag = client.read_u8().unwrap() as char;
+            let len = client.read_be_i32().unwrap();
+            messages.push((tag, client.read_exact(len as uint - 4).unwrap()));
+            if tag == 'Z' {
+                return messages;
+            }
+        }
+    }
+
+    fn tags(messages: &[(char, Vec<u8>)]) -> String {
+        messages.iter().map(|&(tag, _)| tag).collect()
+    }
+
+    #[test]
+    fn test_statements() {
+        assert_eq!(statements("select 1; select ';' ;;"), vec!["select 1", "select ';'"])
+        assert!(statements(" ; ").is_empty())
+    }
+
+    /// Serves one connection to a database with two districts and returns
+    /// the port.
+    fn serve() -> u16 {
+        let mut acceptor = TcpListener::bind("127.0.0.1", 0).listen().unwrap();
+        let port = acceptor.socket_name().unwrap().port;
+        spawn(proc() {
+            let catalog = ddl::parse("create table district (d_w_id int, d_id int, \
+                                      d_name varchar(10), primary key (d_w_id, d_id));").unwrap();
+            let mut db = Districts {
+                table: DynTable::new(catalog.table("district").unwrap().clone())
+            };
+            for line in ["1|1|north", "1|2|"].iter() {
+                let row = db.table.parse_row(line.split('|')).unwrap();
+                db.table.insert(row);
+            }
+            handle(acceptor.accept().unwrap(), &catalog, &db).unwrap();
+        });
+        port
+    }
+
+    /// Connects and sends the startup message.
+    fn connect(port: u16) -> TcpStream {
+        let mut client = TcpStream::connect("127.0.0.1", port).unwrap();
+        let mut startup = MemWriter::new();
+        startup.write_be_i32(PROTOCOL_VERSION).unwrap();
+        startup.write(b"user\0test\0\0").unwrap();
+        let startup = startup.unwrap();
+        client.write_be_i32(startup.len() as i32 + 4).unwrap();
+        client.write(startup[]).unwrap();
+        client
+    }
+
+    #[test]
+    fn test_simple_query() {
+        let mut client = connect(serve());
+        let messages = read_messages(&mut client);
+        assert_eq!(tags(messages[]), "RSSSSSSZ".to_string())
+        assert_eq!(messages[0], ('R', vec![0, 0, 0, 0]))
+
+        let query = b"select d_id, d_name from district order by d_id; select x from nowhere\0";
+        client.write_u8(b'Q').unwrap();
+        client.write_be_i32(query.len() as i32 + 4).unwrap();
+        client.write(query).unwrap();
+        let messages = read_messages(&mut client);
+        assert_eq!(tags(messages[]), "TDDCEZ".to_string())
+        // two columns, the first named d_id of type int8
+        let (_, ref description) = messages[0];
+        assert_eq!(description[..7], b"\0\x02d_id\0")
+        assert_eq!(description[13..17], b"\0\0\0\x14")
+        assert_eq!(messages[1], ('D', b"\0\x02\0\0\0\x011\0\0\0\x05north".to_vec()))
+        // NULL is length -1
+        assert_eq!(messages[2], ('D', b"\0\x02\0\0\0\x012\xff\xff\xff\xff".to_vec()))
+        assert_eq!(messages[3], ('C', b"SELECT 2\0".to_vec()))
+        let (_, ref error) = messages[4];
+        assert_eq!(error[..14], b"SERROR\0C42P01\0")
+        assert_eq!(messages[5], ('Z', vec![b'I']))
+
+        client.write(b"X\0\0\0\x04").unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_messages() {
+        let mut client = connect(serve());
+        read_messages(&mut client);
+        // a function call is answered right away
+        client.write(b"F\0\0\0\x04").unwrap();
+        assert_eq!(tags(read_messages(&mut client)[]), "EZ".to_string())
+        // Parse, Bind and Execute are answered by a single error, then Sync
+        client.write(b"P\0\0\0\x04B\0\0\0\x04E\0\0\0\x04S\0\0\0\x04").unwrap();
+        assert_eq!(tags(read_messages(&mut client)[]), "EZ".to_string())
+
+        let query = b"select d_id from district where d_id = 1\0";
+        client.write_u8(b'Q').unwrap();
+        client.write_be_i32(query.len() as i32 + 4).unwrap();
+        client.write(query).unwrap();
+        assert_eq!(tags(read_messages(&mut client)[]), "TDCZ".to_string())
+        client.write(b"X\0\0\0\x04").unwrap();
+    }
+
+    #[test]
+    fn test_message_too_long() {
+        let mut client = connect(serve());
+        read_messages(&mut client);
+        // only the length is sent, the server must not wait for 2 GB
+        client.write(b"Q\x7f\xff\xff\xff").unwrap();
+        assert_eq!(client.read_u8().unwrap(), b'E')
+        let len = client.read_be_i32().unwrap();
+        let body = client.read_exact(len as uint - 4).unwrap();
+        assert_eq!(body[..14], b"SERROR\0C08P01\0")
+    }
+}
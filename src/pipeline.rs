@@ -0,0 +1,532 @@
+//! Push based query execution: a producer (`scan`) pushes every tuple into a
+//! chain of consumers, each of which processes it and pushes its results on
+//! to the next one.
+//!
+//! ```ignore
+//! // sum(ol_amount) per warehouse for orderlines of items cheaper than 10
+//! let mut items = HashMap::new();
+//! let cheap = PriceBelow { price: ten };
+//! scan(&tables.item_table, &mut Select::new(cheap, Build::new(&mut items, ItemId)));
+//! let mut groups = GroupBy::new(AmountPerWarehouse);
+//! scan(&tables.orderline_table, &mut Probe::new(&items, OrderlineItem, &mut groups));
+//! ```
+//!
+//! Unlike the operators of `query`, a plan is not a tree of trait objects
+//! but a nested type: predicates, keys and aggregates are types implementing
+//! the traits below, so the compiler monomorphizes the whole pipeline into a
+//! loop over the table without virtual calls or dynamic `Row`s in between.
+//!
+//! `execute` compiles the physical plans of `planner` into such pipelines
+//! over dynamic `Row`s: a pipeline starts at a scan or lookup and ends at the
+//! build side of a hash join, an aggregation, a sort or the result, the
+//! operators in between becoming stages whose predicates, keys and
+//! aggregations evaluate the plan's expressions. As the shape of a plan is
+//! only known at run time, its stages are linked through boxed consumers,
+//! costing a virtual call per stage and row, and scans read the rows through
+//! `Relation`. Only pipelines written for a query, like those of `backends`,
+//! are monomorphized into a single loop.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use collections::hash::Hash;
+
+use super::dynamic::Row;
+use super::planner::{PhysicalPlan, PlanScan, PlanLookup, PlanFilter, PlanProject, PlanHashJoin,
+                     PlanAggregate, PlanSort, PlanLimit};
+use super::query::{Expr, AggregateFunction, initial_states, accumulate, sort_rows};
+use super::table::{PrimaryKey, Table};
+
+
+/// The receiving end of a pipeline step.
+pub trait Consumer<T> {
+    fn consume(&mut self, tuple: T);
+}
+
+/// Collects the tuples reaching the end of a pipeline.
+impl<T> Consumer<T> for Vec<T> {
+    fn consume(&mut self, tuple: T) {
+        self.push(tuple);
+    }
+}
+
+/// Lets a pipeline end in a consumer that is read once the pipeline ran.
+impl<'a, T, C: Consumer<T>> Consumer<T> for &'a mut C {
+    fn consume(&mut self, tuple: T) {
+        (**self).consume(tuple);
+    }
+}
+
+/// Links the stages of a pipeline compiled from a plan.
+impl<'a, T> Consumer<T> for Box<Consumer<T> + 'a> {
+    fn consume(&mut self, tuple: T) {
+        (**self).consume(tuple);
+    }
+}
+
+pub trait Predicate<T> {
+    fn test(&self, tuple: &T) -> bool;
+}
+
+pub trait Function<T, U> {
+    fn call(&self, tuple: T) -> U;
+}
+
+/// The join or grouping key of a tuple.
+pub trait Key<T, K> {
+    fn key(&self, tuple: &T) -> K;
+}
+
+/// Groups tuples by `key` and folds each group into a state.
+pub trait Aggregation<T, K, S> {
+    fn key(&self, tuple: &T) -> K;
+    fn init(&self) -> S;
+    fn update(&self, state: &mut S, tuple: T);
+}
+
+/// Pushes all rows of `table` into `consumer`.
+pub fn scan<'a, Data: PrimaryKey<Index> + Clone, Index: Eq + Hash, C: Consumer<&'a Data>>
+        (table: &'a Table<Data, Index>, consumer: &mut C) {
+    for row in table.scan() {
+        consumer.consume(row);
+    }
+}
+
+/// Passes on the tuples satisfying `predicate`.
+pub struct Select<P, C> {
+    predicate: P,
+    consumer: C,
+}
+
+impl<P, C> Select<P, C> {
+    pub fn new(predicate: P, consumer: C) -> Select<P, C> {
+        Select { predicate: predicate, consumer: consumer }
+    }
+}
+
+impl<T, P: Predicate<T>, C: Consumer<T>> Consumer<T> for Select<P, C> {
+    fn consume(&mut self, tuple: T) {
+        if self.predicate.test(&tuple) {
+            self.consumer.consume(tuple);
+        }
+    }
+}
+
+/// Passes on `function` applied to each tuple.
+pub struct Map<F, C> {
+    function: F,
+    consumer: C,
+}
+
+impl<F, C> Map<F, C> {
+    pub fn new(function: F, consumer: C) -> Map<F, C> {
+        Map { function: function, consumer: consumer }
+    }
+}
+
+impl<T, U, F: Function<T, U>, C: Consumer<U>> Consumer<T> for Map<F, C> {
+    fn consume(&mut self, tuple: T) {
+        self.consumer.consume(self.function.call(tuple));
+    }
+}
+
+/// The build side of a hash join, ends a pipeline by inserting the tuples
+/// into `table` by `key`.
+pub struct Build<'a, K: 'a, T: 'a, F> {
+    table: &'a mut HashMap<K, Vec<T>>,
+    key: F,
+}
+
+impl<'a, K: Eq + Hash, T, F> Build<'a, K, T, F> {
+    pub fn new(table: &'a mut HashMap<K, Vec<T>>, key: F) -> Build<'a, K, T, F> {
+        Build { table: table, key: key }
+    }
+}
+
+impl<'a, K: Eq + Hash, T, F: Key<T, K>> Consumer<T> for Build<'a, K, T, F> {
+    fn consume(&mut self, tuple: T) {
+        let key = self.key.key(&tuple);
+        self.table.find_or_insert_with(key, |_| Vec::new()).push(tuple);
+    }
+}
+
+/// The probe side of a hash join, passes on (build tuple, probe tuple) for
+/// every build tuple with the same key.
+pub struct Probe<'a, K: 'a, B: 'a, F, C> {
+    table: &'a HashMap<K, Vec<B>>,
+    key: F,
+    consumer: C,
+}
+
+impl<'a, K: Eq + Hash, B, F, C> Probe<'a, K, B, F, C> {
+    pub fn new(table: &'a HashMap<K, Vec<B>>, key: F, consumer: C) -> Probe<'a, K, B, F, C> {
+        Probe { table: table, key: key, consumer: consumer }
+    }
+}
+
+impl<'a, T: Clone, K: Eq + Hash, B: Clone, F: Key<T, K>, C: Consumer<(B, T)>> Consumer<T>
+        for Probe<'a, K, B, F, C> {
+    fn consume(&mut self, tuple: T) {
+        if let Some(matches) = self.table.find(&self.key.key(&tuple)) {
+            for build in matches.iter() {
+                self.consumer.consume((build.clone(), tuple.clone()));
+            }
+        }
+    }
+}
+
+/// Passes on the first `limit` tuples.
+pub struct Take<C> {
+    limit: uint,
+    consumer: C,
+}
+
+impl<C> Take<C> {
+    pub fn new(limit: uint, consumer: C) -> Take<C> {
+        Take { limit: limit, consumer: consumer }
+    }
+}
+
+impl<T, C: Consumer<T>> Consumer<T> for Take<C> {
+    fn consume(&mut self, tuple: T) {
+        if self.limit > 0 {
+            self.limit -= 1;
+            self.consumer.consume(tuple);
+        }
+    }
+}
+
+/// Ends a pipeline by aggregating the tuples into `groups`.
+pub struct GroupBy<K, S, A> {
+    pub groups: HashMap<K, S>,
+    aggregation: A,
+}
+
+impl<K: Eq + Hash, S, A> GroupBy<K, S, A> {
+    pub fn new(aggregation: A) -> GroupBy<K, S, A> {
+        GroupBy { groups: HashMap::new(), aggregation: aggregation }
+    }
+}
+
+impl<T, K: Eq + Hash, S, A: Aggregation<T, K, S>> Consumer<T> for GroupBy<K, S, A> {
+    fn consume(&mut self, tuple: T) {
+        let aggregation = &self.aggregation;
+        let state = self.groups.find_or_insert_with(aggregation.key(&tuple),
+                                                    |_| aggregation.init());
+        aggregation.update(state, tuple);
+    }
+}
+
+
+/// Runs `plan` as push pipelines, producing the same rows in the same order
+/// as its pull operators.
+pub fn execute(plan: &PhysicalPlan) -> Vec<Row> {
+    let mut rows = Vec::new();
+    push(plan, box &mut rows);
+    rows
+}
+
+/// Pushes the rows of `plan` into `consumer`, running the pipelines that
+/// end in the plan's hash tables, aggregations and sorts first.
+fn push<'a>(plan: &PhysicalPlan, mut consumer: Box<Consumer<Row> + 'a>) {
+    match *plan {
+        PlanScan(relation) => {
+            let mut rows = relation.scan();
+            loop {
+                match rows.next() {
+                    Some(row) => consumer.consume(row),
+                    None => return
+                }
+            }
+        },
+        PlanLookup(relation, ref key) => {
+            if let Some(row) = relation.lookup(key) {
+                consumer.consume(row);
+            }
+        },
+        PlanFilter(ref input, ref predicate) =>
+            push(&**input, box Select::new(IsTrue { predicate: predicate }, consumer)),
+        PlanProject(ref input, ref exprs) =>
+            push(&**input, box Map::new(Projection { exprs: exprs[] }, consumer)),
+        PlanHashJoin(ref left, ref right, ref left_keys, ref right_keys) => {
+            // NULL never equals anything
+            let mut table = HashMap::new();
+            push(&**right, box Select::new(NotNull { columns: right_keys[] },
+                                           Build::new(&mut table,
+                                                      Columns { columns: right_keys[] })));
+            let concat = Map::new(Concat, consumer);
+            push(&**left, box Select::new(NotNull { columns: left_keys[] },
+                                          Probe::new(&table, Columns { columns: left_keys[] },
+                                                     concat)));
+        },
+        PlanAggregate(ref input, ref group_by, ref aggregates) => {
+            let mut aggregate = GroupBy::new(Accumulate {
+                group_by: group_by[],
+                aggregates: aggregates[],
+                groups: Cell::new(0),
+            });
+            push(&**input, box &mut aggregate);
+            // in order of the groups' first rows, like `query::Aggregate`
+            let mut groups: Vec<(Row, (uint, Row))> = aggregate.groups.into_iter().collect();
+            groups.sort_by(|&(_, (a, _)), &(_, (b, _))| a.cmp(&b));
+            if groups.is_empty() && group_by.is_empty() {
+                groups.push((Vec::new(), (0, initial_states(aggregates[]))));
+            }
+            for (mut key, (_, states)) in groups.into_iter() {
+                key.extend(states.into_iter());
+                consumer.consume(key);
+            }
+        },
+        PlanSort(ref input, ref keys) => {
+            let mut rows = Vec::new();
+            push(&**input, box &mut rows);
+            sort_rows(&mut rows, keys[]);
+            for row in rows.into_iter() {
+                consumer.consume(row);
+            }
+        },
+        PlanLimit(ref input, limit) => push(&**input, box Take::new(limit, consumer)),
+    }
+}
+
+/// The rows for which `predicate` is `True`.
+struct IsTrue<'a> {
+    predicate: &'a Expr,
+}
+
+impl<'a> Predicate<Row> for IsTrue<'a> {
+    fn test(&self, row: &Row) -> bool {
+        self.predicate.truth(row).is_true()
+    }
+}
+
+/// The rows without NULL in `columns`.
+struct NotNull<'a> {
+    columns: &'a [uint],
+}
+
+impl<'a> Predicate<Row> for NotNull<'a> {
+    fn test(&self, row: &Row) -> bool {
+        self.columns.iter().all(|&i| !row[i].is_null())
+    }
+}
+
+struct Projection<'a> {
+    exprs: &'a [Expr],
+}
+
+impl<'a> Function<Row, Row> for Projection<'a> {
+    fn call(&self, row: Row) -> Row {
+        self.exprs.iter().map(|e| e.eval(&row)).collect()
+    }
+}
+
+/// The values of `columns`.
+struct Columns<'a> {
+    columns: &'a [uint],
+}
+
+impl<'a> Key<Row, Row> for Columns<'a> {
+    fn key(&self, row: &Row) -> Row {
+        self.columns.iter().map(|&i| row[i].clone()).collect()
+    }
+}
+
+/// A joined row from (build row, probe row): the probe (left) row followed
+/// by the build (right) row.
+struct Concat;
+
+impl Function<(Row, Row), Row> for Concat {
+    fn call(&self, (right, mut left): (Row, Row)) -> Row {
+        left.extend(right.into_iter());
+        left
+    }
+}
+
+/// Groups by the `group_by` columns, the state being the position of the
+/// group's first row among the groups and the aggregate states.
+struct Accumulate<'a> {
+    group_by: &'a [uint],
+    aggregates: &'a [AggregateFunction],
+    groups: Cell<uint>,
+}
+
+impl<'a> Aggregation<Row, Row, (uint, Row)> for Accumulate<'a> {
+    fn key(&self, row: &Row) -> Row {
+        self.group_by.iter().map(|&i| row[i].clone()).collect()
+    }
+
+    fn init(&self) -> (uint, Row) {
+        let position = self.groups.get();
+        self.groups.set(position + 1);
+        (position, initial_states(self.aggregates))
+    }
+
+    fn update(&self, state: &mut (uint, Row), row: Row) {
+        let (_, ref mut states) = *state;
+        for (aggregate, value) in self.aggregates.iter().zip(states.iter_mut()) {
+            *value = accumulate(aggregate, &*value, &row);
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use ddl;
+    use dynamic::DynTable;
+    use planner::plan;
+    use query::{rows, Database, Relation};
+    use sql;
+    use sql::SelectStatement;
+    use table::{PrimaryKey, Table};
+
+    use super::{scan, execute, Predicate, Function, Key, Aggregation, Select, Map, Build, Probe,
+                GroupBy};
+
+    #[deriving(Clone, PartialEq, Show)]
+    struct Pair {
+        id: i32,
+        value: i32,
+    }
+
+    impl PrimaryKey<i32> for Pair {
+        fn primary_key(&self) -> i32 {
+            self.id
+        }
+    }
+
+    fn table(pairs: &[(i32, i32)]) -> Table<Pair, i32> {
+        let mut table = Table::new();
+        for &(id, value) in pairs.iter() {
+            table.insert(Pair { id: id, value: value });
+        }
+        table
+    }
+
+    struct Even;
+
+    impl<'a> Predicate<&'a Pair> for Even {
+        fn test(&self, pair: &&'a Pair) -> bool {
+            pair.value % 2 == 0
+        }
+    }
+
+    struct Value;
+
+    impl<'a> Function<&'a Pair, i32> for Value {
+        fn call(&self, pair: &'a Pair) -> i32 {
+            pair.value
+        }
+    }
+
+    impl<'a> Key<&'a Pair, i32> for Value {
+        fn key(&self, pair: &&'a Pair) -> i32 {
+            pair.value
+        }
+    }
+
+    struct Id;
+
+    impl<'a> Key<&'a Pair, i32> for Id {
+        fn key(&self, pair: &&'a Pair) -> i32 {
+            pair.id
+        }
+    }
+
+    /// count(*) and sum(value) of the probe side per build side value.
+    struct CountSum;
+
+    impl<'a, 'b> Aggregation<(&'a Pair, &'b Pair), i32, (uint, i32)> for CountSum {
+        fn key(&self, tuple: &(&'a Pair, &'b Pair)) -> i32 {
+            let (build, _) = *tuple;
+            build.value
+        }
+
+        fn init(&self) -> (uint, i32) {
+            (0, 0)
+        }
+
+        fn update(&self, state: &mut (uint, i32), (_, probe): (&'a Pair, &'b Pair)) {
+            let (count, sum) = *state;
+            *state = (count + 1, sum + probe.value);
+        }
+    }
+
+    #[test]
+    fn test_select_map() {
+        let pairs = table([(1, 10), (2, 11), (3, 12)]);
+        let mut values = Vec::new();
+        scan(&pairs, &mut Select::new(Even, Map::new(Value, &mut values)));
+        assert_eq!(values, vec![10, 12])
+    }
+
+    #[test]
+    fn test_join_group_by() {
+        let build = table([(1, 100), (2, 200), (3, 100)]);
+        let probe = table([(1, 5), (3, 7), (4, 9)]);
+        let mut hash_table = HashMap::new();
+        scan(&build, &mut Build::new(&mut hash_table, Id));
+        let mut group_by = GroupBy::new(CountSum);
+        scan(&probe, &mut Probe::new(&hash_table, Id, &mut group_by));
+        let mut groups: Vec<(i32, (uint, i32))> = group_by.groups.into_iter().collect();
+        groups.sort();
+        assert_eq!(groups, vec![(100, (2, 12))])
+
+        // no probe value is a build id
+        let mut joined: Vec<(&Pair, &Pair)> = Vec::new();
+        scan(&probe, &mut Probe::new(&hash_table, Value, &mut joined));
+        assert!(joined.is_empty())
+    }
+
+    struct TestDatabase {
+        tables: HashMap<String, DynTable>,
+    }
+
+    impl Database for TestDatabase {
+        fn relation(&self, name: &str) -> Option<&Relation> {
+            self.tables.find_equiv(&name).map(|table| table as &Relation)
+        }
+    }
+
+    #[test]
+    fn test_execute() {
+        let catalog = ddl::parse("
+            create table district (d_id int, d_name varchar(10), primary key (d_id));
+            create table customer (c_d_id int, c_id int, c_balance numeric(6, 2) not null,
+                                   primary key (c_d_id, c_id));
+        ").unwrap();
+        let mut db = TestDatabase { tables: HashMap::new() };
+        for &(name, ref rows) in [("district", vec!["1|north", "2|south", "3|"]),
+                                  ("customer", vec!["1|1|10.00", "1|2|-5.50", "2|1|7.25",
+                                                    "9|1|1.00"])].iter() {
+            let mut table = DynTable::new(catalog.table(name).unwrap().clone());
+            for row in rows.iter() {
+                let row = table.parse_row(row.split('|')).unwrap();
+                table.insert(row);
+            }
+            db.tables.insert(name.to_string(), table);
+        }
+
+        for query in ["select d_name, count(*), sum(c_balance) from customer, district \
+                       where c_d_id = d_id group by d_name",
+                      "select c_id, c_balance from customer where c_balance > 0 \
+                       order by c_balance desc limit 2",
+                      "select count(*), max(c_balance) from customer where c_balance > 100",
+                      "select d_name from district where d_id = 2",
+                      "select * from district d, customer c where d_name is null or c_id = 2"]
+                .iter() {
+            let select = match sql::parse(*query) {
+                Ok(SelectStatement(select)) => select,
+                _ => fail!("not a select: {}", query)
+            };
+            let mut planned = plan(&catalog, &db, &select, false).unwrap();
+            let pushed = execute(&planned.physical);
+            assert!(!pushed.is_empty(), "{}", query)
+            assert_eq!(pushed, rows(&mut *planned.root))
+        }
+    }
+}
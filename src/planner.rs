@@ -0,0 +1,880 @@
+//! Turns a parsed `SELECT` into a tree of query operators over a `Database`.
+//!
+//! Conjuncts of the `WHERE` clause referring to a single table are applied
+//! right at its scan, and if they fix all primary key columns to constants the
+//! scan becomes a primary key lookup. The tables are then joined with hash
+//! joins, starting with the smallest (estimated) input and preferring tables
+//! connected to the ones joined so far by an equality predicate. `ORDER BY`
+//! can only refer to the select list (by name, position or repeating the
+//! expression).
+//!
+//! Estimates are crude: equality and `IS NULL` predicates are assumed to keep
+//! a tenth of the rows, all other predicates a third, equi-joins as many rows
+//! as the larger input and grouping a tenth of the rows.
+
+use std::cmp::{max, min};
+use std::mem;
+use std::rc::Rc;
+
+use time::precise_time_ns;
+
+use super::catalog::{Catalog, Column, TableSchema, IntegerType, CharType, VarcharType,
+                     TimestampType};
+use super::dynamic::{Row, Value, NullValue, IntValue, NumericValue, TextValue};
+use super::explain::PlanNode;
+use super::numeric::Numeric;
+use super::query::{rows, Database, Operator, Relation, IndexLookup, Filter, Project, HashJoin,
+                   Aggregate, Sort, Limit, Measure, Stats, SortKey, AggregateFunction, Count,
+                   CountColumn, Sum, Min, Max, Expr, ColumnRef, Literal, Arithmetic, Comparison,
+                   And, Or, Not, IsNull, IsEqual};
+use super::sql;
+use super::sql::{Select, SelectStatement, Explain, SqlExpr, SqlColumn, SqlNumber, SqlString,
+                 SqlNull, SqlArithmetic, SqlCompare, SqlAnd, SqlOr, SqlNot, SqlIsNull,
+                 SqlAggregate, Wildcard, SelectExpr};
+
+
+pub struct Plan<'a> {
+    /// Names of the output columns.
+    pub columns: Vec<String>,
+    pub root: Box<Operator + 'a>,
+    /// The same operators as data, for `pipeline::execute`.
+    pub physical: PhysicalPlan<'a>,
+    /// What `EXPLAIN` shows.
+    pub tree: PlanNode,
+}
+
+/// An operator of `query` with its arguments and inputs.
+pub enum PhysicalPlan<'a> {
+    PlanScan(&'a (Relation + 'a)),
+    /// The row with the primary key (in key order).
+    PlanLookup(&'a (Relation + 'a), Row),
+    PlanFilter(Box<PhysicalPlan<'a>>, Expr),
+    PlanProject(Box<PhysicalPlan<'a>>, Vec<Expr>),
+    /// Left and right input and their key columns, the right input is built.
+    PlanHashJoin(Box<PhysicalPlan<'a>>, Box<PhysicalPlan<'a>>, Vec<uint>, Vec<uint>),
+    PlanAggregate(Box<PhysicalPlan<'a>>, Vec<uint>, Vec<AggregateFunction>),
+    PlanSort(Box<PhysicalPlan<'a>>, Vec<SortKey>),
+    PlanLimit(Box<PhysicalPlan<'a>>, uint),
+}
+
+/// Parses, plans and runs `query`, returning the column names and the rows.
+/// `EXPLAIN` returns the lines of the plan as rows of a single column.
+pub fn query(catalog: &Catalog, db: &Database, query: &str)
+        -> Result<(Vec<String>, Vec<Row>), String> {
+    match try!(sql::parse(query)) {
+        SelectStatement(select) => {
+            let mut plan = try!(plan(catalog, db, &select, false));
+            let rows = rows(&mut *plan.root);
+            Ok((plan.columns, rows))
+        },
+        Explain(select, analyze) => {
+            let mut plan = try!(plan(catalog, db, &select, analyze));
+            let elapsed = if analyze {
+                let time = precise_time_ns();
+                rows(&mut *plan.root);
+                Some(precise_time_ns() - time)
+            } else {
+                None
+            };
+            let mut lines = plan.tree.lines();
+            if let Some(elapsed) = elapsed {
+                lines.push(format!("Execution time: {:.3}ms", elapsed as f64 / 1e6f64));
+            }
+            let rows = lines.into_iter().map(|line| vec![TextValue(line)]).collect();
+            Ok((vec!["QUERY PLAN".to_string()], rows))
+        }
+    }
+}
+
+/// A column of the rows flowing through the plan.
+#[deriving(Clone)]
+struct ScopeColumn {
+    /// Position of the table in the `FROM` clause.
+    table: uint,
+    alias: String,
+    column: Column,
+}
+
+/// An operator, its physical plan and its description.
+struct Node<'a> {
+    op: Box<Operator + 'a>,
+    plan: PhysicalPlan<'a>,
+    tree: PlanNode,
+}
+
+impl<'a> Node<'a> {
+    /// With `analyze` the operator is wrapped to measure its actual rows and
+    /// time.
+    fn new(analyze: bool, op: Box<Operator + 'a>, plan: PhysicalPlan<'a>, label: String,
+           estimate: uint, children: Vec<PlanNode>) -> Node<'a> {
+        let (op, stats) = if analyze {
+            let stats = Rc::new(Stats::new());
+            (box Measure::new(op, stats.clone()) as Box<Operator + 'a>, Some(stats))
+        } else {
+            (op, None)
+        };
+        Node {
+            op: op,
+            plan: plan,
+            tree: PlanNode { label: label, estimate: estimate, stats: stats, children: children },
+        }
+    }
+
+    fn estimate(&self) -> uint {
+        self.tree.estimate
+    }
+}
+
+/// One table of the `FROM` clause with its access path.
+struct Input<'a> {
+    node: Node<'a>,
+    scope: Vec<ScopeColumn>,
+}
+
+/// `left = right` with the two columns coming from different tables.
+struct JoinEdge {
+    left_table: uint,
+    right_table: uint,
+    left: SqlExpr,
+    right: SqlExpr,
+}
+
+pub fn plan<'a>(catalog: &Catalog, db: &'a Database, select: &Select, analyze: bool)
+        -> Result<Plan<'a>, String> {
+    let mut scope = Vec::new();
+    let mut tables = Vec::new();
+    for (i, table) in select.from.iter().enumerate() {
+        if select.from[..i].iter().any(|other| other.alias == table.alias) {
+            return Err(format!("table name {} specified more than once", table.alias));
+        }
+        let (schema, relation) = match (catalog.table(table.name[]), db.relation(table.name[])) {
+            (Some(schema), Some(relation)) => (schema, relation),
+            _ => return Err(format!("relation {} does not exist", table.name))
+        };
+        for column in schema.columns.iter() {
+            scope.push(ScopeColumn { table: i, alias: table.alias.clone(), column: column.clone() });
+        }
+        tables.push((schema, relation));
+    }
+
+    // sort the conjuncts by the tables they refer to
+    let mut conjuncts = Vec::new();
+    if let Some(ref where_clause) = select.where_clause {
+        split_conjuncts(where_clause, &mut conjuncts);
+    }
+    let mut local: Vec<Vec<SqlExpr>> = Vec::from_fn(tables.len(), |_| Vec::new());
+    let mut edges = Vec::new();
+    let mut residual = Vec::new();
+    for conjunct in conjuncts.into_iter() {
+        let mut refs = Vec::new();
+        try!(table_refs(scope[], &conjunct, &mut refs));
+        let edge = match (refs[], &conjunct) {
+            ([a, b], &SqlCompare(ref left, IsEqual, ref right))
+                    if is_column(&**left) && is_column(&**right) => {
+                // `table_refs` saw `left` first
+                Some(JoinEdge { left_table: a, right_table: b, left: (**left).clone(),
+                                right: (**right).clone() })
+            },
+            _ => None
+        };
+        match edge {
+            Some(edge) => edges.push(edge),
+            None if refs.len() == 1 => local.get_mut(refs[0]).push(conjunct),
+            None => residual.push(conjunct)
+        }
+    }
+
+    let mut inputs = Vec::new();
+    for (i, &(schema, relation)) in tables.iter().enumerate() {
+        let table_scope = scope.iter()
+            .filter(|column| column.table == i)
+            .map(|column| column.clone())
+            .collect();
+        let input = try!(access(analyze, &select.from[i], schema, relation, table_scope,
+                                local[i][]));
+        inputs.push(Some(input));
+    }
+
+    let Input { node, scope } = try!(join(analyze, inputs, &mut edges));
+    // edges closing a cycle in the join graph
+    for edge in edges.into_iter() {
+        residual.push(SqlCompare(box edge.left, IsEqual, box edge.right));
+    }
+    let node = try!(filter(analyze, node, scope[], residual[]));
+    output(analyze, node, scope, select)
+}
+
+fn split_conjuncts(e: &SqlExpr, conjuncts: &mut Vec<SqlExpr>) {
+    match *e {
+        SqlAnd(ref left, ref right) => {
+            split_conjuncts(&**left, conjuncts);
+            split_conjuncts(&**right, conjuncts);
+        },
+        _ => conjuncts.push(e.clone())
+    }
+}
+
+fn is_column(e: &SqlExpr) -> bool {
+    match *e {
+        SqlColumn(..) => true,
+        _ => false
+    }
+}
+
+/// Collects the `FROM` positions of the tables `e` refers to, in order of
+/// appearance.
+fn table_refs(scope: &[ScopeColumn], e: &SqlExpr, tables: &mut Vec<uint>) -> Result<(), String> {
+    match *e {
+        SqlColumn(ref table, ref name) => {
+            let t = scope[try!(resolve_column(scope, table, name[]))].table;
+            if !tables.contains(&t) {
+                tables.push(t);
+            }
+        },
+        SqlArithmetic(ref left, _, ref right) | SqlCompare(ref left, _, ref right) |
+        SqlAnd(ref left, ref right) | SqlOr(ref left, ref right) => {
+            try!(table_refs(scope, &**left, tables));
+            try!(table_refs(scope, &**right, tables));
+        },
+        SqlNot(ref e) | SqlIsNull(ref e) => try!(table_refs(scope, &**e, tables)),
+        SqlAggregate(ref name, _) =>
+            return Err(format!("aggregate function {} is not allowed in WHERE", name)),
+        SqlNumber(..) | SqlString(..) | SqlNull => {}
+    }
+    Ok(())
+}
+
+/// Scan or primary key lookup of a single table, filtered by its `local`
+/// conjuncts.
+fn access<'a>(analyze: bool, table: &sql::TableRef, schema: &TableSchema, relation: &'a Relation,
+              scope: Vec<ScopeColumn>, local: &[SqlExpr]) -> Result<Input<'a>, String> {
+    let mut key = Vec::new();
+    let mut used = Vec::new();
+    for &k in schema.primary_key.iter() {
+        let found = local.iter().enumerate().filter_map(|(i, conjunct)| {
+            point_predicate(scope[], k, conjunct).map(|value| (i, value))
+        }).next();
+        if let Some((i, value)) = found {
+            used.push(i);
+            key.push(value);
+        }
+    }
+
+    let name = if table.alias == table.name {
+        table.name.clone()
+    } else {
+        format!("{} {}", table.name, table.alias)
+    };
+    let point_lookup = !key.is_empty() && key.len() == schema.primary_key.len();
+    let (node, filters) = if point_lookup {
+        let columns: Vec<String> = schema.primary_key.iter().zip(key.iter())
+            .map(|(&k, value)| format!("{} = {}", schema.columns[k].name, value))
+            .collect();
+        let label = format!("Index Lookup on {} using primary key ({})", name,
+                            columns.connect(", "));
+        let filters: Vec<SqlExpr> = local.iter().enumerate()
+            .filter(|&(i, _)| !used.contains(&i))
+            .map(|(_, conjunct)| conjunct.clone())
+            .collect();
+        let plan = PlanLookup(relation, key.clone());
+        (Node::new(analyze, box IndexLookup::new(relation, key), plan, label, 1, Vec::new()),
+         filters)
+    } else {
+        let label = format!("Seq Scan on {}", name);
+        (Node::new(analyze, relation.scan(), PlanScan(relation), label, relation.size(),
+                   Vec::new()),
+         local.to_vec())
+    };
+
+    let node = try!(filter(analyze, node, scope[], filters[]));
+    Ok(Input { node: node, scope: scope })
+}
+
+/// Filters `input` by `conjuncts` (if there are any).
+fn filter<'a>(analyze: bool, input: Node<'a>, scope: &[ScopeColumn], conjuncts: &[SqlExpr])
+        -> Result<Node<'a>, String> {
+    if conjuncts.is_empty() {
+        return Ok(input);
+    }
+    let predicate = try!(resolve_all(scope, conjuncts));
+    let selectivity = conjuncts.iter().fold(1f64, |result, conjunct| result * match *conjunct {
+        SqlCompare(_, IsEqual, _) | SqlIsNull(_) => 0.1,
+        _ => 0.33
+    });
+    let estimate = (input.estimate() as f64 * selectivity).ceil() as uint;
+    let labels: Vec<String> = conjuncts.iter().map(|conjunct| match *conjunct {
+        SqlOr(..) => format!("({})", conjunct),
+        _ => conjunct.to_string()
+    }).collect();
+    let plan = PlanFilter(box input.plan, predicate.clone());
+    Ok(Node::new(analyze, box Filter::new(input.op, predicate), plan,
+                 format!("Filter {}", labels.connect(" AND ")), estimate, vec![input.tree]))
+}
+
+/// The key value if `conjunct` is `column = constant` for the column at
+/// position `k`.
+fn point_predicate(scope: &[ScopeColumn], k: uint, conjunct: &SqlExpr) -> Option<Value> {
+    let (left, right) = match *conjunct {
+        SqlCompare(ref left, IsEqual, ref right) => (&**left, &**right),
+        _ => return None
+    };
+    let (table, name, constant) = match (left, right) {
+        (&SqlColumn(ref table, ref name), constant) |
+        (constant, &SqlColumn(ref table, ref name)) => (table, name, constant),
+        _ => return None
+    };
+    if resolve_column(scope, table, name[]) != Ok(k) {
+        return None;
+    }
+    let column = &scope[k].column;
+    match (&column.ty, constant) {
+        (&IntegerType, &SqlNumber(ref text)) => match number(text[]) {
+            Ok(IntValue(i)) => Some(IntValue(i)),
+            _ => None
+        },
+        (&CharType(_), &SqlString(_)) | (&VarcharType(_), &SqlString(_)) |
+        (&TimestampType, &SqlString(_)) => typed_literal(column, constant).ok().and_then(|v| v),
+        // numeric keys would have to match the key's precision exactly
+        _ => None
+    }
+}
+
+/// Joins all inputs, see the module documentation for the order. Edges used
+/// as join keys are removed from `edges`.
+fn join<'a>(analyze: bool, mut inputs: Vec<Option<Input<'a>>>, edges: &mut Vec<JoinEdge>)
+        -> Result<Input<'a>, String> {
+    let first = smallest(inputs[], |_| true).unwrap();
+    let mut result = inputs.get_mut(first).take().unwrap();
+    let mut joined = vec![first];
+
+    while joined.len() < inputs.len() {
+        let next = {
+            let connected = |t: uint| edges.iter().any(|edge| {
+                (edge.right_table == t && joined.contains(&edge.left_table))
+                    || (edge.left_table == t && joined.contains(&edge.right_table))
+            });
+            smallest(inputs[], connected).or_else(|| smallest(inputs[], |_| true)).unwrap()
+        };
+        let Input { node, scope } = inputs.get_mut(next).take().unwrap();
+
+        let mut left_keys = Vec::new();
+        let mut right_keys = Vec::new();
+        let mut conditions = Vec::new();
+        for edge in mem::replace(edges, Vec::new()).into_iter() {
+            let (outer, inner) = if edge.right_table == next && joined.contains(&edge.left_table) {
+                (&edge.left, &edge.right)
+            } else if edge.left_table == next && joined.contains(&edge.right_table) {
+                (&edge.right, &edge.left)
+            } else {
+                edges.push(edge);
+                continue;
+            };
+            left_keys.push(try!(column_position(result.scope[], outer)));
+            right_keys.push(try!(column_position(scope[], inner)));
+            conditions.push(format!("{} = {}", outer, inner));
+        }
+
+        // without join keys this is a cross product
+        let (label, estimate) = if conditions.is_empty() {
+            ("Hash Join (cross product)".to_string(), result.node.estimate() * node.estimate())
+        } else {
+            (format!("Hash Join on {}", conditions.connect(" AND ")),
+             max(result.node.estimate(), node.estimate()))
+        };
+        let plan = PlanHashJoin(box result.node.plan, box node.plan, left_keys.clone(),
+                                right_keys.clone());
+        let op = box HashJoin::new(result.node.op, node.op, left_keys, right_keys);
+        result.node = Node::new(analyze, op, plan, label, estimate,
+                                vec![result.node.tree, node.tree]);
+        result.scope.extend(scope.into_iter());
+        joined.push(next);
+    }
+    Ok(result)
+}
+
+/// Position of the remaining input with the smallest estimate for which
+/// `pred` holds.
+fn smallest(inputs: &[Option<Input>], pred: |uint| -> bool) -> Option<uint> {
+    let mut result: Option<(uint, uint)> = None;
+    for (i, input) in inputs.iter().enumerate() {
+        if let Some(ref input) = *input {
+            let estimate = input.node.estimate();
+            if pred(i) && result.map_or(true, |(_, smallest)| estimate < smallest) {
+                result = Some((i, estimate));
+            }
+        }
+    }
+    result.map(|(i, _)| i)
+}
+
+fn column_position(scope: &[ScopeColumn], e: &SqlExpr) -> Result<uint, String> {
+    match *e {
+        SqlColumn(ref table, ref name) => resolve_column(scope, table, name[]),
+        _ => unreachable!()
+    }
+}
+
+/// Aggregation, the select list, `ORDER BY` and `LIMIT`.
+fn output<'a>(analyze: bool, input: Node<'a>, scope: Vec<ScopeColumn>, select: &Select)
+        -> Result<Plan<'a>, String> {
+    let aggregated = !select.group_by.is_empty() || select.items.iter().any(|item| match *item {
+        SelectExpr(ref e, _) => has_aggregate(e),
+        Wildcard => false
+    });
+    let mut grouping = if aggregated {
+        let mut keys = Vec::new();
+        for e in select.group_by.iter() {
+            keys.push(try!(resolve(scope[], e)));
+        }
+        Some(Grouping { keys: keys, aggregates: Vec::new() })
+    } else {
+        None
+    };
+
+    let mut names = Vec::new();
+    let mut exprs = Vec::new();
+    let mut labels = Vec::new();
+    for item in select.items.iter() {
+        match *item {
+            Wildcard => {
+                if grouping.is_some() {
+                    return Err("SELECT * is not allowed with aggregates".to_string());
+                }
+                // in FROM order, the joins may have reordered the tables
+                let mut columns: Vec<uint> = range(0, scope.len()).collect();
+                columns.sort_by(|&a, &b| scope[a].table.cmp(&scope[b].table));
+                for &i in columns.iter() {
+                    names.push(scope[i].column.name.clone());
+                    exprs.push(ColumnRef(i));
+                }
+                labels.push("*".to_string());
+            },
+            SelectExpr(ref e, ref alias) => {
+                exprs.push(try!(output_expr(scope[], &mut grouping, e)));
+                names.push(alias.clone().unwrap_or_else(|| output_name(e)));
+                labels.push(match *alias {
+                    Some(ref alias) => format!("{} AS {}", e, alias),
+                    None => e.to_string()
+                });
+            }
+        }
+    }
+
+    let mut sort_keys = Vec::new();
+    let mut sort_labels = Vec::new();
+    for item in select.order_by.iter() {
+        let column = try!(order_column(scope[], &mut grouping, names[], exprs[], &item.expr));
+        sort_keys.push(SortKey { column: column, descending: item.descending });
+        sort_labels.push(format!("{}{}", names[column], if item.descending { " DESC" } else { "" }));
+    }
+
+    let mut node = input;
+    if let Some(grouping) = grouping {
+        // compute the group keys and aggregate arguments, then aggregate
+        let mut arguments = grouping.keys.clone();
+        let mut functions = Vec::new();
+        for (i, &(ref name, ref argument)) in grouping.aggregates.iter().enumerate() {
+            let column = grouping.keys.len() + i;
+            arguments.push(argument.clone().unwrap_or(Literal(NullValue)));
+            functions.push(match (name[], argument.is_some()) {
+                ("count", false) => Count,
+                ("count", true) => CountColumn(column),
+                ("sum", _) => Sum(column),
+                ("min", _) => Min(column),
+                _ => Max(column)
+            });
+        }
+        let (label, estimate) = if select.group_by.is_empty() {
+            ("Aggregate".to_string(), 1)
+        } else {
+            let keys: Vec<String> = select.group_by.iter().map(|e| e.to_string()).collect();
+            (format!("Aggregate by {}", keys.connect(", ")), max(1, (node.estimate() + 9) / 10))
+        };
+        let group_by: Vec<uint> = range(0, grouping.keys.len()).collect();
+        let plan = PlanAggregate(box PlanProject(box node.plan, arguments.clone()),
+                                 group_by.clone(), functions.clone());
+        let op = box Aggregate::new(box Project::new(node.op, arguments), group_by, functions);
+        node = Node::new(analyze, op, plan, label, estimate, vec![node.tree]);
+    }
+
+    let estimate = node.estimate();
+    let plan = PlanProject(box node.plan, exprs.clone());
+    node = Node::new(analyze, box Project::new(node.op, exprs), plan,
+                     format!("Project {}", labels.connect(", ")), estimate, vec![node.tree]);
+    if !sort_keys.is_empty() {
+        let plan = PlanSort(box node.plan, sort_keys.clone());
+        node = Node::new(analyze, box Sort::new(node.op, sort_keys), plan,
+                         format!("Sort on {}", sort_labels.connect(", ")), estimate,
+                         vec![node.tree]);
+    }
+    if let Some(limit) = select.limit {
+        node = Node::new(analyze, box Limit::new(node.op, limit), PlanLimit(box node.plan, limit),
+                         format!("Limit {}", limit), min(limit, estimate), vec![node.tree]);
+    }
+    Ok(Plan { columns: names, root: node.op, physical: node.plan, tree: node.tree })
+}
+
+/// The `GROUP BY` expressions and the aggregates (function name and argument,
+/// `None` for `count(*)`) of an aggregating query. Its output has a column
+/// per key followed by one per aggregate.
+struct Grouping {
+    keys: Vec<Expr>,
+    aggregates: Vec<(String, Option<Expr>)>,
+}
+
+impl Grouping {
+    /// `e` in terms of the aggregation output.
+    fn expr(&mut self, scope: &[ScopeColumn], e: &SqlExpr) -> Result<Expr, String> {
+        if let SqlAggregate(ref name, ref argument) = *e {
+            let argument = match *argument {
+                Some(ref argument) if has_aggregate(&**argument) =>
+                    return Err("aggregate function calls cannot be nested".to_string()),
+                Some(ref argument) => Some(try!(resolve(scope, &**argument))),
+                None => None
+            };
+            let aggregate = (name.clone(), argument);
+            let existing = self.aggregates.iter().position(|other| *other == aggregate);
+            let pos = match existing {
+                Some(pos) => pos,
+                None => {
+                    self.aggregates.push(aggregate);
+                    self.aggregates.len() - 1
+                }
+            };
+            return Ok(ColumnRef(self.keys.len() + pos));
+        }
+
+        if let Ok(resolved) = resolve(scope, e) {
+            if let Some(pos) = self.keys.iter().position(|key| *key == resolved) {
+                return Ok(ColumnRef(pos));
+            }
+        }
+        match *e {
+            SqlColumn(_, ref name) => {
+                try!(resolve(scope, e));
+                Err(format!("column {} must appear in the GROUP BY clause or be used in an \
+                             aggregate function", name))
+            },
+            SqlArithmetic(ref left, op, ref right) =>
+                Ok(Arithmetic(box try!(self.expr(scope, &**left)), op,
+                              box try!(self.expr(scope, &**right)))),
+            SqlCompare(ref left, op, ref right) =>
+                Ok(Comparison(box try!(self.expr(scope, &**left)), op,
+                              box try!(self.expr(scope, &**right)))),
+            SqlAnd(ref left, ref right) =>
+                Ok(And(box try!(self.expr(scope, &**left)), box try!(self.expr(scope, &**right)))),
+            SqlOr(ref left, ref right) =>
+                Ok(Or(box try!(self.expr(scope, &**left)), box try!(self.expr(scope, &**right)))),
+            SqlNot(ref e) => Ok(Not(box try!(self.expr(scope, &**e)))),
+            SqlIsNull(ref e) => Ok(IsNull(box try!(self.expr(scope, &**e)))),
+            _ => resolve(scope, e)
+        }
+    }
+}
+
+fn output_expr(scope: &[ScopeColumn], grouping: &mut Option<Grouping>, e: &SqlExpr)
+        -> Result<Expr, String> {
+    match *grouping {
+        Some(ref mut grouping) => grouping.expr(scope, e),
+        None => resolve(scope, e)
+    }
+}
+
+/// Position in the select list an `ORDER BY` item refers to.
+fn order_column(scope: &[ScopeColumn], grouping: &mut Option<Grouping>, names: &[String],
+                exprs: &[Expr], e: &SqlExpr) -> Result<uint, String> {
+    match *e {
+        SqlNumber(ref text) => match from_str::<uint>(text[]) {
+            Some(n) if n >= 1 && n <= names.len() => return Ok(n - 1),
+            _ => return Err(format!("ORDER BY position {} is not in select list", text))
+        },
+        SqlColumn(None, ref name) if names.iter().filter(|n| *n == name).count() == 1 =>
+            return Ok(names.iter().position(|n| n == name).unwrap()),
+        _ => {}
+    }
+    let aggregates = grouping.as_ref().map_or(0, |grouping| grouping.aggregates.len());
+    let expr = try!(output_expr(scope, grouping, e));
+    let position = exprs.iter().position(|other| *other == expr);
+    match position {
+        Some(i) if grouping.as_ref().map_or(0, |g| g.aggregates.len()) == aggregates => Ok(i),
+        _ => Err("ORDER BY expressions must appear in the select list".to_string())
+    }
+}
+
+fn has_aggregate(e: &SqlExpr) -> bool {
+    match *e {
+        SqlAggregate(..) => true,
+        SqlArithmetic(ref left, _, ref right) | SqlCompare(ref left, _, ref right) |
+        SqlAnd(ref left, ref right) | SqlOr(ref left, ref right) =>
+            has_aggregate(&**left) || has_aggregate(&**right),
+        SqlNot(ref e) | SqlIsNull(ref e) => has_aggregate(&**e),
+        SqlColumn(..) | SqlNumber(..) | SqlString(..) | SqlNull => false
+    }
+}
+
+/// Column name of a select item without alias, as PostgreSQL names them.
+fn output_name(e: &SqlExpr) -> String {
+    match *e {
+        SqlColumn(_, ref name) | SqlAggregate(ref name, _) => name.clone(),
+        _ => "?column?".to_string()
+    }
+}
+
+fn resolve_column(scope: &[ScopeColumn], table: &Option<String>, name: &str)
+        -> Result<uint, String> {
+    let matches: Vec<uint> = scope.iter().enumerate()
+        .filter(|&(_, c)| c.column.name[] == name
+                && table.as_ref().map_or(true, |table| c.alias == *table))
+        .map(|(i, _)| i)
+        .collect();
+    match matches[] {
+        [i] => Ok(i),
+        [] => Err(match *table {
+            Some(ref table) => format!("column {}.{} does not exist", table, name),
+            None => format!("column {} does not exist", name)
+        }),
+        _ => Err(format!("column reference {} is ambiguous", name))
+    }
+}
+
+/// The conjunction of `conjuncts`.
+fn resolve_all(scope: &[ScopeColumn], conjuncts: &[SqlExpr]) -> Result<Expr, String> {
+    let mut result = try!(resolve(scope, &conjuncts[0]));
+    for conjunct in conjuncts[1..].iter() {
+        result = And(box result, box try!(resolve(scope, conjunct)));
+    }
+    Ok(result)
+}
+
+fn resolve(scope: &[ScopeColumn], e: &SqlExpr) -> Result<Expr, String> {
+    Ok(match *e {
+        SqlColumn(ref table, ref name) => ColumnRef(try!(resolve_column(scope, table, name[]))),
+        SqlNumber(ref text) => Literal(try!(number(text[]))),
+        SqlString(ref text) => Literal(TextValue(text.clone())),
+        SqlNull => Literal(NullValue),
+        SqlArithmetic(ref left, op, ref right) =>
+            Arithmetic(box try!(resolve(scope, &**left)), op, box try!(resolve(scope, &**right))),
+        SqlCompare(ref left, op, ref right) => {
+            let (left, right) = try!(coerce(scope, &**left, &**right));
+            Comparison(box left, op, box right)
+        },
+        SqlAnd(ref left, ref right) =>
+            And(box try!(resolve(scope, &**left)), box try!(resolve(scope, &**right))),
+        SqlOr(ref left, ref right) =>
+            Or(box try!(resolve(scope, &**left)), box try!(resolve(scope, &**right))),
+        SqlNot(ref e) => Not(box try!(resolve(scope, &**e))),
+        SqlIsNull(ref e) => IsNull(box try!(resolve(scope, &**e))),
+        SqlAggregate(ref name, _) =>
+            return Err(format!("aggregate function {} is not allowed here", name))
+    })
+}
+
+/// Resolves both sides of a comparison, giving a literal compared with a
+/// column that column's type. So `c_state = 'CA'` compares padded `char(2)`
+/// values and `o_entry_d < '2014-01-01'` timestamps.
+fn coerce(scope: &[ScopeColumn], left: &SqlExpr, right: &SqlExpr)
+        -> Result<(Expr, Expr), String> {
+    let mut left_expr = try!(resolve(scope, left));
+    let mut right_expr = try!(resolve(scope, right));
+    if let ColumnRef(i) = left_expr {
+        if let Some(value) = try!(typed_literal(&scope[i].column, right)) {
+            right_expr = Literal(value);
+        }
+    }
+    if let ColumnRef(i) = right_expr {
+        if let Some(value) = try!(typed_literal(&scope[i].column, left)) {
+            left_expr = Literal(value);
+        }
+    }
+    Ok((left_expr, right_expr))
+}
+
+/// `literal` as a value of `column`'s type, `None` if it needs no conversion.
+/// A string longer than a `char(n)` or `varchar(n)` column is kept as it is:
+/// it equals no value of the column and compares as a string otherwise.
+fn typed_literal(column: &Column, literal: &SqlExpr) -> Result<Option<Value>, String> {
+    let is_text = match column.ty {
+        CharType(_) | VarcharType(_) | TimestampType => true,
+        _ => false
+    };
+    match (&column.ty, literal) {
+        (&CharType(len), &SqlString(ref text)) | (&VarcharType(len), &SqlString(ref text))
+                if text.len() > len =>
+            return Ok(Some(TextValue(text.clone()))),
+        _ => {}
+    }
+    match *literal {
+        SqlString(ref text) if is_text => {
+            let not_null = Column { nullable: false, ..column.clone() };
+            Value::parse(&not_null, text[]).map(|value| Some(value)).map_err(|e| {
+                format!("invalid input '{}' for column {}: {}", text, column.name, e)
+            })
+        },
+        SqlString(ref text) =>
+            Err(format!("cannot compare column {} of type {} with '{}'", column.name, column.ty,
+                        text)),
+        SqlNumber(ref text) if is_text =>
+            Err(format!("cannot compare column {} of type {} with {}", column.name, column.ty,
+                        text)),
+        _ => Ok(None)
+    }
+}
+
+/// Integers without a decimal point, numerics with as many fraction digits as
+/// given.
+fn number(text: &str) -> Result<Value, String> {
+    match text.find('.') {
+        None => from_str(text).map(IntValue).ok_or(format!("invalid number {}", text)),
+        Some(dot) => Numeric::from_str(text, 18, text.len() - dot - 1)
+            .map(NumericValue)
+            .map_err(|e| format!("invalid number {}: {}", text, e))
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use catalog::Catalog;
+    use ddl;
+    use dynamic::{DynTable, Value, IntValue, TextValue, NumericValue};
+    use numeric::Numeric;
+    use query::{Database, Relation};
+
+    use super::query;
+
+    struct TestDatabase {
+        tables: HashMap<String, DynTable>,
+    }
+
+    impl Database for TestDatabase {
+        fn relation(&self, name: &str) -> Option<&Relation> {
+            self.tables.find_equiv(&name).map(|table| table as &Relation)
+        }
+    }
+
+    fn database() -> (Catalog, TestDatabase) {
+        let catalog = ddl::parse("
+            create table district (d_w_id int, d_id int, d_name varchar(10) not null,
+                                   primary key (d_w_id, d_id));
+            create table customer (c_w_id int, c_d_id int, c_id int, c_state char(2),
+                                   c_balance numeric(6, 2) not null,
+                                   primary key (c_w_id, c_d_id, c_id));
+        ").unwrap();
+        let mut db = TestDatabase { tables: HashMap::new() };
+        for &(name, ref rows) in [("district", vec!["1|1|north", "1|2|south"]),
+                             ("customer", vec!["1|1|1|CA|10.00", "1|1|2||-5.50",
+                                               "1|2|1|NY|7.25"])].iter() {
+            let mut table = DynTable::new(catalog.table(name).unwrap().clone());
+            for row in rows.iter() {
+                let row = table.parse_row(row.split('|')).unwrap();
+                table.insert(row);
+            }
+            db.tables.insert(name.to_string(), table);
+        }
+        (catalog, db)
+    }
+
+    fn run(sql: &str) -> Vec<Vec<Value>> {
+        let (catalog, db) = database();
+        let (_, rows) = query(&catalog, &db, sql).unwrap();
+        rows
+    }
+
+    fn text(s: &str) -> Value {
+        TextValue(s.to_string())
+    }
+
+    #[test]
+    fn test_point_query() {
+        assert_eq!(run("select c_balance from customer where c_id = 2 and c_d_id = 1 \
+                        and c_w_id = 1"),
+                   vec![vec![NumericValue(Numeric::new(-550, 6, 2))]])
+        assert_eq!(run("select * from customer where c_w_id = 1 and c_d_id = 9 and c_id = 1"),
+                   Vec::<Vec<Value>>::new())
+    }
+
+    #[test]
+    fn test_filter_on_char() {
+        assert_eq!(run("select c_id from customer where c_state = 'NY' or c_state is null \
+                        order by c_id desc"),
+                   vec![vec![IntValue(2)], vec![IntValue(1)]])
+    }
+
+    #[test]
+    fn test_literal_longer_than_column() {
+        assert_eq!(run("select c_id from customer where c_state = 'CAL'"),
+                   Vec::<Vec<Value>>::new())
+        assert_eq!(run("select c_id from customer where c_state <> 'CAL' order by c_d_id"),
+                   vec![vec![IntValue(1)], vec![IntValue(1)]])
+        assert_eq!(run("select d_id from district where d_name < 'northernmost'"),
+                   vec![vec![IntValue(1)]])
+        assert_eq!(run("select c_id from customer where c_w_id = 1 and c_d_id = 1 \
+                        and c_id = 1 and c_state = 'CAL'"),
+                   Vec::<Vec<Value>>::new())
+    }
+
+    #[test]
+    fn test_mixed_precision() {
+        assert_eq!(run("select c_d_id, 2 * c_balance from customer where c_balance > 7 \
+                        order by c_d_id"),
+                   vec![vec![IntValue(1), NumericValue(Numeric::new(2000, 6, 2))],
+                        vec![IntValue(2), NumericValue(Numeric::new(1450, 6, 2))]])
+        assert_eq!(run("select c_id from customer where c_balance > 0 and c_d_id = 1"),
+                   vec![vec![IntValue(1)]])
+    }
+
+    #[test]
+    fn test_join_group_order() {
+        assert_eq!(run("select d.d_name, count(*), sum(c_balance) as total \
+                        from customer c join district d on c_d_id = d_id and c_w_id = d_w_id \
+                        group by d.d_name order by total"),
+                   vec![vec![text("north"), IntValue(2), NumericValue(Numeric::new(450, 6, 2))],
+                        vec![text("south"), IntValue(1), NumericValue(Numeric::new(725, 6, 2))]])
+    }
+
+    #[test]
+    fn test_limit() {
+        assert_eq!(run("select d_id from district, customer where d_id = c_d_id \
+                        order by 1 limit 1"),
+                   vec![vec![IntValue(1)]])
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(run("explain select d_name, count(*) from customer c join district d \
+                        on c_d_id = d_id and c_w_id = d_w_id where c_state = 'CA' \
+                        group by d_name"),
+                   vec![vec![text("Project d_name, count(*)  (rows=1)")],
+                        vec![text("  ->  Aggregate by d_name  (rows=1)")],
+                        vec![text("        ->  Hash Join on c_d_id = d_id AND c_w_id = d_w_id  \
+                                   (rows=2)")],
+                        vec![text("              ->  Filter c_state = 'CA'  (rows=1)")],
+                        vec![text("                    ->  Seq Scan on customer c  (rows=3)")],
+                        vec![text("              ->  Seq Scan on district d  (rows=2)")]])
+        let lines = run("explain analyze select c_balance from customer \
+                         where c_w_id = 1 and c_d_id = 1 and c_id = 2");
+        let lines: Vec<String> = lines.iter().map(|row| row[0].to_string()).collect();
+        assert_eq!(lines.len(), 3)
+        assert!(lines[0].starts_with("Project c_balance  (rows=1) (actual rows=1 time="))
+        assert!(lines[1].starts_with("  ->  Index Lookup on customer using primary key \
+                                      (c_w_id = 1, c_d_id = 1, c_id = 2)  (rows=1) (actual rows=1"))
+        assert!(lines[2].starts_with("Execution time: "))
+    }
+
+    #[test]
+    fn test_errors() {
+        let (catalog, db) = database();
+        for sql in ["select * from nation",
+                    "select c_foo from customer",
+                    "select c_id from customer c, customer d",
+                    "select c_id, count(*) from customer",
+                    "select c_id from customer order by c_balance",
+                    "select * from customer where c_state = 5",
+                    "select * from customer where sum(c_id) > 1"].iter() {
+            assert!(query(&catalog, &db, *sql).is_err(), "{}", sql)
+        }
+    }
+}
@@ -0,0 +1,759 @@
+//! Pull based query operators over dynamic `Row`s: every operator produces
+//! its rows one at a time from `next`, pulling them from its inputs.
+//!
+//! ```ignore
+//! // sum(ol_amount) per district of warehouse 1, largest first
+//! let scan = box Scan::new(tables.orderline_table.scan());
+//! let filter = box Filter::new(scan, Comparison(box ColumnRef(2), IsEqual,
+//!                                               box Literal(IntValue(1))));
+//! let aggregate = box Aggregate::new(filter, vec![1], vec![Sum(8)]);
+//! let sort = box Sort::new(aggregate, vec![SortKey { column: 1, descending: true }]);
+//! for row in rows(&mut *sort).iter() { ... }
+//! ```
+//!
+//! Columns are referred to by position, a join's output being the left row
+//! followed by the right row.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::slice::Items;
+use std::vec::MoveItems;
+
+use collections::hash::Hash;
+use time::precise_time_ns;
+
+use super::dynamic::{DynTable, Row, FromValue, ToRow, Value, NullValue, IntValue, NumericValue,
+                     TextValue, TimestampValue};
+use super::null::{Truth, Unknown};
+use super::numeric::Numeric;
+use super::table::{PrimaryKey, Table};
+
+
+pub trait Operator {
+    /// The next row, `None` once all rows were produced.
+    fn next(&mut self) -> Option<Row>;
+}
+
+/// Runs `op` to completion.
+pub fn rows(op: &mut Operator) -> Vec<Row> {
+    let mut rows = Vec::new();
+    loop {
+        match op.next() {
+            Some(row) => rows.push(row),
+            None => return rows
+        }
+    }
+}
+
+
+/// A table as seen by the planner, with rows in schema column order.
+pub trait Relation {
+    fn scan<'a>(&'a self) -> Box<Operator + 'a>;
+    /// The row whose primary key columns (in key order) are `key`.
+    fn lookup(&self, key: &Row) -> Option<Row>;
+    fn size(&self) -> uint;
+}
+
+/// The tables queries can refer to, by name.
+pub trait Database {
+    fn relation(&self, name: &str) -> Option<&Relation>;
+}
+
+/// Conversion of the key columns of a `Relation::lookup` to the key type of a
+/// `Table`.
+pub trait FromKey {
+    fn from_key(key: &Row) -> Option<Self>;
+}
+
+impl FromKey for i32 {
+    fn from_key(key: &Row) -> Option<i32> {
+        match key[] {
+            [ref a] => FromValue::from_value(a),
+            _ => None
+        }
+    }
+}
+
+impl<A: FromValue, B: FromValue> FromKey for (A, B) {
+    fn from_key(key: &Row) -> Option<(A, B)> {
+        match key[] {
+            [ref a, ref b] => match (FromValue::from_value(a), FromValue::from_value(b)) {
+                (Some(a), Some(b)) => Some((a, b)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+impl<A: FromValue, B: FromValue, C: FromValue> FromKey for (A, B, C) {
+    fn from_key(key: &Row) -> Option<(A, B, C)> {
+        match key[] {
+            [ref a, ref b, ref c] => match (FromValue::from_value(a), FromValue::from_value(b),
+                                            FromValue::from_value(c)) {
+                (Some(a), Some(b), Some(c)) => Some((a, b, c)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+impl<A: FromValue, B: FromValue, C: FromValue, D: FromValue> FromKey for (A, B, C, D) {
+    fn from_key(key: &Row) -> Option<(A, B, C, D)> {
+        match key[] {
+            [ref a, ref b, ref c, ref d] => match (FromValue::from_value(a),
+                                                   FromValue::from_value(b),
+                                                   FromValue::from_value(c),
+                                                   FromValue::from_value(d)) {
+                (Some(a), Some(b), Some(c), Some(d)) => Some((a, b, c, d)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+impl<Data: ToRow + PrimaryKey<Index> + Clone, Index: Eq + Hash + FromKey> Relation
+        for Table<Data, Index> {
+    fn scan<'a>(&'a self) -> Box<Operator + 'a> {
+        box Scan::new(self.scan()) as Box<Operator + 'a>
+    }
+
+    fn lookup(&self, key: &Row) -> Option<Row> {
+        let key: Option<Index> = FromKey::from_key(key);
+        key.and_then(|key| self.find(&key)).map(|data| data.to_row())
+    }
+
+    fn size(&self) -> uint {
+        self.size()
+    }
+}
+
+impl Relation for DynTable {
+    fn scan<'a>(&'a self) -> Box<Operator + 'a> {
+        box Scan::new(self.scan()) as Box<Operator + 'a>
+    }
+
+    fn lookup(&self, key: &Row) -> Option<Row> {
+        self.find(key).map(|row| row.clone())
+    }
+
+    fn size(&self) -> uint {
+        self.size()
+    }
+}
+
+
+#[deriving(Clone, PartialEq)]
+pub enum CompareOp {
+    IsEqual,
+    IsNotEqual,
+    IsLess,
+    IsLessOrEqual,
+    IsGreater,
+    IsGreaterOrEqual,
+}
+
+impl CompareOp {
+    fn matches(&self, ordering: Ordering) -> bool {
+        match *self {
+            IsEqual => ordering == Equal,
+            IsNotEqual => ordering != Equal,
+            IsLess => ordering == Less,
+            IsLessOrEqual => ordering != Greater,
+            IsGreater => ordering == Greater,
+            IsGreaterOrEqual => ordering != Less,
+        }
+    }
+}
+
+impl fmt::Show for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            IsEqual => "=",
+            IsNotEqual => "<>",
+            IsLess => "<",
+            IsLessOrEqual => "<=",
+            IsGreater => ">",
+            IsGreaterOrEqual => ">=",
+        })
+    }
+}
+
+#[deriving(Clone, PartialEq)]
+pub enum ArithmeticOp {
+    Addition,
+    Subtraction,
+    Multiplication,
+}
+
+impl fmt::Show for ArithmeticOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            Addition => "+",
+            Subtraction => "-",
+            Multiplication => "*",
+        })
+    }
+}
+
+#[deriving(Clone, PartialEq)]
+pub enum Expr {
+    ColumnRef(uint),
+    Literal(Value),
+    Arithmetic(Box<Expr>, ArithmeticOp, Box<Expr>),
+    Comparison(Box<Expr>, CompareOp, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    IsNull(Box<Expr>),
+}
+
+impl Expr {
+    /// Value of a scalar expression, fails for predicates.
+    pub fn eval(&self, row: &Row) -> Value {
+        match *self {
+            ColumnRef(i) => row[i].clone(),
+            Literal(ref value) => value.clone(),
+            Arithmetic(ref left, op, ref right) => arithmetic(&left.eval(row), op,
+                                                              &right.eval(row)),
+            _ => fail!("predicate used as a value")
+        }
+    }
+
+    /// Value of a predicate, fails for scalar expressions.
+    pub fn truth(&self, row: &Row) -> Truth {
+        match *self {
+            Comparison(ref left, op, ref right) => {
+                match compare_values(&left.eval(row), &right.eval(row)) {
+                    Some(ordering) => Truth::from_bool(op.matches(ordering)),
+                    None => Unknown
+                }
+            },
+            And(ref left, ref right) => left.truth(row).and(right.truth(row)),
+            Or(ref left, ref right) => left.truth(row).or(right.truth(row)),
+            Not(ref e) => e.truth(row).not(),
+            IsNull(ref e) => Truth::from_bool(e.eval(row).is_null()),
+            _ => fail!("value used as a predicate")
+        }
+    }
+}
+
+/// Integers mixed with numerics are treated as numerics of precision 0.
+fn as_numeric(value: &Value) -> Option<Numeric> {
+    match *value {
+        IntValue(i) => Some(Numeric::new(i, 18, 0)),
+        NumericValue(n) => Some(n),
+        _ => None
+    }
+}
+
+/// Orders two values of compatible types, `None` if one of them is NULL.
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (&NullValue, _) | (_, &NullValue) => None,
+        (&IntValue(a), &IntValue(b)) => Some(a.cmp(&b)),
+        (&TextValue(ref a), &TextValue(ref b)) => Some(a.cmp(b)),
+        (&TimestampValue(a), &TimestampValue(b)) => Some(a.cmp(&b)),
+        _ => match (as_numeric(a), as_numeric(b)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => fail!("cannot compare {} with {}", a, b)
+        }
+    }
+}
+
+/// Total order for sorting: NULL comes after every value.
+fn sort_order(a: &Value, b: &Value) -> Ordering {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => Equal,
+        (true, false) => Greater,
+        (false, true) => Less,
+        (false, false) => compare_values(a, b).unwrap()
+    }
+}
+
+fn arithmetic(a: &Value, op: ArithmeticOp, b: &Value) -> Value {
+    match (a, b) {
+        (&NullValue, _) | (_, &NullValue) => NullValue,
+        (&IntValue(a), &IntValue(b)) => IntValue(match op {
+            Addition => a + b,
+            Subtraction => a - b,
+            Multiplication => a * b,
+        }),
+        _ => match (as_numeric(a), as_numeric(b)) {
+            (Some(a), Some(b)) => NumericValue(match op {
+                Addition => a + b,
+                Subtraction => a - b,
+                Multiplication => a * b,
+            }),
+            _ => fail!("cannot compute {} {} {}", a, op, b)
+        }
+    }
+}
+
+
+/// Produces the rows of a `Table` (or anything else iterating over `ToRow`s).
+pub struct Scan<'a, Data: 'a> {
+    rows: Items<'a, Data>,
+}
+
+impl<'a, Data: ToRow> Scan<'a, Data> {
+    pub fn new(rows: Items<'a, Data>) -> Scan<'a, Data> {
+        Scan { rows: rows }
+    }
+}
+
+impl<'a, Data: ToRow> Operator for Scan<'a, Data> {
+    fn next(&mut self) -> Option<Row> {
+        self.rows.next().map(|data| data.to_row())
+    }
+}
+
+impl ToRow for Row {
+    fn to_row(&self) -> Row {
+        self.clone()
+    }
+}
+
+/// The row of a primary key lookup, if it exists.
+pub struct IndexLookup<'a> {
+    relation: &'a (Relation + 'a),
+    key: Option<Row>,
+}
+
+impl<'a> IndexLookup<'a> {
+    pub fn new(relation: &'a Relation, key: Row) -> IndexLookup<'a> {
+        IndexLookup { relation: relation, key: Some(key) }
+    }
+}
+
+impl<'a> Operator for IndexLookup<'a> {
+    fn next(&mut self) -> Option<Row> {
+        self.key.take().and_then(|key| self.relation.lookup(&key))
+    }
+}
+
+/// The input rows for which `predicate` is `True`.
+pub struct Filter<'a> {
+    input: Box<Operator + 'a>,
+    predicate: Expr,
+}
+
+impl<'a> Filter<'a> {
+    pub fn new(input: Box<Operator + 'a>, predicate: Expr) -> Filter<'a> {
+        Filter { input: input, predicate: predicate }
+    }
+}
+
+impl<'a> Operator for Filter<'a> {
+    fn next(&mut self) -> Option<Row> {
+        loop {
+            match self.input.next() {
+                Some(row) => if self.predicate.truth(&row).is_true() {
+                    return Some(row);
+                },
+                None => return None
+            }
+        }
+    }
+}
+
+/// Evaluates an expression per output column.
+pub struct Project<'a> {
+    input: Box<Operator + 'a>,
+    exprs: Vec<Expr>,
+}
+
+impl<'a> Project<'a> {
+    pub fn new(input: Box<Operator + 'a>, exprs: Vec<Expr>) -> Project<'a> {
+        Project { input: input, exprs: exprs }
+    }
+}
+
+impl<'a> Operator for Project<'a> {
+    fn next(&mut self) -> Option<Row> {
+        self.input.next().map(|row| self.exprs.iter().map(|e| e.eval(&row)).collect())
+    }
+}
+
+/// Inner equi-join: builds a hash table on the right input (on the first
+/// call to `next`) and probes it with the rows of the left input.
+pub struct HashJoin<'a> {
+    left: Box<Operator + 'a>,
+    right: Box<Operator + 'a>,
+    left_keys: Vec<uint>,
+    right_keys: Vec<uint>,
+    table: Option<HashMap<Row, Vec<Row>>>,
+    /// The current left row, its key and the position of its next match.
+    current: Option<(Row, Row, uint)>,
+}
+
+impl<'a> HashJoin<'a> {
+    pub fn new(left: Box<Operator + 'a>, right: Box<Operator + 'a>, left_keys: Vec<uint>,
+               right_keys: Vec<uint>) -> HashJoin<'a> {
+        assert_eq!(left_keys.len(), right_keys.len());
+        HashJoin {
+            left: left,
+            right: right,
+            left_keys: left_keys,
+            right_keys: right_keys,
+            table: None,
+            current: None,
+        }
+    }
+
+    fn build(&mut self) {
+        let mut table = HashMap::new();
+        loop {
+            let row = match self.right.next() {
+                Some(row) => row,
+                None => break
+            };
+            // NULL never equals anything
+            if let Some(key) = key_of(&row, self.right_keys[]) {
+                table.find_or_insert_with(key, |_| Vec::new()).push(row);
+            }
+        }
+        self.table = Some(table);
+    }
+}
+
+fn key_of(row: &Row, columns: &[uint]) -> Option<Row> {
+    let key: Row = columns.iter().map(|&i| row[i].clone()).collect();
+    if key.iter().any(|value| value.is_null()) {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+impl<'a> Operator for HashJoin<'a> {
+    fn next(&mut self) -> Option<Row> {
+        if self.table.is_none() {
+            self.build();
+        }
+        let table = self.table.as_ref().unwrap();
+        loop {
+            if let Some((ref left, ref key, ref mut pos)) = self.current {
+                let matches = &table[*key];
+                if *pos < matches.len() {
+                    let mut row = left.clone();
+                    row.push_all(matches[*pos][]);
+                    *pos += 1;
+                    return Some(row);
+                }
+            }
+            let left = match self.left.next() {
+                Some(row) => row,
+                None => return None
+            };
+            self.current = match key_of(&left, self.left_keys[]) {
+                Some(ref key) if table.contains_key(key) => Some((left, key.clone(), 0)),
+                _ => None
+            };
+        }
+    }
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum AggregateFunction {
+    /// `count(*)`
+    Count,
+    /// Number of non-NULL values.
+    CountColumn(uint),
+    Sum(uint),
+    Min(uint),
+    Max(uint),
+}
+
+/// Groups the input by the `group_by` columns and produces one row per group
+/// holding the group columns followed by the aggregates, groups being in
+/// order of their first row. Without `group_by` a single row is produced even
+/// for empty input.
+pub struct Aggregate<'a> {
+    input: Box<Operator + 'a>,
+    group_by: Vec<uint>,
+    aggregates: Vec<AggregateFunction>,
+    output: Option<MoveItems<Row>>,
+}
+
+impl<'a> Aggregate<'a> {
+    pub fn new(input: Box<Operator + 'a>, group_by: Vec<uint>,
+               aggregates: Vec<AggregateFunction>) -> Aggregate<'a> {
+        Aggregate { input: input, group_by: group_by, aggregates: aggregates, output: None }
+    }
+
+
+    fn run(&mut self) -> Vec<Row> {
+        let mut positions: HashMap<Row, uint> = HashMap::new();
+        let mut keys: Vec<Row> = Vec::new();
+        let mut states: Vec<Row> = Vec::new();
+        loop {
+            let row = match self.input.next() {
+                Some(row) => row,
+                None => break
+            };
+            let key: Row = self.group_by.iter().map(|&i| row[i].clone()).collect();
+            let existing = positions.find(&key).map(|&pos| pos);
+            let pos = match existing {
+                Some(pos) => pos,
+                None => {
+                    positions.insert(key.clone(), keys.len());
+                    keys.push(key);
+                    states.push(initial_states(self.aggregates[]));
+                    keys.len() - 1
+                }
+            };
+
+            for (aggregate, value) in self.aggregates.iter().zip(states.get_mut(pos).iter_mut()) {
+                *value = accumulate(aggregate, &*value, &row);
+            }
+        }
+        if keys.is_empty() && self.group_by.is_empty() {
+            keys.push(Vec::new());
+            states.push(initial_states(self.aggregates[]));
+        }
+        keys.into_iter().zip(states.into_iter()).map(|(mut key, state)| {
+            key.extend(state.into_iter());
+            key
+        }).collect()
+    }
+}
+
+/// The states of `aggregates` before the first row of a group.
+pub fn initial_states(aggregates: &[AggregateFunction]) -> Row {
+    aggregates.iter().map(|aggregate| match *aggregate {
+        Count | CountColumn(_) => IntValue(0),
+        _ => NullValue
+    }).collect()
+}
+
+/// The state of `aggregate` after `row`.
+pub fn accumulate(aggregate: &AggregateFunction, state: &Value, row: &Row) -> Value {
+    match *aggregate {
+        CountColumn(i) if row[i].is_null() => state.clone(),
+        Count | CountColumn(_) => match *state {
+            IntValue(n) => IntValue(n + 1),
+            _ => unreachable!()
+        },
+        Sum(i) if row[i].is_null() => state.clone(),
+        Sum(i) if state.is_null() => row[i].clone(),
+        Sum(i) => arithmetic(state, Addition, &row[i]),
+        Min(i) | Max(i) => {
+            let value = &row[i];
+            let replace = match compare_values(value, state) {
+                Some(ordering) => ordering == if *aggregate == Max(i) { Greater } else { Less },
+                None => state.is_null()
+            };
+            if replace { value.clone() } else { state.clone() }
+        }
+    }
+}
+
+impl<'a> Operator for Aggregate<'a> {
+    fn next(&mut self) -> Option<Row> {
+        if self.output.is_none() {
+            let rows = self.run();
+            self.output = Some(rows.into_iter());
+        }
+        self.output.as_mut().unwrap().next()
+    }
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct SortKey {
+    pub column: uint,
+    pub descending: bool,
+}
+
+/// Sorts the whole input (stably) on the first call to `next`.
+pub struct Sort<'a> {
+    input: Box<Operator + 'a>,
+    keys: Vec<SortKey>,
+    output: Option<MoveItems<Row>>,
+}
+
+impl<'a> Sort<'a> {
+    pub fn new(input: Box<Operator + 'a>, keys: Vec<SortKey>) -> Sort<'a> {
+        Sort { input: input, keys: keys, output: None }
+    }
+}
+
+impl<'a> Operator for Sort<'a> {
+    fn next(&mut self) -> Option<Row> {
+        if self.output.is_none() {
+            let mut input = rows(&mut *self.input);
+            sort_rows(&mut input, self.keys[]);
+            self.output = Some(input.into_iter());
+        }
+        self.output.as_mut().unwrap().next()
+    }
+}
+
+/// Sorts `rows` (stably) by `keys`.
+pub fn sort_rows(rows: &mut Vec<Row>, keys: &[SortKey]) {
+    rows.sort_by(|a, b| {
+        for key in keys.iter() {
+            let ordering = sort_order(&a[key.column], &b[key.column]);
+            if ordering != Equal {
+                return if key.descending { ordering.reverse() } else { ordering };
+            }
+        }
+        Equal
+    });
+}
+
+/// The first `limit` input rows.
+pub struct Limit<'a> {
+    input: Box<Operator + 'a>,
+    limit: uint,
+}
+
+impl<'a> Limit<'a> {
+    pub fn new(input: Box<Operator + 'a>, limit: uint) -> Limit<'a> {
+        Limit { input: input, limit: limit }
+    }
+}
+
+impl<'a> Operator for Limit<'a> {
+    fn next(&mut self) -> Option<Row> {
+        if self.limit == 0 {
+            return None;
+        }
+        self.limit -= 1;
+        self.input.next()
+    }
+}
+
+/// What `Measure` saw of its input.
+pub struct Stats {
+    pub rows: Cell<uint>,
+    /// Time spent producing the rows, including the time of the inputs.
+    pub nanos: Cell<u64>,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats { rows: Cell::new(0), nanos: Cell::new(0) }
+    }
+}
+
+/// Passes on the rows of `input`, counting them and the time it takes to
+/// produce them into `stats` (for `EXPLAIN ANALYZE`).
+pub struct Measure<'a> {
+    input: Box<Operator + 'a>,
+    stats: Rc<Stats>,
+}
+
+impl<'a> Measure<'a> {
+    pub fn new(input: Box<Operator + 'a>, stats: Rc<Stats>) -> Measure<'a> {
+        Measure { input: input, stats: stats }
+    }
+}
+
+impl<'a> Operator for Measure<'a> {
+    fn next(&mut self) -> Option<Row> {
+        let time = precise_time_ns();
+        let row = self.input.next();
+        self.stats.nanos.set(self.stats.nanos.get() + precise_time_ns() - time);
+        if row.is_some() {
+            self.stats.rows.set(self.stats.rows.get() + 1);
+        }
+        row
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use dynamic::{Row, Value, NullValue, IntValue, NumericValue, TextValue};
+    use numeric::Numeric;
+
+    use super::{rows, Operator, Scan, Filter, Project, HashJoin, Aggregate, Sort, Limit,
+                SortKey, ColumnRef, Literal, Arithmetic, Comparison, Or, IsNull, IsEqual,
+                IsGreater, Multiplication, Count, Sum, Max, compare_values, arithmetic};
+
+    fn text(s: &str) -> Value {
+        TextValue(s.to_string())
+    }
+
+    fn money(cents: i64) -> Value {
+        NumericValue(Numeric::new(cents, 6, 2))
+    }
+
+    /// (district, customer, amount)
+    fn orders() -> Vec<Row> {
+        vec![vec![IntValue(1), IntValue(10), money(500)],
+             vec![IntValue(2), IntValue(20), money(250)],
+             vec![IntValue(1), IntValue(11), money(125)],
+             vec![IntValue(2), IntValue(21), NullValue]]
+    }
+
+    /// (district, name)
+    fn districts() -> Vec<Row> {
+        vec![vec![IntValue(1), text("one")],
+             vec![IntValue(2), text("two")],
+             vec![IntValue(3), text("three")]]
+    }
+
+    #[test]
+    fn test_filter_project_limit() {
+        let orders = orders();
+        let scan = box Scan::new(orders.iter());
+        let filter = box Filter::new(scan, Or(box Comparison(box ColumnRef(2), IsGreater,
+                                                             box Literal(money(200))),
+                                              box IsNull(box ColumnRef(2))));
+        let project = box Project::new(filter, vec![
+            ColumnRef(1),
+            Arithmetic(box ColumnRef(2), Multiplication, box Literal(IntValue(2)))]);
+        let mut limit = Limit::new(project, 2);
+        assert_eq!(rows(&mut limit as &mut Operator),
+                   vec![vec![IntValue(10), money(1000)], vec![IntValue(20), money(500)]])
+    }
+
+    #[test]
+    fn test_hash_join() {
+        let (orders, districts) = (orders(), districts());
+        let mut join = HashJoin::new(box Scan::new(orders.iter()), box Scan::new(districts.iter()),
+                                     vec![0], vec![0]);
+        let names: Vec<Value> = rows(&mut join as &mut Operator).into_iter()
+            .map(|row| row[4].clone())
+            .collect();
+        assert_eq!(names, vec![text("one"), text("two"), text("one"), text("two")])
+    }
+
+    #[test]
+    fn test_aggregate_sort() {
+        let orders = orders();
+        let aggregate = box Aggregate::new(box Scan::new(orders.iter()), vec![0],
+                                           vec![Count, Sum(2), Max(1)]);
+        let mut sort = Sort::new(aggregate, vec![SortKey { column: 2, descending: false }]);
+        assert_eq!(rows(&mut sort as &mut Operator),
+                   vec![vec![IntValue(2), IntValue(2), money(250), IntValue(21)],
+                        vec![IntValue(1), IntValue(2), money(625), IntValue(11)]])
+    }
+
+    #[test]
+    fn test_aggregate_empty_input() {
+        let empty: Vec<Row> = Vec::new();
+        let filter = box Filter::new(box Scan::new(empty.iter()),
+                                     Comparison(box ColumnRef(0), IsEqual, box Literal(IntValue(1))));
+        let mut aggregate = Aggregate::new(filter, Vec::new(), vec![Count, Sum(0)]);
+        assert_eq!(rows(&mut aggregate as &mut Operator), vec![vec![IntValue(0), NullValue]])
+    }
+
+    #[test]
+    fn test_mixed_precision() {
+        assert_eq!(compare_values(&money(1000), &IntValue(10)), Some(Equal))
+        assert_eq!(compare_values(&money(1000), &IntValue(0)), Some(Greater))
+        assert_eq!(compare_values(&IntValue(10), &money(1001)), Some(Less))
+        assert_eq!(arithmetic(&IntValue(2), Multiplication, &money(1050)), money(2100))
+        assert_eq!(arithmetic(&money(1050), Multiplication, &IntValue(2)), money(2100))
+        assert_eq!(arithmetic(&money(1050), Multiplication, &money(200)),
+                   NumericValue(Numeric::new(210000, 12, 4)))
+    }
+}
@@ -0,0 +1,248 @@
+//! An interactive shell for inspecting and changing the loaded tables:
+//!
+//! ```text
+//! get customer 1 2 3                 the row with the primary key (1, 2, 3)
+//! scan orderline where ol_o_id = 5   the rows satisfying a SQL condition
+//! count [table]                      the number of rows of one or all tables
+//! run neworder|payment|mix 1000      runs transactions
+//! checkpoint [directory]             writes all tables as .tbl files
+//! ```
+//!
+//! Tables are accessed through their `Relation`, with rows in the column
+//! order of the schema.
+
+use std::io::{stdin, stdio, File, USER_RWX};
+use std::io::fs::mkdir_recursive;
+use std::rand::task_rng;
+
+use time::precise_time_ns;
+
+use super::{urand, new_order_random, payment_random, NUM_WAREHOUSES};
+use catalog::{Catalog, TableSchema};
+use driver::random_transaction;
+use dynamic::{Row, Value, NullValue};
+use planner;
+use query::{rows, Database, Relation};
+use tables::Tables;
+use timestamp::Timestamp;
+
+
+const HELP: &'static str = "\
+get <table> <key>...            the row with this primary key
+scan <table> [where ...]        the rows satisfying a condition, may end with limit <n>
+count [<table>]                 the number of rows of one or all tables
+run neworder|payment|mix <n>    runs n transactions
+checkpoint [<directory>]        writes all tables to <directory>/tpcc_<table>.tbl
+help
+quit";
+
+const CHECKPOINT_DIRECTORY: &'static str = "../data/checkpoint";
+
+/// Reads commands from stdin until it ends or `quit`.
+pub fn run(catalog: &Catalog, tables: &mut Tables) {
+    let mut input = stdin();
+    loop {
+        print!("> ");
+        let _ = stdio::flush();
+        let line = match input.read_line() {
+            Ok(line) => line,
+            Err(_) => return
+        };
+        let words: Vec<&str> = line[].words().collect();
+        let result = match words[] {
+            [] => Ok(()),
+            ["quit"] | ["exit"] => return,
+            ["help"] => {
+                println!("{}", HELP);
+                Ok(())
+            },
+            ["get", table, key..] => get(catalog, tables, table, key),
+            ["scan", table, ..] => scan(catalog, tables, table, after_words(line[], 2)),
+            ["count"] => {
+                count_all(catalog, tables);
+                Ok(())
+            },
+            ["count", table] => count(catalog, tables, table),
+            ["run", kind, n] => run_transactions(tables, kind, n),
+            ["checkpoint"] => checkpoint(catalog, tables, CHECKPOINT_DIRECTORY),
+            ["checkpoint", directory] => checkpoint(catalog, tables, directory),
+            _ => Err(format!("unknown command, try help"))
+        };
+        if let Err(e) = result {
+            println!("ERROR: {}", e);
+        }
+    }
+}
+
+/// `line` without its first `n` words, the rest being passed on as it is.
+fn after_words(line: &str, n: uint) -> &str {
+    let mut rest = line.trim_left();
+    for _ in range(0, n) {
+        let end = rest.find(|c: char| c.is_whitespace()).unwrap_or(rest.len());
+        rest = rest[end..].trim_left();
+    }
+    rest.trim_right()
+}
+
+fn relation<'a>(catalog: &'a Catalog, tables: &'a Tables, name: &str)
+        -> Result<(&'a TableSchema, &'a Relation), String> {
+    match (catalog.table(name), tables.relation(name)) {
+        (Some(schema), Some(relation)) => Ok((schema, relation)),
+        _ => Err(format!("there is no table {}", name))
+    }
+}
+
+fn get(catalog: &Catalog, tables: &Tables, name: &str, key: &[&str]) -> Result<(), String> {
+    let (schema, relation) = try!(relation(catalog, tables, name));
+    if key.len() != schema.primary_key.len() {
+        let columns: Vec<&str> = schema.primary_key.iter()
+            .map(|&i| schema.columns[i].name[])
+            .collect();
+        return Err(format!("the key of {} is ({})", name, columns.connect(", ")));
+    }
+    let mut key_row = Vec::new();
+    for (&i, field) in schema.primary_key.iter().zip(key.iter()) {
+        let column = &schema.columns[i];
+        key_row.push(try!(Value::parse(column, *field)
+                          .map_err(|e| format!("{}: {}", column.name, e))));
+    }
+    match relation.lookup(&key_row) {
+        Some(row) => {
+            for (column, value) in schema.columns.iter().zip(row.iter()) {
+                println!("{:>16}: {}", column.name, value);
+            }
+        },
+        None => println!("(no row)")
+    }
+    Ok(())
+}
+
+/// Runs `select * from <table> <clauses>`, so the clauses may be a where and
+/// a limit clause.
+fn scan(catalog: &Catalog, tables: &Tables, name: &str, clauses: &str) -> Result<(), String> {
+    let query = format!("select * from {} {}", name, clauses);
+    let (columns, rows) = try!(planner::query(catalog, tables, query[]));
+    println!("{}", columns.connect("|"));
+    for row in rows.iter() {
+        println!("{}", tbl_line(row));
+    }
+    println!("({} rows)", rows.len());
+    Ok(())
+}
+
+fn count(catalog: &Catalog, tables: &Tables, name: &str) -> Result<(), String> {
+    let (_, relation) = try!(relation(catalog, tables, name));
+    println!("{}", relation.size());
+    Ok(())
+}
+
+fn count_all(catalog: &Catalog, tables: &Tables) {
+    for schema in catalog.tables().iter() {
+        if let Some(relation) = tables.relation(schema.name[]) {
+            println!("{:>10}: {}", schema.name, relation.size());
+        }
+    }
+}
+
+fn run_transactions(tables: &mut Tables, kind: &str, n: &str) -> Result<(), String> {
+    let n: uint = match from_str(n) {
+        Some(n) => n,
+        None => return Err(format!("not a number: {}", n))
+    };
+    let rng = &mut task_rng();
+    let mut aborts = 0u;
+    let time = precise_time_ns();
+    for _ in range(0, n) {
+        let w_id = urand(1, NUM_WAREHOUSES, rng);
+        let committed = match kind {
+            "neworder" => new_order_random(tables, Timestamp::now(), w_id, rng),
+            "payment" => {
+                payment_random(tables, w_id, rng);
+                true
+            },
+            "mix" => {
+                let (_, committed) = random_transaction(tables, rng);
+                committed
+            },
+            _ => return Err(format!("unknown transaction {}, use neworder, payment or mix", kind))
+        };
+        if !committed {
+            aborts += 1;
+        }
+    }
+    println!("{} transactions, {} aborted, {}s", n, aborts,
+             (precise_time_ns() - time) as f64 / 1e9f64);
+    Ok(())
+}
+
+/// The fields of `row` separated by `|`, with NULL as the empty field.
+fn tbl_line(row: &Row) -> String {
+    let fields: Vec<String> = row.iter().map(|value| match *value {
+        NullValue => String::new(),
+        ref value => value.to_string()
+    }).collect();
+    fields.connect("|")
+}
+
+/// Writes every table to `directory`, in the format `read_tables` loads.
+fn checkpoint(catalog: &Catalog, tables: &Tables, directory: &str) -> Result<(), String> {
+    let time = precise_time_ns();
+    let directory = Path::new(directory);
+    try!(mkdir_recursive(&directory, USER_RWX).map_err(|e| e.to_string()));
+    for schema in catalog.tables().iter() {
+        let relation = match tables.relation(schema.name[]) {
+            Some(relation) => relation,
+            None => continue
+        };
+        let path = directory.join(format!("tpcc_{}.tbl", schema.name));
+        let mut file = File::create(&path);
+        let mut scan = relation.scan();
+        for row in rows(&mut *scan).iter() {
+            try!(file.write_line(tbl_line(row)[]).map_err(|e| e.to_string()));
+        }
+        println!("{}: {} rows", path.display(), relation.size());
+    }
+    println!("checkpoint {}s", (precise_time_ns() - time) as f64 / 1e9f64);
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::io::TempDir;
+
+    use ddl;
+    use query::{rows, Database};
+    use table::{read_table, fixture};
+    use tables::Tables;
+
+    use super::{after_words, checkpoint};
+
+    #[test]
+    fn test_after_words() {
+        assert_eq!(after_words(" scan customer  where c_last = 'A  B'\n", 2),
+                   "where c_last = 'A  B'")
+        assert_eq!(after_words("scan customer\n", 2), "")
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let catalog = ddl::parse(include_str!("../schema.sql")).unwrap();
+        let dir = TempDir::new("checkpoint").unwrap();
+        let directory = dir.path().as_str().unwrap();
+        let t = fixture();
+        checkpoint(&catalog, &t, directory).unwrap();
+
+        let mut loaded = Tables::new();
+        let file = |name: &str| format!("{}/tpcc_{}.tbl", directory, name);
+        read_table(file("customer")[], &mut loaded.customer_table);
+        read_table(file("order")[], &mut loaded.order_table);
+        read_table(file("orderline")[], &mut loaded.orderline_table);
+        // with NULL carriers and delivery dates, numerics and timestamps
+        for name in ["customer", "order", "orderline"].iter() {
+            let original = rows(&mut *t.relation(*name).unwrap().scan());
+            assert_eq!(rows(&mut *loaded.relation(*name).unwrap().scan()), original)
+        }
+    }
+}
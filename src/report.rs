@@ -0,0 +1,132 @@
+//! The result of a benchmark run as JSON or CSV, for comparing the
+//! performance of commits automatically. Latencies are in microseconds.
+
+use std::io::File;
+
+use driver::{micros, RunStats, Stats, PERCENTILES};
+
+
+pub struct BenchmarkResult<'a> {
+    pub warehouses: i32,
+    pub transactions: uint,
+    pub seed: uint,
+    pub load_seconds: f64,
+    pub run: &'a RunStats,
+    pub table_sizes: Vec<(&'static str, uint)>,
+}
+
+impl<'a> BenchmarkResult<'a> {
+    pub fn to_json(&self) -> String {
+        let types: Vec<String> = self.run.transactions.iter().map(|s| {
+            let fields: Vec<String> = latency_fields(s).into_iter()
+                .map(|(name, value)| format!("\"{}\": {}", name, value))
+                .collect();
+            format!("    \"{}\": {{{}}}", s.name, fields.connect(", "))
+        }).collect();
+        let sizes: Vec<String> = self.table_sizes.iter()
+            .map(|&(name, size)| format!("\"{}\": {}", name, size))
+            .collect();
+        format!("{{\n  \"warehouses\": {},\n  \"transactions\": {},\n  \"seed\": {},\n  \
+                 \"load_seconds\": {:.6},\n  \"run_seconds\": {:.6},\n  \"throughput\": {:.3},\n  \
+                 \"transaction_types\": {{\n{}\n  }},\n  \"table_sizes\": {{{}}}\n}}\n",
+                self.warehouses, self.transactions, self.seed, self.load_seconds,
+                self.run.seconds, self.run.throughput(), types.connect(",\n"), sizes.connect(", "))
+    }
+
+    /// A header and one line per transaction type, each repeating the
+    /// configuration and table sizes.
+    pub fn to_csv(&self) -> String {
+        let mut header = vec!["warehouses".to_string(), "transactions".to_string(),
+                              "seed".to_string(), "load_seconds".to_string(),
+                              "run_seconds".to_string(), "throughput".to_string(),
+                              "type".to_string()];
+        let mut lines = Vec::new();
+        for s in self.run.transactions.iter() {
+            let fields = latency_fields(s);
+            if lines.is_empty() {
+                header.extend(fields.iter().map(|&(ref name, _)| name.clone()));
+                header.extend(self.table_sizes.iter().map(|&(name, _)| name.to_string()));
+                lines.push(header.connect(","));
+            }
+            let mut line = vec![self.warehouses.to_string(), self.transactions.to_string(),
+                                self.seed.to_string(), format!("{:.6}", self.load_seconds),
+                                format!("{:.6}", self.run.seconds),
+                                format!("{:.3}", self.run.throughput()), s.name.to_string()];
+            line.extend(fields.into_iter().map(|(_, value)| value));
+            line.extend(self.table_sizes.iter().map(|&(_, size)| size.to_string()));
+            lines.push(line.connect(","));
+        }
+        lines.push(String::new());
+        lines.connect("\n")
+    }
+}
+
+/// Names and values of the statistics of one transaction type.
+fn latency_fields(s: &Stats) -> Vec<(String, String)> {
+    let latencies = &s.latencies;
+    let mut fields = vec![("count".to_string(), latencies.count().to_string()),
+                          ("aborts".to_string(), s.aborts.to_string()),
+                          ("abort_rate".to_string(), format!("{:.6}", s.abort_rate())),
+                          ("mean_us".to_string(), format!("{:.3}", latencies.mean() / 1e3f64))];
+    for &(percentile, name) in PERCENTILES.iter() {
+        let value = micros(latencies.percentile(percentile));
+        fields.push((format!("{}_us", name), format!("{:.3}", value)));
+    }
+    fields.push(("max_us".to_string(), format!("{:.3}", micros(latencies.max()))));
+    fields
+}
+
+/// Writes `contents` to the file `filename`.
+pub fn write(filename: &str, contents: String) {
+    if let Err(e) = File::create(&Path::new(filename)).write_str(contents[]) {
+        fail!("{}: {}", filename, e)
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use driver::{RunStats, Stats};
+
+    use super::BenchmarkResult;
+
+    fn run() -> RunStats {
+        let mut new_order = Stats::new("neworder");
+        new_order.latencies.record(1023);
+        new_order.latencies.record(2047);
+        new_order.aborts = 1;
+        RunStats { seconds: 0.5, transactions: vec![new_order, Stats::new("payment")] }
+    }
+
+    #[test]
+    fn test_csv() {
+        let run = run();
+        let result = BenchmarkResult { warehouses: 5, transactions: 2, seed: 42,
+                                       load_seconds: 1.5, run: &run,
+                                       table_sizes: vec![("Warehouse", 5)] };
+        let csv = result.to_csv();
+        let lines: Vec<&str> = csv[].lines().collect();
+        assert_eq!(lines.len(), 3)
+        assert_eq!(lines[0], "warehouses,transactions,seed,load_seconds,run_seconds,throughput,\
+                              type,count,aborts,abort_rate,mean_us,p50_us,p90_us,p99_us,\
+                              p99.9_us,max_us,Warehouse")
+        assert_eq!(lines[1], "5,2,42,1.500000,0.500000,4.000,neworder,2,1,0.500000,1.535,\
+                              1.023,2.047,2.047,2.047,2.047,5")
+        assert!(lines[2].starts_with("5,2,42,1.500000,0.500000,4.000,payment,0,0,0.000000,"))
+    }
+
+    #[test]
+    fn test_json() {
+        let run = run();
+        let result = BenchmarkResult { warehouses: 5, transactions: 2, seed: 42,
+                                       load_seconds: 1.5, run: &run,
+                                       table_sizes: vec![("Warehouse", 5), ("District", 50)] };
+        let json = result.to_json();
+        assert!(json[].contains("\"seed\": 42,"))
+        assert!(json[].contains("\"throughput\": 4.000,"))
+        assert!(json[].contains("\"neworder\": {\"count\": 2, \"aborts\": 1, \
+                                 \"abort_rate\": 0.500000, \"mean_us\": 1.535, \"p50_us\": 1.023"))
+        assert!(json[].contains("\"table_sizes\": {\"Warehouse\": 5, \"District\": 50}"))
+    }
+}
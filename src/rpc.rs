@@ -0,0 +1,517 @@
+//! A binary protocol to call the TPC-C transactions as stored procedures
+//! over TCP or Unix sockets, and a load generator that calls them from a
+//! number of terminals.
+//!
+//! A request is the procedure as one byte followed by its parameters, the
+//! inputs of `new_order` and `payment`, with integers in big endian:
+//!
+//! ```text
+//! 0 NewOrder  w_id d_id c_id: i32, ol_cnt: u8, ol_cnt * (supware itemid qty: i32)
+//! 1 Payment   w_id d_id c_w_id c_d_id c_id: i32, h_amount: i64 in cents
+//! ```
+//!
+//! The response is one byte, 0 if the transaction committed, 1 if it rolled
+//! back and 2 if the request was invalid, which is followed by the message
+//! as u16 length and UTF-8 bytes. An unknown procedure is answered as
+//! invalid and closes the connection.
+//!
+//! Every connection is served by its own task, the transactions run one at
+//! a time while holding the lock on the tables.
+
+use std::cmp::min;
+use std::fmt;
+use std::io::{Acceptor, BufferedStream, EndOfFile, InvalidInput, IoError, IoResult, Listener,
+              Stream};
+use std::io::net::pipe::{UnixListener, UnixStream};
+use std::io::net::tcp::{TcpListener, TcpStream};
+use std::rand::{Rng, SeedableRng, StdRng};
+use std::sync::{Arc, Mutex};
+use std::u16;
+
+use time::precise_time_ns;
+
+use super::{urand, nurand, urandexcept, new_order, payment, NUM_WAREHOUSES};
+use driver::{new_stats, print_summary, RunStats, Stats, NEW_ORDER, PAYMENT};
+use numeric::Numeric;
+use tables::Tables;
+use timestamp::Timestamp;
+
+
+const COMMITTED: u8 = 0;
+const ABORTED: u8 = 1;
+const INVALID: u8 = 2;
+
+/// Where the server listens, `host:port` for TCP and any other string is the
+/// path of a Unix socket.
+#[deriving(Clone, PartialEq)]
+pub enum Address {
+    TcpAddress(String, u16),
+    UnixAddress(Path),
+}
+
+impl Address {
+    pub fn parse(address: &str) -> Result<Address, String> {
+        match address.rfind(':') {
+            Some(i) => match from_str(address[i + 1..]) {
+                Some(port) => Ok(TcpAddress(address[..i].to_string(), port)),
+                None => Err(format!("not a port: {}", address[i + 1..]))
+            },
+            None => Ok(UnixAddress(Path::new(address)))
+        }
+    }
+}
+
+impl fmt::Show for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TcpAddress(ref host, port) => write!(f, "{}:{}", host, port),
+            UnixAddress(ref path) => write!(f, "{}", path.display())
+        }
+    }
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct NewOrderInput {
+    pub w_id: i32,
+    pub d_id: i32,
+    pub c_id: i32,
+    pub supware: Vec<i32>,
+    pub itemid: Vec<i32>,
+    pub qty: Vec<i32>,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct PaymentInput {
+    pub w_id: i32,
+    pub d_id: i32,
+    pub c_w_id: i32,
+    pub c_d_id: i32,
+    pub c_id: i32,
+    /// In cents.
+    pub h_amount: i64,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Call {
+    NewOrderCall(NewOrderInput),
+    PaymentCall(PaymentInput),
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Outcome {
+    Committed,
+    Aborted,
+    Invalid(String),
+}
+
+pub fn write_call(writer: &mut Writer, call: &Call) -> IoResult<()> {
+    match *call {
+        NewOrderCall(ref input) => {
+            try!(writer.write_u8(NEW_ORDER as u8));
+            for &value in [input.w_id, input.d_id, input.c_id].iter() {
+                try!(writer.write_be_i32(value));
+            }
+            try!(writer.write_u8(input.itemid.len() as u8));
+            for i in range(0, input.itemid.len()) {
+                try!(writer.write_be_i32(input.supware[i]));
+                try!(writer.write_be_i32(input.itemid[i]));
+                try!(writer.write_be_i32(input.qty[i]));
+            }
+            Ok(())
+        },
+        PaymentCall(ref input) => {
+            try!(writer.write_u8(PAYMENT as u8));
+            for &value in [input.w_id, input.d_id, input.c_w_id, input.c_d_id, input.c_id].iter() {
+                try!(writer.write_be_i32(value));
+            }
+            writer.write_be_i64(input.h_amount)
+        }
+    }
+}
+
+pub fn read_call(reader: &mut Reader) -> IoResult<Call> {
+    match try!(reader.read_u8()) as uint {
+        NEW_ORDER => {
+            let mut input = NewOrderInput {
+                w_id: try!(reader.read_be_i32()), d_id: try!(reader.read_be_i32()),
+                c_id: try!(reader.read_be_i32()), supware: Vec::new(), itemid: Vec::new(),
+                qty: Vec::new()
+            };
+            for _ in range(0, try!(reader.read_u8())) {
+                input.supware.push(try!(reader.read_be_i32()));
+                input.itemid.push(try!(reader.read_be_i32()));
+                input.qty.push(try!(reader.read_be_i32()));
+            }
+            Ok(NewOrderCall(input))
+        },
+        PAYMENT => Ok(PaymentCall(PaymentInput {
+            w_id: try!(reader.read_be_i32()), d_id: try!(reader.read_be_i32()),
+            c_w_id: try!(reader.read_be_i32()), c_d_id: try!(reader.read_be_i32()),
+            c_id: try!(reader.read_be_i32()), h_amount: try!(reader.read_be_i64())
+        })),
+        procedure => Err(IoError {
+            kind: InvalidInput, desc: "unknown procedure",
+            detail: Some(procedure.to_string())
+        })
+    }
+}
+
+pub fn write_outcome(writer: &mut Writer, outcome: &Outcome) -> IoResult<()> {
+    match *outcome {
+        Committed => writer.write_u8(COMMITTED),
+        Aborted => writer.write_u8(ABORTED),
+        Invalid(ref message) => {
+            let bytes = message.as_bytes();
+            let bytes = bytes[..min(bytes.len(), u16::MAX as uint)];
+            try!(writer.write_u8(INVALID));
+            try!(writer.write_be_u16(bytes.len() as u16));
+            writer.write(bytes)
+        }
+    }
+}
+
+pub fn read_outcome(reader: &mut Reader) -> IoResult<Outcome> {
+    match try!(reader.read_u8()) {
+        COMMITTED => Ok(Committed),
+        ABORTED => Ok(Aborted),
+        INVALID => {
+            let len = try!(reader.read_be_u16()) as uint;
+            let bytes = try!(reader.read_exact(len));
+            Ok(Invalid(String::from_utf8_lossy(bytes[]).into_string()))
+        },
+        status => Err(IoError {
+            kind: InvalidInput, desc: "unknown status", detail: Some(status.to_string())
+        })
+    }
+}
+
+/// Runs `call`, unless it refers to rows that do not exist, for which the
+/// transactions would fail.
+pub fn execute(tables: &mut Tables, call: &Call) -> Outcome {
+    if let Err(message) = check(tables, call) {
+        return Invalid(message);
+    }
+    match *call {
+        NewOrderCall(ref input) => {
+            let committed = new_order(tables, input.w_id, input.d_id, input.c_id,
+                                      input.itemid.len() as i32, input.supware[],
+                                      input.itemid[], input.qty[], Timestamp::now());
+            if committed { Committed } else { Aborted }
+        },
+        PaymentCall(ref input) => {
+            payment(tables, input.w_id, input.d_id, input.c_w_id, input.c_d_id, input.c_id,
+                    Numeric::new(input.h_amount, 6, 2));
+            Committed
+        }
+    }
+}
+
+fn check(tables: &Tables, call: &Call) -> Result<(), String> {
+    let (w_id, d_id, c_key) = match *call {
+        NewOrderCall(ref input) => {
+            if input.supware.len() != input.itemid.len() || input.qty.len() != input.itemid.len() {
+                return Err("supware, itemid and qty differ in length".to_string());
+            }
+            // an unused item rolls the transaction back, but the stock of a
+            // used one has to exist
+            for (&supware, &itemid) in input.supware.iter().zip(input.itemid.iter()) {
+                if tables.item_table.find(&itemid).is_some()
+                        && tables.stock_table.find(&(supware, itemid)).is_none() {
+                    return Err(format!("no stock for w_id {} i_id {}", supware, itemid));
+                }
+            }
+            (input.w_id, input.d_id, (input.w_id, input.d_id, input.c_id))
+        },
+        PaymentCall(ref input) => {
+            (input.w_id, input.d_id, (input.c_w_id, input.c_d_id, input.c_id))
+        }
+    };
+    if tables.warehouse_table.find(&w_id).is_none() {
+        return Err(format!("no warehouse {}", w_id));
+    }
+    if d_id < 1 || d_id > 10 || tables.district_table.find(&(w_id, d_id)).is_none() {
+        return Err(format!("no district w_id {} d_id {}", w_id, d_id));
+    }
+    if tables.customer_table.find(&c_key).is_none() {
+        let (c_w_id, c_d_id, c_id) = c_key;
+        return Err(format!("no customer w_id {} d_id {} c_id {}", c_w_id, c_d_id, c_id));
+    }
+    Ok(())
+}
+
+/// Accepts connections on `address`, forever.
+pub fn serve(tables: Tables, address: &Address) {
+    let tables = Arc::new(Mutex::new(tables));
+    match *address {
+        TcpAddress(ref host, port) => accept(TcpListener::bind(host[], port).listen(), tables,
+                                             address),
+        UnixAddress(ref path) => accept(UnixListener::bind(path).listen(), tables, address)
+    }
+}
+
+fn accept<S: Stream + Send, A: Acceptor<S>>(acceptor: IoResult<A>, tables: Arc<Mutex<Tables>>,
+                                            address: &Address) {
+    let mut acceptor = match acceptor {
+        Ok(acceptor) => acceptor,
+        Err(e) => fail!("cannot listen on {}: {}", address, e)
+    };
+    println!("listening on {}", address);
+    for stream in acceptor.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("accept failed: {}", e);
+                continue;
+            }
+        };
+        let tables = tables.clone();
+        spawn(proc() {
+            if let Err(e) = handle(stream, &*tables) {
+                println!("connection failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Answers the calls of one client until it closes the connection.
+pub fn handle<S: Stream>(stream: S, tables: &Mutex<Tables>) -> IoResult<()> {
+    let mut stream = BufferedStream::new(stream);
+    loop {
+        let call = match read_call(&mut stream) {
+            Ok(call) => call,
+            Err(ref e) if e.kind == EndOfFile => return Ok(()),
+            Err(ref e) if e.kind == InvalidInput => {
+                try!(write_outcome(&mut stream, &Invalid(e.to_string())));
+                return stream.flush();
+            },
+            Err(e) => return Err(e)
+        };
+        let outcome = execute(&mut *tables.lock(), &call);
+        try!(write_outcome(&mut stream, &outcome));
+        try!(stream.flush());
+    }
+}
+
+/// One connection to the server.
+pub struct Client<S> {
+    stream: BufferedStream<S>,
+}
+
+impl<S: Stream> Client<S> {
+    pub fn new(stream: S) -> Client<S> {
+        Client { stream: BufferedStream::new(stream) }
+    }
+
+    /// Sends `call` and waits for its outcome.
+    pub fn call(&mut self, call: &Call) -> IoResult<Outcome> {
+        try!(write_call(&mut self.stream, call));
+        try!(self.stream.flush());
+        read_outcome(&mut self.stream)
+    }
+}
+
+/// The inputs of a NewOrder in district `d_id` of warehouse `w_id`, as
+/// `new_order_random` runs it.
+pub fn random_new_order<R: Rng>(w_id: i32, d_id: i32, rng: &mut R) -> NewOrderInput {
+    let c_id = nurand(1023, 1, 3000, rng);
+    let ol_cnt = urand(5, 15, rng) as uint;
+    let mut input = NewOrderInput {
+        w_id: w_id, d_id: d_id, c_id: c_id, supware: Vec::with_capacity(ol_cnt),
+        itemid: Vec::with_capacity(ol_cnt), qty: Vec::with_capacity(ol_cnt)
+    };
+    for _ in range(0, ol_cnt) {
+        input.supware.push(if urand(1, 100, rng) > 1 {
+            w_id
+        } else {
+            urandexcept(1, NUM_WAREHOUSES, w_id, rng)
+        });
+        input.itemid.push(nurand(8191, 1, 100000, rng));
+        input.qty.push(urand(1, 10, rng));
+    }
+    // 1% of the transactions order an unused item and roll back
+    if urand(1, 100, rng) == 1 {
+        *input.itemid.get_mut(ol_cnt - 1) = 100001;
+    }
+    input
+}
+
+/// The inputs of a Payment in district `d_id` of warehouse `w_id`, as
+/// `payment_random` runs it.
+pub fn random_payment<R: Rng>(w_id: i32, d_id: i32, rng: &mut R) -> PaymentInput {
+    // 15% of the payments are for a customer of another warehouse
+    let (c_w_id, c_d_id) = if urand(1, 100, rng) <= 85 {
+        (w_id, d_id)
+    } else {
+        (urandexcept(1, NUM_WAREHOUSES, w_id, rng), d_id)
+    };
+    let c_id = nurand(1023, 1, 3000, rng);
+    let h_amount = urand(100, 500000, rng) as i64;
+    PaymentInput {
+        w_id: w_id, d_id: d_id, c_w_id: c_w_id, c_d_id: c_d_id, c_id: c_id, h_amount: h_amount
+    }
+}
+
+/// `random_new_order` or `random_payment` in the ratio of
+/// `driver::random_transaction`, and which of them it is.
+pub fn random_call<R: Rng>(w_id: i32, d_id: i32, rng: &mut R) -> (uint, Call) {
+    if urand(1, 88, rng) <= 45 {
+        (NEW_ORDER, NewOrderCall(random_new_order(w_id, d_id, rng)))
+    } else {
+        (PAYMENT, PaymentCall(random_payment(w_id, d_id, rng)))
+    }
+}
+
+/// Runs `transactions` calls from each of `terminals` terminals, every one
+/// with its own connection, a fixed warehouse and a random number generator
+/// seeded from `seed` and its number, and prints the latencies like
+/// `driver::run`.
+pub fn load(address: &Address, terminals: uint, transactions: uint, seed: uint) -> RunStats {
+    let (sender, receiver) = channel();
+    let start = precise_time_ns();
+    for terminal in range(0, terminals) {
+        let sender = sender.clone();
+        let address = address.clone();
+        spawn(proc() {
+            let w_id = (terminal % NUM_WAREHOUSES as uint) as i32 + 1;
+            let mut rng: StdRng = SeedableRng::from_seed([seed, terminal][]);
+            let stats = match address {
+                TcpAddress(ref host, port) => run_terminal(TcpStream::connect(host[], port),
+                                                           &address, w_id, &mut rng,
+                                                           transactions),
+                UnixAddress(ref path) => run_terminal(UnixStream::connect(path), &address, w_id,
+                                                      &mut rng, transactions)
+            };
+            sender.send(stats);
+        });
+    }
+
+    let mut stats = new_stats();
+    for _ in range(0, terminals) {
+        for (total, terminal) in stats.iter_mut().zip(receiver.recv().iter()) {
+            total.latencies.add(&terminal.latencies);
+            total.aborts += terminal.aborts;
+        }
+    }
+    let result = RunStats {
+        seconds: (precise_time_ns() - start) as f64 / 1e9f64, transactions: stats
+    };
+    print_summary(&result);
+    result
+}
+
+fn run_terminal<S: Stream, R: Rng>(stream: IoResult<S>, address: &Address, w_id: i32,
+                                   rng: &mut R, transactions: uint) -> Vec<Stats> {
+    let mut client = match stream {
+        Ok(stream) => Client::new(stream),
+        Err(e) => fail!("cannot connect to {}: {}", address, e)
+    };
+    let mut stats = new_stats();
+    for _ in range(0, transactions) {
+        let (kind, call) = random_call(w_id, 1, rng);
+        let time = precise_time_ns();
+        let outcome = match client.call(&call) {
+            Ok(outcome) => outcome,
+            Err(e) => fail!("call to {} failed: {}", address, e)
+        };
+        let kind_stats = stats.get_mut(kind);
+        kind_stats.latencies.record(precise_time_ns() - time);
+        match outcome {
+            Committed => {},
+            Aborted => kind_stats.aborts += 1,
+            Invalid(message) => fail!("invalid call {}: {}", call, message)
+        }
+    }
+    stats
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{Acceptor, Listener, MemReader, MemWriter};
+    use std::io::net::tcp::{TcpListener, TcpStream};
+    use std::rand::{SeedableRng, StdRng};
+    use std::sync::Mutex;
+
+    use driver::{NEW_ORDER, PAYMENT};
+    use tables::Tables;
+
+    use super::{read_call, read_outcome, write_call, write_outcome, handle, random_call, Address,
+                Client, TcpAddress, UnixAddress, NewOrderInput, PaymentInput, NewOrderCall,
+                PaymentCall, Committed, Aborted, Invalid};
+
+    #[test]
+    fn test_address() {
+        assert_eq!(Address::parse("127.0.0.1:5434"), Ok(TcpAddress("127.0.0.1".to_string(), 5434)))
+        assert_eq!(Address::parse("/tmp/tpcc.sock"), Ok(UnixAddress(Path::new("/tmp/tpcc.sock"))))
+        assert!(Address::parse("localhost:http").is_err())
+    }
+
+    #[test]
+    fn test_encoding() {
+        let calls = vec![
+            NewOrderCall(NewOrderInput { w_id: 1, d_id: 2, c_id: 3, supware: vec![1, 4],
+                                         itemid: vec![5, 100001], qty: vec![6, 7] }),
+            PaymentCall(PaymentInput { w_id: 1, d_id: 2, c_w_id: 3, c_d_id: 4, c_id: 5,
+                                       h_amount: 123456 }),
+        ];
+        let outcomes = vec![Committed, Aborted, Invalid("no warehouse 6".to_string())];
+        let mut writer = MemWriter::new();
+        for call in calls.iter() {
+            write_call(&mut writer, call).unwrap();
+        }
+        for outcome in outcomes.iter() {
+            write_outcome(&mut writer, outcome).unwrap();
+        }
+        let bytes = writer.unwrap();
+        // procedure, 3 ids, ol_cnt and 2 * 3 values
+        assert_eq!(bytes[..14], b"\0\0\0\0\x01\0\0\0\x02\0\0\0\x03\x02")
+        let mut reader = MemReader::new(bytes);
+        assert_eq!(read_call(&mut reader).unwrap(), calls[0])
+        assert_eq!(read_call(&mut reader).unwrap(), calls[1])
+        for outcome in outcomes.iter() {
+            assert_eq!(read_outcome(&mut reader).unwrap(), *outcome)
+        }
+        assert!(reader.eof())
+    }
+
+    #[test]
+    fn test_random_call() {
+        let mut rng: StdRng = SeedableRng::from_seed([42u][]);
+        for _ in range(0u, 1000) {
+            match random_call(2, 3, &mut rng) {
+                (NEW_ORDER, NewOrderCall(input)) => {
+                    assert!(input.w_id == 2 && input.d_id == 3)
+                    assert!(input.itemid.len() >= 5 && input.itemid.len() <= 15)
+                    assert_eq!(input.supware.len(), input.itemid.len())
+                    assert_eq!(input.qty.len(), input.itemid.len())
+                },
+                (PAYMENT, PaymentCall(input)) => {
+                    assert!(input.w_id == 2 && input.d_id == 3 && input.c_d_id == 3)
+                    assert!(input.h_amount >= 100 && input.h_amount <= 500000)
+                },
+                (kind, call) => fail!("{} for kind {}", call, kind)
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_call() {
+        let mut acceptor = TcpListener::bind("127.0.0.1", 0).listen().unwrap();
+        let port = acceptor.socket_name().unwrap().port;
+        spawn(proc() {
+            let tables = Mutex::new(Tables::new());
+            handle(acceptor.accept().unwrap(), &tables).unwrap();
+        });
+
+        let mut client = Client::new(TcpStream::connect("127.0.0.1", port).unwrap());
+        let call = PaymentCall(PaymentInput { w_id: 1, d_id: 1, c_w_id: 1, c_d_id: 1, c_id: 1,
+                                              h_amount: 100 });
+        assert_eq!(client.call(&call).unwrap(), Invalid("no warehouse 1".to_string()))
+        // an unknown procedure closes the connection
+        client.stream.write_u8(7).unwrap();
+        client.stream.flush().unwrap();
+        assert_eq!(read_outcome(&mut client.stream).unwrap(),
+                   Invalid("unknown procedure (7)".to_string()))
+    }
+}
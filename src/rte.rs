@@ -0,0 +1,207 @@
+//! A remote terminal emulator for the NewOrder/Payment mix as TPC-C
+//! prescribes it: every warehouse has 10 terminals, each bound to one of its
+//! districts. A terminal picks a transaction, waits for its keying time,
+//! submits it and, once it completed, waits for a think time drawn from an
+//! exponential distribution before it picks the next one.
+//!
+//! The response time of a transaction is measured from the moment its
+//! terminal submits it, so it includes waiting for the transactions of the
+//! other terminals, which all run on the calling task. tpmC is the number of
+//! NewOrder transactions completed per minute, rolled back ones included.
+//!
+//! `speedup` divides the keying and think times, for a higher load in a
+//! shorter run. The tpmC is never a valid one: Delivery, Order-Status and
+//! Stock-Level are not run, so NewOrder makes up about half of the mix
+//! instead of at most 45%. The run reports this together with the response
+//! times, measurement length and speedup that do not meet the requirements.
+
+use std::io::timer::sleep;
+use std::rand::{Rng, SeedableRng, StdRng};
+use std::time::Duration;
+
+use time::precise_time_ns;
+
+use super::NUM_WAREHOUSES;
+use driver::{new_stats, print_interval, print_interval_header, print_summary, RunStats,
+             NEW_ORDER, PAYMENT};
+use rpc::{execute, random_call, Call, Committed, Aborted, Invalid};
+use tables::Tables;
+
+
+pub const TERMINALS_PER_WAREHOUSE: i32 = 10;
+
+/// The minimum keying times and mean think times in seconds, indexed by
+/// `NEW_ORDER` and `PAYMENT`.
+const KEYING_TIMES: [f64, ..2] = [18f64, 3f64];
+const THINK_TIMES: [f64, ..2] = [12f64, 12f64];
+
+/// The bound on the 90th percentile of the response times of both, in
+/// seconds.
+const MAX_RESPONSE_TIME: f64 = 5f64;
+const MIN_MEASUREMENT_SECONDS: f64 = 7200f64;
+const REPORT_INTERVAL: f64 = 60f64;
+
+struct Terminal {
+    w_id: i32,
+    d_id: i32,
+    rng: StdRng,
+    /// The transaction type and inputs that are being keyed in.
+    kind: uint,
+    call: Call,
+    /// When the transaction is submitted, as `precise_time_ns`.
+    submit: u64,
+}
+
+impl Terminal {
+    fn new(w_id: i32, d_id: i32, seed: uint, now: u64, speedup: f64) -> Terminal {
+        let mut rng: StdRng = SeedableRng::from_seed([seed, w_id as uint, d_id as uint][]);
+        let (kind, call) = random_call(w_id, d_id, &mut rng);
+        Terminal {
+            w_id: w_id, d_id: d_id, rng: rng, kind: kind, call: call,
+            submit: now + nanos(KEYING_TIMES[kind] / speedup)
+        }
+    }
+
+    /// Thinks after the transaction completed at `now` and picks the next
+    /// one.
+    fn next(&mut self, now: u64, speedup: f64) {
+        let think = think_time(THINK_TIMES[self.kind], self.rng.gen());
+        let (kind, call) = random_call(self.w_id, self.d_id, &mut self.rng);
+        self.kind = kind;
+        self.call = call;
+        self.submit = now + nanos((think + KEYING_TIMES[kind]) / speedup);
+    }
+}
+
+/// The think time for the uniform random number `r` in [0, 1), with
+/// `mean` and at most 10 times it.
+fn think_time(mean: f64, r: f64) -> f64 {
+    (-(1f64 - r).ln() * mean).min(10f64 * mean)
+}
+
+fn nanos(seconds: f64) -> u64 {
+    (seconds * 1e9f64) as u64
+}
+
+/// Runs the terminals of all warehouses for `seconds`, printing a report
+/// every minute and the tpmC at the end.
+pub fn run(tables: &mut Tables, seconds: f64, speedup: f64, seed: uint) -> RunStats {
+    let start = precise_time_ns();
+    let mut terminals = Vec::new();
+    for w_id in range(1, NUM_WAREHOUSES + 1) {
+        for d_id in range(1, TERMINALS_PER_WAREHOUSE + 1) {
+            terminals.push(Terminal::new(w_id, d_id, seed, start, speedup));
+        }
+    }
+    println!("{} terminals for {}s, keying and think times divided by {}", terminals.len(),
+             seconds, speedup);
+
+    let mut stats = new_stats();
+    let end = start + nanos(seconds);
+    let interval_ns = nanos(REPORT_INTERVAL / speedup);
+    let mut interval_start = start;
+    print_interval_header();
+    loop {
+        let (i, submit) = terminals.iter().enumerate()
+            .map(|(i, terminal)| (i, terminal.submit))
+            .min_by(|&(_, submit)| submit)
+            .unwrap();
+        if submit >= end {
+            break;
+        }
+        let now = precise_time_ns();
+        if submit > now {
+            sleep(Duration::nanoseconds((submit - now) as i64));
+        }
+
+        let terminal = terminals.get_mut(i);
+        let committed = match execute(tables, &terminal.call) {
+            Committed => true,
+            Aborted => false,
+            Invalid(message) => fail!("terminal w_id {} d_id {}: {}", terminal.w_id,
+                                      terminal.d_id, message)
+        };
+        let now = precise_time_ns();
+        stats.get_mut(terminal.kind).record(now - submit, committed);
+        terminal.next(now, speedup);
+
+        if now - interval_start >= interval_ns {
+            print_interval(stats.as_mut_slice(), now - start, now - interval_start);
+            interval_start = now;
+        }
+    }
+    let now = precise_time_ns();
+    print_interval(stats.as_mut_slice(), now - start, now - interval_start);
+
+    let elapsed = if now > end { now - start } else { end - start };
+    let result = RunStats { seconds: elapsed as f64 / 1e9f64, transactions: stats };
+    print_summary(&result);
+    println!("tpmC {:.2}", tpmc(&result));
+    for problem in invalid(&result, speedup).iter() {
+        println!("not valid: {}", problem);
+    }
+    result
+}
+
+/// NewOrder transactions per minute.
+fn tpmc(result: &RunStats) -> f64 {
+    result.transactions[NEW_ORDER].latencies.count() as f64 / (result.seconds / 60f64)
+}
+
+/// Why the tpmC of the run is not valid, never empty as the mix lacks three
+/// of the five transactions.
+fn invalid(result: &RunStats, speedup: f64) -> Vec<String> {
+    let new_orders = result.transactions[NEW_ORDER].latencies.count() as f64;
+    let payments = result.transactions[PAYMENT].latencies.count() as f64;
+    let mut problems = vec![format!("Delivery, Order-Status and Stock-Level are not run, \
+                                     NewOrder is {:.1}% of the mix",
+                                    100f64 * new_orders / (new_orders + payments))];
+    if speedup != 1f64 {
+        problems.push(format!("keying and think times divided by {}", speedup));
+    }
+    if result.seconds < MIN_MEASUREMENT_SECONDS {
+        problems.push(format!("measured {:.0}s, not at least {:.0}s", result.seconds,
+                              MIN_MEASUREMENT_SECONDS));
+    }
+    for &kind in [NEW_ORDER, PAYMENT].iter() {
+        let s = &result.transactions[kind];
+        let p90 = s.latencies.percentile(90f64) as f64 / 1e9f64;
+        if p90 > MAX_RESPONSE_TIME {
+            problems.push(format!("90th percentile response time of {} is {:.3}s, more than \
+                                   {}s", s.name, p90, MAX_RESPONSE_TIME));
+        }
+    }
+    problems
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use driver::{new_stats, RunStats, NEW_ORDER, PAYMENT};
+
+    use super::{invalid, think_time, tpmc};
+
+    #[test]
+    fn test_think_time() {
+        assert_eq!(think_time(12f64, 0f64), 0f64)
+        assert!((think_time(12f64, 0.5f64) - 12f64 * 2f64.ln()).abs() < 1e-9)
+        assert_eq!(think_time(12f64, 1f64 - 1e-12), 120f64)
+    }
+
+    #[test]
+    fn test_validity() {
+        let mut stats = new_stats();
+        for _ in range(0u, 100) {
+            stats.get_mut(NEW_ORDER).latencies.record(1000000000);
+        }
+        stats.get_mut(PAYMENT).latencies.record(6000000000);
+        let result = RunStats { seconds: 7200f64, transactions: stats };
+        assert_eq!(tpmc(&result), 100f64 / 120f64)
+        let problems = invalid(&result, 1f64);
+        assert_eq!(problems.len(), 2)
+        assert!(problems[0][].ends_with("NewOrder is 99.0% of the mix"))
+        assert!(problems[1][].starts_with("90th percentile response time of payment"))
+        assert_eq!(invalid(&result, 10f64).len(), 3)
+    }
+}
@@ -0,0 +1,448 @@
+//! Parser for the `SELECT` subset of SQL understood by the planner:
+//!
+//!     SELECT * | expr [[AS] alias], ...
+//!     FROM table [alias] {, table [alias] | [INNER] JOIN table [alias] ON expr}
+//!     [WHERE expr] [GROUP BY expr, ...] [ORDER BY expr [ASC | DESC], ...] [LIMIT n]
+//!
+//! `JOIN ... ON` conditions are added to the `WHERE` clause. A statement can
+//! be prefixed with `EXPLAIN [ANALYZE]`.
+
+use std::fmt;
+
+use super::lexer::{Tokens, Ident, QuotedIdent, Number, StringLit, Comma, Dot, Semicolon,
+                   LParen, RParen, Star, Plus, Minus, Equals, NotEquals, LessThan, LessEquals,
+                   GreaterThan, GreaterEquals};
+use super::query::{CompareOp, IsEqual, IsNotEqual, IsLess, IsLessOrEqual, IsGreater,
+                   IsGreaterOrEqual, ArithmeticOp, Addition, Subtraction, Multiplication};
+
+
+#[deriving(Clone, PartialEq)]
+pub enum SqlExpr {
+    /// Optional table name or alias, column name.
+    SqlColumn(Option<String>, String),
+    /// The literal's text, typed by the planner.
+    SqlNumber(String),
+    SqlString(String),
+    SqlNull,
+    SqlArithmetic(Box<SqlExpr>, ArithmeticOp, Box<SqlExpr>),
+    SqlCompare(Box<SqlExpr>, CompareOp, Box<SqlExpr>),
+    SqlAnd(Box<SqlExpr>, Box<SqlExpr>),
+    SqlOr(Box<SqlExpr>, Box<SqlExpr>),
+    SqlNot(Box<SqlExpr>),
+    SqlIsNull(Box<SqlExpr>),
+    /// `count`, `sum`, `min` or `max`; the argument is `None` for `count(*)`.
+    SqlAggregate(String, Option<Box<SqlExpr>>),
+}
+
+/// Prints the expression as SQL, parenthesizing nested operators.
+impl fmt::Show for SqlExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SqlColumn(Some(ref table), ref name) => write!(f, "{}.{}", table, name),
+            SqlColumn(None, ref name) => write!(f, "{}", name),
+            SqlNumber(ref text) => write!(f, "{}", text),
+            SqlString(ref text) => write!(f, "'{}'", text.replace("'", "''")),
+            SqlNull => write!(f, "NULL"),
+            SqlArithmetic(ref left, op, ref right) =>
+                write!(f, "{} {} {}", Operand(&**left), op, Operand(&**right)),
+            SqlCompare(ref left, op, ref right) =>
+                write!(f, "{} {} {}", Operand(&**left), op, Operand(&**right)),
+            SqlAnd(ref left, ref right) =>
+                write!(f, "{} AND {}", Operand(&**left), Operand(&**right)),
+            SqlOr(ref left, ref right) => write!(f, "{} OR {}", Operand(&**left), Operand(&**right)),
+            SqlNot(ref e) => write!(f, "NOT {}", Operand(&**e)),
+            SqlIsNull(ref e) => write!(f, "{} IS NULL", Operand(&**e)),
+            SqlAggregate(ref name, Some(ref argument)) => write!(f, "{}({})", name, argument),
+            SqlAggregate(ref name, None) => write!(f, "{}(*)", name),
+        }
+    }
+}
+
+/// An operand of an operator, parenthesized unless it is a primary expression.
+struct Operand<'a>(&'a SqlExpr);
+
+impl<'a> fmt::Show for Operand<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Operand(e) = *self;
+        match *e {
+            SqlColumn(..) | SqlNumber(..) | SqlString(..) | SqlNull | SqlAggregate(..) =>
+                write!(f, "{}", e),
+            _ => write!(f, "({})", e)
+        }
+    }
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum SelectItem {
+    /// `*`
+    Wildcard,
+    /// An expression and its alias.
+    SelectExpr(SqlExpr, Option<String>),
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct TableRef {
+    pub name: String,
+    /// The table name if no alias is given.
+    pub alias: String,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct OrderItem {
+    pub expr: SqlExpr,
+    pub descending: bool,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct Select {
+    pub items: Vec<SelectItem>,
+    pub from: Vec<TableRef>,
+    pub where_clause: Option<SqlExpr>,
+    pub group_by: Vec<SqlExpr>,
+    pub order_by: Vec<OrderItem>,
+    pub limit: Option<uint>,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Statement {
+    SelectStatement(Select),
+    /// `EXPLAIN [ANALYZE]`, `true` for `ANALYZE`.
+    Explain(Select, bool),
+}
+
+/// Keywords that end an expression or table reference, so they cannot be
+/// used as an alias without `AS`.
+static RESERVED: &'static [&'static str] = &["select", "from", "where", "group", "order", "by",
+                                             "limit", "join", "inner", "on", "as", "and", "or",
+                                             "not", "is", "null", "asc", "desc"];
+
+pub fn parse(input: &str) -> Result<Statement, String> {
+    let mut tokens = try!(Tokens::new(input));
+    let statement = if tokens.eat_keyword("explain") {
+        let analyze = tokens.eat_keyword("analyze");
+        Explain(try!(parse_select(&mut tokens)), analyze)
+    } else {
+        SelectStatement(try!(parse_select(&mut tokens)))
+    };
+    tokens.eat(&Semicolon);
+    if !tokens.at_end() {
+        return Err(tokens.unexpected("end of input"));
+    }
+    Ok(statement)
+}
+
+/// Parses a `SELECT` statement from the current position of `tokens`.
+pub fn parse_select(tokens: &mut Tokens) -> Result<Select, String> {
+    try!(tokens.expect_keyword("select"));
+    let mut items = Vec::new();
+    loop {
+        if tokens.eat(&Star) {
+            items.push(Wildcard);
+        } else {
+            let expr = try!(expr(tokens));
+            let alias = try!(alias(tokens));
+            items.push(SelectExpr(expr, alias));
+        }
+        if !tokens.eat(&Comma) {
+            break;
+        }
+    }
+
+    try!(tokens.expect_keyword("from"));
+    let mut from = vec![try!(table_ref(tokens))];
+    let mut conditions = Vec::new();
+    loop {
+        if tokens.eat(&Comma) {
+            from.push(try!(table_ref(tokens)));
+        } else if tokens.is_keyword("join") || tokens.is_keyword("inner") {
+            tokens.eat_keyword("inner");
+            try!(tokens.expect_keyword("join"));
+            from.push(try!(table_ref(tokens)));
+            try!(tokens.expect_keyword("on"));
+            conditions.push(try!(expr(tokens)));
+        } else {
+            break;
+        }
+    }
+
+    if tokens.eat_keyword("where") {
+        conditions.push(try!(expr(tokens)));
+    }
+    let where_clause = conditions.into_iter().fold(None, |result, condition| match result {
+        Some(result) => Some(SqlAnd(box result, box condition)),
+        None => Some(condition)
+    });
+
+    let mut group_by = Vec::new();
+    if tokens.eat_keyword("group") {
+        try!(tokens.expect_keyword("by"));
+        loop {
+            group_by.push(try!(expr(tokens)));
+            if !tokens.eat(&Comma) {
+                break;
+            }
+        }
+    }
+
+    let mut order_by = Vec::new();
+    if tokens.eat_keyword("order") {
+        try!(tokens.expect_keyword("by"));
+        loop {
+            let expr = try!(expr(tokens));
+            let descending = if tokens.eat_keyword("desc") {
+                true
+            } else {
+                tokens.eat_keyword("asc");
+                false
+            };
+            order_by.push(OrderItem { expr: expr, descending: descending });
+            if !tokens.eat(&Comma) {
+                break;
+            }
+        }
+    }
+
+    let limit = if tokens.eat_keyword("limit") { Some(try!(tokens.uint())) } else { None };
+
+    Ok(Select {
+        items: items,
+        from: from,
+        where_clause: where_clause,
+        group_by: group_by,
+        order_by: order_by,
+        limit: limit,
+    })
+}
+
+/// `[AS] alias`
+fn alias(tokens: &mut Tokens) -> Result<Option<String>, String> {
+    if tokens.eat_keyword("as") {
+        return tokens.ident().map(|alias| Some(alias));
+    }
+    let is_alias = match tokens.peek() {
+        Some(&Ident(ref word)) => !RESERVED.contains(&word[]),
+        Some(&QuotedIdent(_)) => true,
+        _ => false
+    };
+    if is_alias { tokens.ident().map(|alias| Some(alias)) } else { Ok(None) }
+}
+
+fn table_ref(tokens: &mut Tokens) -> Result<TableRef, String> {
+    let name = try!(tokens.ident());
+    let alias = try!(alias(tokens)).unwrap_or_else(|| name.clone());
+    Ok(TableRef { name: name, alias: alias })
+}
+
+fn expr(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    let mut left = try!(and_expr(tokens));
+    while tokens.eat_keyword("or") {
+        left = SqlOr(box left, box try!(and_expr(tokens)));
+    }
+    Ok(left)
+}
+
+fn and_expr(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    let mut left = try!(not_expr(tokens));
+    while tokens.eat_keyword("and") {
+        left = SqlAnd(box left, box try!(not_expr(tokens)));
+    }
+    Ok(left)
+}
+
+fn not_expr(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    if tokens.eat_keyword("not") {
+        return Ok(SqlNot(box try!(not_expr(tokens))));
+    }
+    comparison(tokens)
+}
+
+/// `a op b`, `a IS [NOT] NULL`
+fn comparison(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    let left = try!(additive(tokens));
+    if tokens.eat_keyword("is") {
+        let negated = tokens.eat_keyword("not");
+        try!(tokens.expect_keyword("null"));
+        let is_null = SqlIsNull(box left);
+        return Ok(if negated { SqlNot(box is_null) } else { is_null });
+    }
+    let op = match tokens.peek() {
+        Some(&Equals) => IsEqual,
+        Some(&NotEquals) => IsNotEqual,
+        Some(&LessThan) => IsLess,
+        Some(&LessEquals) => IsLessOrEqual,
+        Some(&GreaterThan) => IsGreater,
+        Some(&GreaterEquals) => IsGreaterOrEqual,
+        _ => return Ok(left)
+    };
+    tokens.next();
+    Ok(SqlCompare(box left, op, box try!(additive(tokens))))
+}
+
+fn additive(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    let mut left = try!(multiplicative(tokens));
+    loop {
+        let op = match tokens.peek() {
+            Some(&Plus) => Addition,
+            Some(&Minus) => Subtraction,
+            _ => return Ok(left)
+        };
+        tokens.next();
+        left = SqlArithmetic(box left, op, box try!(multiplicative(tokens)));
+    }
+}
+
+fn multiplicative(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    let mut left = try!(primary(tokens));
+    while tokens.eat(&Star) {
+        left = SqlArithmetic(box left, Multiplication, box try!(primary(tokens)));
+    }
+    Ok(left)
+}
+
+fn primary(tokens: &mut Tokens) -> Result<SqlExpr, String> {
+    let token = match tokens.peek() {
+        Some(token) => token.clone(),
+        None => return Err(tokens.unexpected("expression"))
+    };
+    match token {
+        Number(text) => {
+            tokens.next();
+            Ok(SqlNumber(text))
+        },
+        Minus => {
+            tokens.next();
+            match tokens.next() {
+                Some(Number(text)) => Ok(SqlNumber(format!("-{}", text))),
+                _ => Err("syntax error: expected number after -".to_string())
+            }
+        },
+        StringLit(text) => {
+            tokens.next();
+            Ok(SqlString(text))
+        },
+        LParen => {
+            tokens.next();
+            let e = try!(expr(tokens));
+            try!(tokens.expect(&RParen));
+            Ok(e)
+        },
+        Ident(ref word) if word[] == "null" => {
+            tokens.next();
+            Ok(SqlNull)
+        },
+        Ident(ref word) if RESERVED.contains(&word[]) => Err(tokens.unexpected("expression")),
+        Ident(_) | QuotedIdent(_) => {
+            let name = try!(tokens.ident());
+            if tokens.peek() == Some(&LParen) {
+                return aggregate(tokens, name);
+            }
+            if tokens.eat(&Dot) {
+                Ok(SqlColumn(Some(name), try!(tokens.ident())))
+            } else {
+                Ok(SqlColumn(None, name))
+            }
+        },
+        _ => Err(tokens.unexpected("expression"))
+    }
+}
+
+/// `count(*)` or `function(expr)`, the name already being consumed.
+fn aggregate(tokens: &mut Tokens, name: String) -> Result<SqlExpr, String> {
+    if !["count", "sum", "min", "max"].contains(&name[]) {
+        return Err(format!("function {} does not exist", name));
+    }
+    try!(tokens.expect(&LParen));
+    let arg = if name[] == "count" && tokens.eat(&Star) {
+        None
+    } else {
+        Some(box try!(expr(tokens)))
+    };
+    try!(tokens.expect(&RParen));
+    Ok(SqlAggregate(name, arg))
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use query::{IsEqual, IsGreater, Multiplication};
+
+    use super::{parse, Select, SelectStatement, Explain, SqlExpr, SelectExpr, Wildcard, TableRef,
+                OrderItem, SqlColumn, SqlNumber, SqlString, SqlArithmetic, SqlCompare, SqlAnd,
+                SqlNot, SqlIsNull, SqlAggregate};
+
+    fn column(name: &str) -> SqlExpr {
+        SqlColumn(None, name.to_string())
+    }
+
+    fn select(sql: &str) -> Select {
+        match parse(sql).unwrap() {
+            SelectStatement(select) => select,
+            Explain(..) => fail!("not a SELECT: {}", sql)
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let select = select("SELECT c.c_id, sum(ol_amount * 2) AS total \
+                            FROM customer c JOIN orderline ON c.c_id = ol_c_id \
+                            WHERE c_last = 'ABLE' AND ol_amount > 10 \
+                            GROUP BY c.c_id ORDER BY total DESC LIMIT 5;");
+        let c_id = SqlColumn(Some("c".to_string()), "c_id".to_string());
+        assert_eq!(select.items, vec![
+            SelectExpr(c_id.clone(), None),
+            SelectExpr(SqlAggregate("sum".to_string(),
+                                    Some(box SqlArithmetic(box column("ol_amount"), Multiplication,
+                                                           box SqlNumber("2".to_string())))),
+                       Some("total".to_string()))])
+        assert_eq!(select.from, vec![
+            TableRef { name: "customer".to_string(), alias: "c".to_string() },
+            TableRef { name: "orderline".to_string(), alias: "orderline".to_string() }])
+        assert_eq!(select.where_clause, Some(SqlAnd(
+            box SqlCompare(box c_id.clone(), IsEqual, box column("ol_c_id")),
+            box SqlAnd(box SqlCompare(box column("c_last"), IsEqual,
+                                      box SqlString("ABLE".to_string())),
+                       box SqlCompare(box column("ol_amount"), IsGreater,
+                                      box SqlNumber("10".to_string()))))))
+        assert_eq!(select.group_by, vec![c_id])
+        assert_eq!(select.order_by, vec![OrderItem { expr: column("total"), descending: true }])
+        assert_eq!(select.limit, Some(5))
+    }
+
+    #[test]
+    fn test_star_and_null() {
+        let select = select("select *, count(*) from item where not i_data is not null");
+        assert_eq!(select.items, vec![Wildcard, SelectExpr(SqlAggregate("count".to_string(), None),
+                                                           None)])
+        assert_eq!(select.where_clause,
+                   Some(SqlNot(box SqlNot(box SqlIsNull(box column("i_data"))))))
+    }
+
+    #[test]
+    fn test_explain() {
+        match parse("explain analyze select * from item").unwrap() {
+            Explain(select, analyze) => {
+                assert!(analyze)
+                assert_eq!(select.from[0].name, "item".to_string())
+            },
+            SelectStatement(..) => fail!("not an EXPLAIN")
+        }
+    }
+
+    #[test]
+    fn test_show() {
+        let select = select("select * from t where (a + 1) * 2 > b and not c = 'it''s' \
+                             or sum(d) is null");
+        assert_eq!(select.where_clause.unwrap().to_string(),
+                   "((((a + 1) * 2) > b) AND (NOT (c = 'it''s'))) OR (sum(d) IS NULL)".to_string())
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse("select from item").is_err())
+        assert!(parse("explain explain select * from item").is_err())
+        assert!(parse("select avg(i_price) from item").is_err())
+        assert!(parse("select * from item where").is_err())
+        assert!(parse("select * from item limit 5 5").is_err())
+    }
+}
@@ -1,15 +1,277 @@
+use std::cmp::max;
 use std::collections::HashMap;
-use std::str::CharSplits;
+use std::fmt;
+use std::io::{BufferedReader, File};
+use std::os;
+use std::slice::Items;
+use std::str;
+use std::sync::Arc;
 
 use collections::hash::Hash;
+use time::precise_time_ns;
+
+use super::format::{Fields, Format, Parser};
+use super::null::Truth;
+use super::numeric::Numeric;
+#[cfg(test)]
+use super::tables::Tables;
+use super::timestamp::Timestamp;
 
 
 pub trait PrimaryKey<T> {
     fn primary_key(&self) -> T;
 }
 
+/// Implemented by `#[deriving_from_row]`, see the `table_derive` crate.
 pub trait FromRow {
-    fn from_row(mut row: CharSplits<char>) -> Self;
+    fn from_row<'a, I: Iterator<&'a str>>(mut row: I) -> Result<Self, RowError>;
+
+    /// The columns in the order `from_row` reads them. The argument only
+    /// selects the implementation, as in `FromRow::columns(None::<District>)`.
+    fn columns(_: Option<Self>) -> Vec<&'static str>;
+}
+
+/// Why a `.tbl` line could not be turned into a row.
+#[deriving(PartialEq, Clone)]
+pub enum RowError {
+    MissingField(String),
+    /// The column, the offending value and what is wrong with it.
+    InvalidField(String, String, String),
+}
+
+impl fmt::Show for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MissingField(ref column) => write!(f, "missing value for column {}", column),
+            InvalidField(ref column, ref value, ref reason) =>
+                write!(f, "invalid value \"{}\" for column {}: {}", value, column, reason)
+        }
+    }
+}
+
+/// Parsing of a single `.tbl` field for all non-numeric column types.
+pub trait FromField {
+    fn from_field(field: &str) -> Result<Self, String>;
+}
+
+impl FromField for i32 {
+    fn from_field(field: &str) -> Result<i32, String> {
+        from_str(field).ok_or("not an integer".to_string())
+    }
+}
+
+impl FromField for i64 {
+    fn from_field(field: &str) -> Result<i64, String> {
+        from_str(field).ok_or("not an integer".to_string())
+    }
+}
+
+impl FromField for Timestamp {
+    fn from_field(field: &str) -> Result<Timestamp, String> {
+        from_str(field).ok_or("not a timestamp".to_string())
+    }
+}
+
+/// The empty field is NULL.
+impl<T: FromField> FromField for Option<T> {
+    fn from_field(field: &str) -> Result<Option<T>, String> {
+        if field.is_empty() {
+            Ok(None)
+        } else {
+            FromField::from_field(field).map(|value| Some(value))
+        }
+    }
+}
+
+pub fn field<'a, T: FromField, I: Iterator<&'a str>>(row: &mut I, column: &'static str)
+        -> Result<T, RowError> {
+    let value = try!(next_field(row, column));
+    FromField::from_field(value)
+        .map_err(|reason| InvalidField(column.to_string(), value.to_string(), reason))
+}
+
+pub fn numeric_field<'a, I: Iterator<&'a str>>(row: &mut I, column: &'static str, len: uint,
+                                               precision: uint) -> Result<Numeric, RowError> {
+    let value = try!(next_field(row, column));
+    Numeric::from_str(value, len, precision)
+        .map_err(|e| InvalidField(column.to_string(), value.to_string(), e.to_string()))
+}
+
+pub fn nullable_numeric_field<'a, I: Iterator<&'a str>>(row: &mut I, column: &'static str,
+                                                        len: uint, precision: uint)
+        -> Result<Option<Numeric>, RowError> {
+    let value = try!(next_field(row, column));
+    if value.is_empty() {
+        return Ok(None);
+    }
+    Numeric::from_str(value, len, precision)
+        .map(|numeric| Some(numeric))
+        .map_err(|e| InvalidField(column.to_string(), value.to_string(), e.to_string()))
+}
+
+pub fn read_table<Data: Clone + FromRow + PrimaryKey<Index>, Index: Eq + Hash>
+        (filename: &str, table: &mut Table<Data, Index>) {
+    read_table_with(filename, &Format::tbl(), table);
+}
+
+/// Like `read_table`, for a file in `format`.
+pub fn read_table_with<Data: Clone + FromRow + PrimaryKey<Index>, Index: Eq + Hash>
+        (filename: &str, format: &Format, table: &mut Table<Data, Index>) {
+    let columns = FromRow::columns(None::<Data>);
+    read_records(filename, format, columns[], |row| {
+        table.insert(try!(FromRow::from_row(row)));
+        Ok(())
+    });
+}
+
+/// Calls `insert` with the fields of every line of a `.tbl` file.
+pub fn read_tbl(filename: &str, insert: |Fields| -> Result<(), RowError>) {
+    read_records(filename, &Format::tbl(), [], insert);
+}
+
+/// Calls `insert` with the fields of every record of a file in `format`,
+/// which are those of `columns` in this order if the file has a header.
+pub fn read_records(filename: &str, format: &Format, columns: &[&str],
+                    insert: |Fields| -> Result<(), RowError>) {
+    let path = Path::new(filename);
+    let mut file = BufferedReader::new(File::open(&path));
+    let mut parser = Parser::new(format.clone());
+    let mut header = format.header;
+    // the line the current record starts on
+    let mut first = 0;
+    for (i, line) in file.lines().enumerate() {
+        let line = line.unwrap();
+        if !parser.pending() {
+            first = i;
+        }
+        if !parser.push(line[].trim_right_chars(['\r', '\n'][])) {
+            continue;
+        }
+        let result = if header {
+            header = false;
+            parser.set_header(columns)
+        } else {
+            insert(parser.fields()).map_err(|e| e.to_string())
+        };
+        if let Err(e) = result {
+            fail!("{}:{}: {}", filename, first + 1, e)
+        }
+    }
+    if parser.pending() {
+        fail!("{}:{}: unterminated quoted field", filename, first + 1)
+    }
+}
+
+/// `bulk_load` if `filename` exists, otherwise the table stays empty. Only
+/// `chgen` writes the files of the CH-benCHmark tables, for example.
+pub fn bulk_load_if_exists<Data: Clone + FromRow + PrimaryKey<Index> + Send,
+                           Index: Eq + Hash>
+        (filename: &str, format: &Format, table: &mut Table<Data, Index>) {
+    if !Path::new(filename).exists() {
+        println!("{}: not found, the table stays empty", filename);
+        return;
+    }
+    bulk_load(filename, format, table);
+}
+
+/// Loads a file in `format` by parsing chunks of it in parallel, one per
+/// CPU, and inserting the rows of all chunks in file order. Prints how long
+/// it took. A record may span lines if the format has quotes and only the
+/// first chunk would see a header, so such files are read by
+/// `read_table_with` instead.
+pub fn bulk_load<Data: Clone + FromRow + PrimaryKey<Index> + Send, Index: Eq + Hash>
+        (filename: &str, format: &Format, table: &mut Table<Data, Index>) {
+    let time = precise_time_ns();
+    if format.quote.is_some() || format.header {
+        read_table_with(filename, format, table);
+        println!("{}: {} rows in {:.3}s", filename, table.size(),
+                 (precise_time_ns() - time) as f64 / 1e9f64);
+        return;
+    }
+    let bytes = match File::open(&Path::new(filename)).read_to_end() {
+        Ok(bytes) => Arc::new(bytes),
+        Err(e) => fail!("{}: {}", filename, e)
+    };
+    if str::from_utf8(bytes.as_slice()).is_none() {
+        fail!("{}: not UTF-8", filename)
+    }
+    // the chunks end after a newline, so they are UTF-8 as well
+    let chunks = chunks(bytes.as_slice(), os::num_cpus());
+
+    let (sender, receiver) = channel();
+    for (i, &(start, end)) in chunks.iter().enumerate() {
+        let sender = sender.clone();
+        let bytes = bytes.clone();
+        let format = format.clone();
+        spawn(proc() {
+            let text = str::from_utf8(bytes.as_slice()[start..end]).unwrap();
+            sender.send((i, parse_chunk::<Data>(text, format)));
+        });
+    }
+    let mut parsed: Vec<Option<Result<Vec<Data>, (uint, RowError)>>> =
+        Vec::from_fn(chunks.len(), |_| None);
+    for _ in range(0, chunks.len()) {
+        let (i, rows) = receiver.recv();
+        *parsed.get_mut(i) = Some(rows);
+    }
+
+    let mut chunk_rows = Vec::with_capacity(parsed.len());
+    for (rows, &(start, _)) in parsed.into_iter().zip(chunks.iter()) {
+        match rows.unwrap() {
+            Ok(rows) => chunk_rows.push(rows),
+            Err((line, e)) => {
+                let lines_before = bytes.as_slice()[..start].iter()
+                    .filter(|&&b| b == b'\n')
+                    .count();
+                fail!("{}:{}: {}", filename, lines_before + line + 1, e)
+            }
+        }
+    }
+    table.reserve(chunk_rows.iter().fold(0, |count, rows| count + rows.len()));
+    for rows in chunk_rows.into_iter() {
+        for row in rows.into_iter() {
+            table.insert(row);
+        }
+    }
+    println!("{}: {} rows in {:.3}s", filename, table.size(),
+             (precise_time_ns() - time) as f64 / 1e9f64);
+}
+
+/// Splits `bytes` into at most `n` ranges of about the same size, each
+/// ending after a newline or at the end.
+fn chunks(bytes: &[u8], n: uint) -> Vec<(uint, uint)> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for i in range(1, n + 1) {
+        let mut end = max(start, bytes.len() * i / n);
+        while end > start && end < bytes.len() && bytes[end - 1] != b'\n' {
+            end += 1;
+        }
+        if end > start {
+            chunks.push((start, end));
+            start = end;
+        }
+    }
+    chunks
+}
+
+/// The rows of the lines of `text`, a record per line, or the number of the
+/// first line that cannot be parsed, counted from 0.
+fn parse_chunk<Data: FromRow>(text: &str, format: Format)
+        -> Result<Vec<Data>, (uint, RowError)> {
+    let mut parser = Parser::new(format);
+    let mut rows = Vec::new();
+    for (i, line) in text.split_terminator('\n').enumerate() {
+        if parser.push(line.trim_right_chars('\r')) {
+            rows.push(try!(FromRow::from_row(parser.fields()).map_err(|e| (i, e))));
+        }
+    }
+    Ok(rows)
+}
+
+fn next_field<'a, I: Iterator<&'a str>>(row: &mut I, column: &'static str)
+        -> Result<&'a str, RowError> {
+    row.next().ok_or(MissingField(column.to_string()))
 }
 
 #[deriving(Show)]
@@ -27,8 +289,15 @@ impl<Data: PrimaryKey<Index> + Clone, Index: Eq + Hash> Table<Data, Index> {
     }
 
     pub fn insert(&mut self, data: Data) {
-        self.rows.push(data.clone());
-        self.index.insert(data.primary_key(), self.rows.len() - 1);
+        let key = data.primary_key();
+        self.rows.push(data);
+        self.index.insert(key, self.rows.len() - 1);
+    }
+
+    /// Makes room for `additional` more rows in the rows and the index.
+    pub fn reserve(&mut self, additional: uint) {
+        self.rows.reserve_additional(additional);
+        self.index.reserve(self.rows.len() + additional);
     }
 
     pub fn lookup(&self, index_elem: Index) -> &Data {
@@ -39,7 +308,127 @@ impl<Data: PrimaryKey<Index> + Clone, Index: Eq + Hash> Table<Data, Index> {
         self.rows.get_mut(self.index[index_elem])
     }
 
+    /// Like `lookup`, but `None` for a key that does not exist.
+    pub fn find(&self, index_elem: &Index) -> Option<&Data> {
+        self.index.find(index_elem).map(|&pos| &self.rows[pos])
+    }
+
     pub fn size(&self) -> uint {
         self.rows.len()
     }
+
+    pub fn scan(&self) -> Items<Data> {
+        self.rows.iter()
+    }
+
+    /// All rows for which `predicate` is `True`, rows yielding `Unknown`
+    /// because of NULLs are dropped just like in a SQL `WHERE` clause.
+    pub fn filter(&self, predicate: |&Data| -> Truth) -> Vec<&Data> {
+        self.rows.iter().filter(|row| predicate(*row).is_true()).collect()
+    }
+}
+
+/// A row from a line of `.tbl` fields, for the tests.
+#[cfg(test)]
+pub fn row<T: FromRow>(line: &str) -> T {
+    FromRow::from_row(line.split('|')).unwrap()
+}
+
+/// Warehouse 1 with two districts, a delivered and a new order of customer 1
+/// in district 1 and no orders in district 2, meeting the consistency
+/// conditions. The tests start from it and add or change rows.
+#[cfg(test)]
+pub fn fixture() -> Tables {
+    let mut t = Tables::new();
+    t.warehouse_table.insert(row("1|name|street|street|city|CA|123456789|0.1000|300.00"));
+    t.district_table.insert(row("1|1|one|street|street|city|CA|123456789|0.1000|100.00|3"));
+    t.district_table.insert(row("2|1|two|street|street|city|CA|123456789|0.1000|200.00|1"));
+    t.customer_table.insert(row("1|1|1|first|OE|LAST|street|street|city|CA|123456789|\
+                                 1000000000000000|2010-05-01|GC|50000.00|0.1000|20.00|\
+                                 10.00|1|1|data"));
+    t.order_table.insert(row("1|1|1|1|2010-05-01|5|1|1"));
+    t.order_table.insert(row("2|1|1|1|2010-05-02||1|1"));
+    t.neworder_table.insert(row("2|1|1"));
+    t.orderline_table.insert(row("1|1|1|1|1|1|2010-05-03|5|30.00|info"));
+    t.orderline_table.insert(row("2|1|1|1|1|1||5|40.00|info"));
+    t
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::io::{File, TempDir};
+
+    use super::{bulk_load, chunks, field, numeric_field, Table, MissingField, InvalidField};
+    use format::Format;
+    use numeric::Numeric;
+    use tables::ItemTable;
+
+    /// Writes `contents` to the file `name` in `dir` and returns its path.
+    fn write(dir: &TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        File::create(&path).write_str(contents).unwrap();
+        path.as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_chunks() {
+        let bytes = b"1|a\n22|bb\n333|ccc\n4|d";
+        assert_eq!(chunks(bytes, 1), vec![(0, bytes.len())])
+        assert_eq!(chunks(bytes, 2), vec![(0, 10), (10, 21)])
+        // a chunk that would end in the middle of a line is extended
+        assert_eq!(chunks(bytes, 4), vec![(0, 10), (10, 18), (18, 21)])
+        assert_eq!(chunks(b"1|a\n", 8), vec![(0, 4)])
+        assert!(chunks(b"", 4).is_empty())
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut row = "1||x|12.50".split('|');
+        assert_eq!(field::<i32, _>(&mut row, "a"), Ok(1))
+        assert_eq!(field::<Option<i32>, _>(&mut row, "b"), Ok(None))
+        assert_eq!(field::<Option<i32>, _>(&mut row, "c"),
+                   Err(InvalidField("c".to_string(), "x".to_string(), "not an integer".to_string())))
+        assert_eq!(numeric_field(&mut row, "d", 4, 2), Ok(Numeric::new(1250, 4, 2)))
+        assert_eq!(field::<i32, _>(&mut row, "e"), Err(MissingField("e".to_string())))
+    }
+
+    #[test]
+    fn test_bulk_load_csv_with_header() {
+        let dir = TempDir::new("table").unwrap();
+        let filename = write(&dir, "item.csv", "i_data,i_price,i_id,i_im_id,i_name,i_extra\n\
+                                                \"a, b\",10.50,1,7,\"one \"\"x\"\"\",z\n\
+                                                plain,2.00,2,8,two,z\n");
+        let mut table: ItemTable = Table::new();
+        bulk_load(filename[], &Format { header: true, ..Format::csv() }, &mut table);
+        assert_eq!(table.size(), 2)
+        let item = table.lookup(1);
+        assert_eq!(item.i_name.as_slice(), "one \"x\"")
+        assert_eq!(item.i_data.as_slice(), "a, b")
+        assert_eq!(item.i_price, Numeric::new(1050, 5, 2))
+        assert_eq!(table.lookup(2).i_im_id, 8)
+    }
+
+    #[test]
+    fn test_bulk_load_trailing_blank_line() {
+        let dir = TempDir::new("table").unwrap();
+        let filename = write(&dir, "item.tbl", "1|7|one|10.50|data\n2|8|two|2.00|data\n\n");
+        let mut table: ItemTable = Table::new();
+        bulk_load(filename[], &Format::tbl(), &mut table);
+        assert_eq!(table.size(), 2)
+    }
+
+    #[test]
+    fn test_bulk_load_tsv() {
+        let dir = TempDir::new("table").unwrap();
+        let filename = write(&dir, "item.tsv", "# items\n1\t7\tone\t10.50\tdata\t\n\
+                                                2\t8\ttwo\t2.00\t\t\r\n");
+        let format = Format { trailing_delimiter: true, comment: Some('#'), ..Format::tsv() };
+        let mut table: ItemTable = Table::new();
+        bulk_load(filename[], &format, &mut table);
+        assert_eq!(table.size(), 2)
+        assert_eq!(table.lookup(1).i_name.as_slice(), "one")
+        assert_eq!(table.lookup(2).i_data.as_slice(), "")
+    }
 }
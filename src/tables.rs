@@ -1,213 +1,161 @@
-use std::str::CharSplits;
+// Generated by `rust-code codegen schema.sql`, do not edit.
 
-use super::table::{FromRow, PrimaryKey, Table};
+use super::column::ColumnTable;
+use super::format::Format;
 use super::numeric::Numeric;
+use super::query::{Database, Relation};
+use super::table::{bulk_load_if_exists, Table};
+use super::timestamp::Timestamp;
+
+
+char_type!(Char2, 2)
+char_type!(Char9, 9)
+char_type!(Char15, 15)
+char_type!(Char16, 16)
+char_type!(Char24, 24)
+char_type!(Char25, 25)
+char_type!(Char55, 55)
+char_type!(Char101, 101)
+char_type!(Char152, 152)
+
+varchar_type!(Varchar10, 10)
+varchar_type!(Varchar16, 16)
+varchar_type!(Varchar20, 20)
+varchar_type!(Varchar24, 24)
+varchar_type!(Varchar40, 40)
+varchar_type!(Varchar50, 50)
+varchar_type!(Varchar500, 500)
+
 
 #[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(w_id)]
+#[deriving_columns]
 pub struct Warehouse {
-    w_id: i32,
-    w_name: String,
-    w_street_1: String,
-    w_street_2: String,
-    w_city: String,
-    w_state: String,
-    w_zip: String,
-    pub w_tax: Numeric, // numeric(4, 4)
-    w_ytd: Numeric, // numeric(12, 2)
-}
-
-impl PrimaryKey<i32> for Warehouse {
-    fn primary_key(&self) -> i32 {
-        self.w_id
-    }
-}
-
-impl FromRow for Warehouse {
-    fn from_row(mut row: CharSplits<char>) -> Warehouse {
-        Warehouse {
-            w_id: from_str(row.next().unwrap()).unwrap(),
-            w_name: row.next().unwrap().into_string(),
-            w_street_1: row.next().unwrap().into_string(),
-            w_street_2: row.next().unwrap().into_string(),
-            w_city: row.next().unwrap().into_string(),
-            w_state: row.next().unwrap().into_string(),
-            w_zip: row.next().unwrap().into_string(),
-            w_tax: Numeric::from_str(row.next().unwrap(), 4, 4).unwrap(),
-            w_ytd: Numeric::from_str(row.next().unwrap(), 12, 2).unwrap(),
-        }
-    }
+    pub w_id: i32,
+    pub w_name: Varchar10,
+    pub w_street_1: Varchar20,
+    pub w_street_2: Varchar20,
+    pub w_city: Varchar20,
+    pub w_state: Char2,
+    pub w_zip: Char9,
+    #[numeric = "4,4"]
+    pub w_tax: Numeric,
+    #[numeric = "12,2"]
+    pub w_ytd: Numeric,
 }
 
 pub type  WarehouseTable = Table<Warehouse, i32>;
+pub type  WarehouseColumnTable = ColumnTable<Warehouse, WarehouseColumns, i32>;
 
 
 #[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(d_w_id, d_id)]
+#[deriving_columns]
 pub struct District {
-    d_id: i32,
-    d_w_id: i32,
-    d_name: String,
-    d_street_1: String,
-    d_street_2: String,
-    d_city: String,
-    d_state: String,
-    d_zip: String,
-    pub d_tax: Numeric, // numeric(4, 4)
-    d_ytd: Numeric, // numeric(12,2)
+    pub d_id: i32,
+    pub d_w_id: i32,
+    pub d_name: Varchar10,
+    pub d_street_1: Varchar20,
+    pub d_street_2: Varchar20,
+    pub d_city: Varchar20,
+    pub d_state: Char2,
+    pub d_zip: Char9,
+    #[numeric = "4,4"]
+    pub d_tax: Numeric,
+    #[numeric = "12,2"]
+    pub d_ytd: Numeric,
     pub d_next_o_id: i32,
 }
 
-impl PrimaryKey<(i32, i32)> for District {
-    fn primary_key(&self) -> (i32, i32) {
-        (self.d_w_id, self.d_id)
-    }
-}
-
-impl FromRow for District {
-    fn from_row(mut row: CharSplits<char>) -> District {
-        District {
-            d_id: from_str(row.next().unwrap()).unwrap(),
-            d_w_id: from_str(row.next().unwrap()).unwrap(),
-            d_name: row.next().unwrap().into_string(),
-            d_street_1: row.next().unwrap().into_string(),
-            d_street_2: row.next().unwrap().into_string(),
-            d_city: row.next().unwrap().into_string(),
-            d_state: row.next().unwrap().into_string(),
-            d_zip: row.next().unwrap().into_string(),
-            d_tax: Numeric::from_str(row.next().unwrap(), 4, 4).unwrap(),
-            d_ytd: Numeric::from_str(row.next().unwrap(), 12, 2).unwrap(),
-            d_next_o_id: from_str(row.next().unwrap()).unwrap(),
-        }
-    }
-}
-
 pub type  DistrictTable = Table<District, (i32, i32)>;
+pub type  DistrictColumnTable = ColumnTable<District, DistrictColumns, (i32, i32)>;
 
 
 #[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(c_w_id, c_d_id, c_id)]
+#[deriving_columns]
 pub struct Customer {
-    c_id: i32,
-    c_d_id: i32,
-    c_w_id: i32,
-    c_first: String,
-    c_middle: String,
-    c_last: String,
-    c_street_1: String,
-    c_street_2: String,
-    c_city: String,
-    c_state: String,
-    c_zip: String,
-    c_phone: String,
-    c_since: i32, // Timestamp
-    c_credit: String,
-    c_credit_lim: Numeric, // numeric(12,2)
-    pub c_discount: Numeric, // numeric(4, 4)
-    c_balance: Numeric, // numeric(12,2)
-    c_ytd_paymenr: Numeric, // numeric(12,2)
-    c_payment_cnt: Numeric, // numeric(4,0)
-    c_delivery_cnt: Numeric, // numeric(4,0)
-    c_data: String,
-}
-
-impl PrimaryKey<(i32, i32, i32)> for Customer {
-    fn primary_key(&self) -> (i32, i32, i32) {
-        (self.c_w_id, self.c_d_id, self.c_id)
-    }
-}
-
-impl FromRow for Customer {
-    fn from_row(mut row: CharSplits<char>) -> Customer {
-        Customer {
-            c_id: from_str(row.next().unwrap()).unwrap(),
-            c_d_id: from_str(row.next().unwrap()).unwrap(),
-            c_w_id: from_str(row.next().unwrap()).unwrap(),
-            c_first: row.next().unwrap().into_string(),
-            c_middle: row.next().unwrap().into_string(),
-            c_last: row.next().unwrap().into_string(),
-            c_street_1: row.next().unwrap().into_string(),
-            c_street_2: row.next().unwrap().into_string(),
-            c_city: row.next().unwrap().into_string(),
-            c_state: row.next().unwrap().into_string(),
-            c_zip: row.next().unwrap().into_string(),
-            c_phone: row.next().unwrap().into_string(),
-            c_since: from_str(row.next().unwrap()).unwrap(), // Timestamp
-            c_credit: row.next().unwrap().into_string(),
-            c_credit_lim: Numeric::from_str(row.next().unwrap(), 12, 2).unwrap(), // numeric(12,2)
-            c_discount: Numeric::from_str(row.next().unwrap(), 4, 4).unwrap(), // numeric(4, 4)
-            c_balance: Numeric::from_str(row.next().unwrap(), 12, 2).unwrap(), // numeric(12,2)
-            c_ytd_paymenr: Numeric::from_str(row.next().unwrap(), 12, 2).unwrap(), // numeric(12,2)
-            c_payment_cnt: Numeric::from_str(row.next().unwrap(), 4, 0).unwrap(), // numeric(4,0)
-            c_delivery_cnt: Numeric::from_str(row.next().unwrap(), 4, 0).unwrap(), // numeric(4,0)
-            c_data: row.next().unwrap().into_string(),
-        }
-    }
+    pub c_id: i32,
+    pub c_d_id: i32,
+    pub c_w_id: i32,
+    pub c_first: Varchar16,
+    pub c_middle: Char2,
+    pub c_last: Varchar16,
+    pub c_street_1: Varchar20,
+    pub c_street_2: Varchar20,
+    pub c_city: Varchar20,
+    pub c_state: Char2,
+    pub c_zip: Char9,
+    pub c_phone: Char16,
+    pub c_since: Timestamp,
+    pub c_credit: Char2,
+    #[numeric = "12,2"]
+    pub c_credit_lim: Numeric,
+    #[numeric = "4,4"]
+    pub c_discount: Numeric,
+    #[numeric = "12,2"]
+    pub c_balance: Numeric,
+    #[numeric = "12,2"]
+    pub c_ytd_payment: Numeric,
+    #[numeric = "4,0"]
+    pub c_payment_cnt: Numeric,
+    #[numeric = "4,0"]
+    pub c_delivery_cnt: Numeric,
+    pub c_data: Varchar500,
 }
 
 pub type  CustomerTable = Table<Customer, (i32, i32, i32)>;
+pub type  CustomerColumnTable = ColumnTable<Customer, CustomerColumns, (i32, i32, i32)>;
 
 
 #[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(no_w_id, no_d_id, no_o_id)]
+#[deriving_columns]
 pub struct Neworder {
     pub no_o_id: i32,
     pub no_d_id: i32,
     pub no_w_id: i32,
 }
 
-impl PrimaryKey<(i32, i32, i32)> for Neworder {
-    fn primary_key(&self) -> (i32, i32, i32) {
-        (self.no_w_id, self.no_d_id, self.no_o_id)
-    }
-}
-
-impl FromRow for Neworder {
-    fn from_row(mut row: CharSplits<char>) -> Neworder {
-        Neworder {
-            no_o_id: from_str(row.next().unwrap()).unwrap(),
-            no_d_id: from_str(row.next().unwrap()).unwrap(),
-            no_w_id: from_str(row.next().unwrap()).unwrap(),
-        }
-    }
-}
-
 pub type  NeworderTable = Table<Neworder, (i32, i32, i32)>;
+pub type  NeworderColumnTable = ColumnTable<Neworder, NeworderColumns, (i32, i32, i32)>;
 
 
 #[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(o_w_id, o_d_id, o_id)]
+#[deriving_columns]
 pub struct Order {
     pub o_id: i32,
     pub o_d_id: i32,
     pub o_w_id: i32,
     pub o_c_id: i32,
-    pub o_entry_d: i64, // Timestamp
-    pub o_carrier_id: i32,
-    pub o_ol_cnt: Numeric, // numeric(2,0)
-    pub o_all_local: Numeric, // numeric(1, 0)
-}
-
-impl PrimaryKey<(i32, i32, i32)> for Order {
-    fn primary_key(&self) -> (i32, i32, i32) {
-        (self.o_w_id, self.o_d_id, self.o_id)
-    }
-}
-
-impl FromRow for Order {
-    fn from_row(mut row: CharSplits<char>) -> Order {
-        Order {
-            o_id: from_str(row.next().unwrap()).unwrap(),
-            o_d_id: from_str(row.next().unwrap()).unwrap(),
-            o_w_id: from_str(row.next().unwrap()).unwrap(),
-            o_c_id: from_str(row.next().unwrap()).unwrap(),
-            o_entry_d: from_str(row.next().unwrap()).unwrap(),
-            o_carrier_id: from_str(row.next().unwrap()).unwrap(),
-            o_ol_cnt: Numeric::from_str(row.next().unwrap(), 2, 0).unwrap(),
-            o_all_local: Numeric::from_str(row.next().unwrap(), 1, 0).unwrap(),
-        }
-    }
+    pub o_entry_d: Timestamp,
+    pub o_carrier_id: Option<i32>,
+    #[numeric = "2,0"]
+    pub o_ol_cnt: Numeric,
+    #[numeric = "1,0"]
+    pub o_all_local: Numeric,
 }
 
 pub type  OrderTable = Table<Order, (i32, i32, i32)>;
+pub type  OrderColumnTable = ColumnTable<Order, OrderColumns, (i32, i32, i32)>;
 
 
 #[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(ol_w_id, ol_d_id, ol_o_id, ol_number)]
+#[deriving_columns]
 pub struct Orderline {
     pub ol_o_id: i32,
     pub ol_d_id: i32,
@@ -215,119 +163,215 @@ pub struct Orderline {
     pub ol_number: i32,
     pub ol_i_id: i32,
     pub ol_supply_w_id: i32,
-    pub ol_delivery_d: i32,
-    pub ol_quantity: Numeric, // numeric(2,0)
-    pub ol_amount: Numeric, // numeric(6, 2)
-    pub ol_dist_info: String,
+    pub ol_delivery_d: Option<Timestamp>,
+    #[numeric = "2,0"]
+    pub ol_quantity: Numeric,
+    #[numeric = "6,2"]
+    pub ol_amount: Numeric,
+    pub ol_dist_info: Char24,
 }
 
-impl PrimaryKey<(i32, i32, i32, i32)> for Orderline {
-    fn primary_key(&self) -> (i32, i32, i32, i32) {
-        (self.ol_w_id, self.ol_d_id, self.ol_o_id, self.ol_number)
-    }
-}
+pub type  OrderlineTable = Table<Orderline, (i32, i32, i32, i32)>;
+pub type  OrderlineColumnTable = ColumnTable<Orderline, OrderlineColumns, (i32, i32, i32, i32)>;
 
-impl FromRow for Orderline {
-    fn from_row(mut row: CharSplits<char>) -> Orderline {
-        Orderline {
-            ol_o_id: from_str(row.next().unwrap()).unwrap(),
-            ol_d_id: from_str(row.next().unwrap()).unwrap(),
-            ol_w_id: from_str(row.next().unwrap()).unwrap(),
-            ol_number: from_str(row.next().unwrap()).unwrap(),
-            ol_i_id: from_str(row.next().unwrap()).unwrap(),
-            ol_supply_w_id: from_str(row.next().unwrap()).unwrap(),
-            ol_delivery_d: from_str(row.next().unwrap()).unwrap(),
-            ol_quantity: Numeric::from_str(row.next().unwrap(), 2, 0).unwrap(),
-            ol_amount: Numeric::from_str(row.next().unwrap(), 6, 2).unwrap(),
-            ol_dist_info: row.next().unwrap().into_string(),
-        }
-    }
+
+#[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(i_id)]
+#[deriving_columns]
+pub struct Item {
+    pub i_id: i32,
+    pub i_im_id: i32,
+    pub i_name: Varchar24,
+    #[numeric = "5,2"]
+    pub i_price: Numeric,
+    pub i_data: Varchar50,
 }
 
-pub type  OrderlineTable = Table<Orderline, (i32, i32, i32, i32)>;
+pub type  ItemTable = Table<Item, i32>;
+pub type  ItemColumnTable = ColumnTable<Item, ItemColumns, i32>;
 
 
 #[deriving(Clone, Show)]
-pub struct Item {
-    i_id: i32,
-    i_im_id: i32,
-    i_name: String,
-    pub i_price: Numeric, // numeric(5,2)
-    i_data: String,
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(s_w_id, s_i_id)]
+#[deriving_columns]
+pub struct Stock {
+    pub s_i_id: i32,
+    pub s_w_id: i32,
+    #[numeric = "4,0"]
+    pub s_quantity: Numeric,
+    pub s_dist_01: Char24,
+    pub s_dist_02: Char24,
+    pub s_dist_03: Char24,
+    pub s_dist_04: Char24,
+    pub s_dist_05: Char24,
+    pub s_dist_06: Char24,
+    pub s_dist_07: Char24,
+    pub s_dist_08: Char24,
+    pub s_dist_09: Char24,
+    pub s_dist_10: Char24,
+    #[numeric = "8,0"]
+    pub s_ytd: Numeric,
+    #[numeric = "4,0"]
+    pub s_order_cnt: Numeric,
+    #[numeric = "4,0"]
+    pub s_remote_cnt: Numeric,
+    pub s_data: Varchar50,
 }
 
-impl PrimaryKey<i32> for Item {
-    fn primary_key(&self) -> i32 {
-        self.i_id
-    }
+pub type  StockTable = Table<Stock, (i32, i32)>;
+pub type  StockColumnTable = ColumnTable<Stock, StockColumns, (i32, i32)>;
+
+
+#[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(su_suppkey)]
+#[deriving_columns]
+pub struct Supplier {
+    pub su_suppkey: i32,
+    pub su_name: Char25,
+    pub su_address: Varchar40,
+    pub su_nationkey: i32,
+    pub su_phone: Char15,
+    #[numeric = "12,2"]
+    pub su_acctbal: Numeric,
+    pub su_comment: Char101,
 }
 
-impl FromRow for Item {
-    fn from_row(mut row: CharSplits<char>) -> Item {
-        Item {
-            i_id: from_str(row.next().unwrap()).unwrap(),
-            i_im_id: from_str(row.next().unwrap()).unwrap(),
-            i_name: row.next().unwrap().into_string(),
-            i_price: Numeric::from_str(row.next().unwrap(), 5, 2).unwrap(),
-            i_data: row.next().unwrap().into_string(),
-        }
-    }
+pub type  SupplierTable = Table<Supplier, i32>;
+pub type  SupplierColumnTable = ColumnTable<Supplier, SupplierColumns, i32>;
+
+
+#[deriving(Clone, Show)]
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(n_nationkey)]
+#[deriving_columns]
+pub struct Nation {
+    pub n_nationkey: i32,
+    pub n_name: Char25,
+    pub n_regionkey: i32,
+    pub n_comment: Char152,
 }
 
-pub type  ItemTable = Table<Item, i32>;
+pub type  NationTable = Table<Nation, i32>;
+pub type  NationColumnTable = ColumnTable<Nation, NationColumns, i32>;
 
 
 #[deriving(Clone, Show)]
-pub struct Stock {
-    pub s_i_id: i32,
-    pub s_w_id: i32,
-    pub s_quantity: Numeric, // numeric(4,0)
-    pub s_dist_01: String,
-    pub s_dist_02: String,
-    pub s_dist_03: String,
-    pub s_dist_04: String,
-    pub s_dist_05: String,
-    pub s_dist_06: String,
-    pub s_dist_07: String,
-    pub s_dist_08: String,
-    pub s_dist_09: String,
-    pub s_dist_10: String,
-    pub s_ytd: Numeric, // numeric(8,0)
-    pub s_order_cnt: Numeric, // numeric(4, 0)
-    pub s_remote_cnt: Numeric, // numeric(4,0)
-    pub s_data: String,
+#[deriving_from_row]
+#[deriving_to_row]
+#[deriving_primary_key(r_regionkey)]
+#[deriving_columns]
+pub struct Region {
+    pub r_regionkey: i32,
+    pub r_name: Char55,
+    pub r_comment: Char152,
 }
 
-impl PrimaryKey<(i32, i32)> for Stock {
-    fn primary_key(&self) -> (i32, i32) {
-        (self.s_w_id, self.s_i_id)
-    }
+pub type  RegionTable = Table<Region, i32>;
+pub type  RegionColumnTable = ColumnTable<Region, RegionColumns, i32>;
+
+
+pub struct Tables {
+    pub warehouse_table: WarehouseTable,
+    pub district_table: DistrictTable,
+    pub customer_table: CustomerTable,
+    pub neworder_table: NeworderTable,
+    pub order_table: OrderTable,
+    pub orderline_table: OrderlineTable,
+    pub item_table: ItemTable,
+    pub stock_table: StockTable,
+    pub supplier_table: SupplierTable,
+    pub nation_table: NationTable,
+    pub region_table: RegionTable,
 }
 
-impl FromRow for Stock {
-    fn from_row(mut row: CharSplits<char>) -> Stock {
-        Stock {
-            s_i_id: from_str(row.next().unwrap()).unwrap(),
-            s_w_id: from_str(row.next().unwrap()).unwrap(),
-            s_quantity: Numeric::from_str(row.next().unwrap(), 4, 0).unwrap(),
-            s_dist_01: row.next().unwrap().into_string(),
-            s_dist_02: row.next().unwrap().into_string(),
-            s_dist_03: row.next().unwrap().into_string(),
-            s_dist_04: row.next().unwrap().into_string(),
-            s_dist_05: row.next().unwrap().into_string(),
-            s_dist_06: row.next().unwrap().into_string(),
-            s_dist_07: row.next().unwrap().into_string(),
-            s_dist_08: row.next().unwrap().into_string(),
-            s_dist_09: row.next().unwrap().into_string(),
-            s_dist_10: row.next().unwrap().into_string(),
-            s_ytd: Numeric::from_str(row.next().unwrap(), 8, 0).unwrap(),
-            s_order_cnt: Numeric::from_str(row.next().unwrap(), 4, 0).unwrap(),
-            s_remote_cnt: Numeric::from_str(row.next().unwrap(), 4, 0).unwrap(),
-            s_data: row.next().unwrap().into_string(),
+impl Tables {
+    pub fn new() -> Tables {
+        Tables {
+            warehouse_table: Table::new(),
+            district_table: Table::new(),
+            customer_table: Table::new(),
+            neworder_table: Table::new(),
+            order_table: Table::new(),
+            orderline_table: Table::new(),
+            item_table: Table::new(),
+            stock_table: Table::new(),
+            supplier_table: Table::new(),
+            nation_table: Table::new(),
+            region_table: Table::new(),
         }
     }
+
+    /// Number of rows per table, in schema order.
+    pub fn sizes(&self) -> Vec<(&'static str, uint)> {
+        vec![
+            ("Warehouse", self.warehouse_table.size()),
+            ("District", self.district_table.size()),
+            ("Customer", self.customer_table.size()),
+            ("Neworder", self.neworder_table.size()),
+            ("Order", self.order_table.size()),
+            ("Orderline", self.orderline_table.size()),
+            ("Item", self.item_table.size()),
+            ("Stock", self.stock_table.size()),
+            ("Supplier", self.supplier_table.size()),
+            ("Nation", self.nation_table.size()),
+            ("Region", self.region_table.size()),
+        ]
+    }
 }
 
-pub type  StockTable = Table<Stock, (i32, i32)>;
+impl Database for Tables {
+    fn relation(&self, name: &str) -> Option<&Relation> {
+        match name {
+            "warehouse" => Some(&self.warehouse_table as &Relation),
+            "district" => Some(&self.district_table as &Relation),
+            "customer" => Some(&self.customer_table as &Relation),
+            "neworder" => Some(&self.neworder_table as &Relation),
+            "order" => Some(&self.order_table as &Relation),
+            "orderline" => Some(&self.orderline_table as &Relation),
+            "item" => Some(&self.item_table as &Relation),
+            "stock" => Some(&self.stock_table as &Relation),
+            "supplier" => Some(&self.supplier_table as &Relation),
+            "nation" => Some(&self.nation_table as &Relation),
+            "region" => Some(&self.region_table as &Relation),
+            _ => None
+        }
+    }
+}
 
+pub fn read_tables(tables: &mut Tables) {
+    read_tables_with(tables, &Format::tbl(), "tbl");
+}
 
+/// Like `read_tables`, for files in `format` ending in `.extension`. A
+/// missing file leaves its table empty.
+pub fn read_tables_with(tables: &mut Tables, format: &Format, extension: &str) {
+    bulk_load_if_exists(format!("../data/tpcc_warehouse.{}", extension)[], format,
+                        &mut tables.warehouse_table);
+    bulk_load_if_exists(format!("../data/tpcc_district.{}", extension)[], format,
+                        &mut tables.district_table);
+    bulk_load_if_exists(format!("../data/tpcc_customer.{}", extension)[], format,
+                        &mut tables.customer_table);
+    bulk_load_if_exists(format!("../data/tpcc_neworder.{}", extension)[], format,
+                        &mut tables.neworder_table);
+    bulk_load_if_exists(format!("../data/tpcc_order.{}", extension)[], format,
+                        &mut tables.order_table);
+    bulk_load_if_exists(format!("../data/tpcc_orderline.{}", extension)[], format,
+                        &mut tables.orderline_table);
+    bulk_load_if_exists(format!("../data/tpcc_item.{}", extension)[], format,
+                        &mut tables.item_table);
+    bulk_load_if_exists(format!("../data/tpcc_stock.{}", extension)[], format,
+                        &mut tables.stock_table);
+    bulk_load_if_exists(format!("../data/tpcc_supplier.{}", extension)[], format,
+                        &mut tables.supplier_table);
+    bulk_load_if_exists(format!("../data/tpcc_nation.{}", extension)[], format,
+                        &mut tables.nation_table);
+    bulk_load_if_exists(format!("../data/tpcc_region.{}", extension)[], format,
+                        &mut tables.region_table);
+}
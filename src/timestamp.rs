@@ -0,0 +1,270 @@
+use std::char::{is_whitespace, to_digit};
+use std::fmt;
+use std::from_str::FromStr;
+use std::time::Duration;
+
+use time;
+
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// A point in time with microsecond precision, counted from the Unix epoch.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    micros: i64
+}
+
+/// A calendar day, counted from the Unix epoch.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    days: i64
+}
+
+impl Timestamp {
+    pub fn from_micros(micros: i64) -> Timestamp {
+        Timestamp { micros: micros }
+    }
+
+    pub fn now() -> Timestamp {
+        let now = time::get_time();
+        Timestamp { micros: now.sec * MICROS_PER_SECOND + now.nsec as i64 / 1000 }
+    }
+
+    pub fn micros(&self) -> i64 {
+        self.micros
+    }
+
+    pub fn date(&self) -> Date {
+        Date { days: div_floor(self.micros, MICROS_PER_DAY) }
+    }
+
+    /// The time elapsed between `earlier` and `self`.
+    pub fn since(&self, earlier: &Timestamp) -> Duration {
+        Duration::microseconds(self.micros - earlier.micros)
+    }
+}
+
+impl Date {
+    /// Returns `None` if the day does not exist in the given month.
+    pub fn from_ymd(year: i64, month: uint, day: uint) -> Option<Date> {
+        if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { days: days_from_civil(year, month as i64, day as i64) })
+    }
+
+    pub fn ymd(&self) -> (i64, uint, uint) {
+        let (year, month, day) = civil_from_days(self.days);
+        (year, month as uint, day as uint)
+    }
+
+    pub fn at_midnight(&self) -> Timestamp {
+        Timestamp { micros: self.days * MICROS_PER_DAY }
+    }
+}
+
+impl Add<Duration, Timestamp> for Timestamp {
+    fn add(&self, rhs: &Duration) -> Timestamp {
+        Timestamp { micros: self.micros + duration_micros(rhs) }
+    }
+}
+
+impl Sub<Duration, Timestamp> for Timestamp {
+    fn sub(&self, rhs: &Duration) -> Timestamp {
+        Timestamp { micros: self.micros - duration_micros(rhs) }
+    }
+}
+
+impl Sub<Timestamp, Duration> for Timestamp {
+    fn sub(&self, rhs: &Timestamp) -> Duration {
+        self.since(rhs)
+    }
+}
+
+/// Accepts the formats found in `.tbl` files: integer seconds since the epoch,
+/// `YYYY-MM-DD` and `YYYY-MM-DD HH:MM:SS[.ffffff]` (with `T` instead of the
+/// space also being fine).
+impl FromStr for Timestamp {
+    fn from_str(s: &str) -> Option<Timestamp> {
+        let s = s.trim_chars(is_whitespace);
+        if let Some(seconds) = from_str::<i64>(s) {
+            return Some(Timestamp { micros: seconds * MICROS_PER_SECOND });
+        }
+
+        let (date, time) = match s.find(|c: char| c == ' ' || c == 'T') {
+            Some(i) => (s[..i], Some(s[i + 1..])),
+            None => (s, None)
+        };
+        let date: Date = match from_str(date) {
+            Some(date) => date,
+            None => return None
+        };
+        let micros = match time {
+            Some(time) => match parse_time_of_day(time) {
+                Some(micros) => micros,
+                None => return None
+            },
+            None => 0
+        };
+
+        Some(Timestamp { micros: date.at_midnight().micros + micros })
+    }
+}
+
+impl FromStr for Date {
+    fn from_str(s: &str) -> Option<Date> {
+        let parts: Vec<&str> = s.trim_chars(is_whitespace).split('-').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        match (from_str(parts[0]), from_str(parts[1]), from_str(parts[2])) {
+            (Some(year), Some(month), Some(day)) => Date::from_ymd(year, month, day),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Show for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let of_day = mod_floor(self.micros, MICROS_PER_DAY);
+        let seconds = of_day / MICROS_PER_SECOND;
+        write!(f, "{} {:02}:{:02}:{:02}.{:06}", self.date(), seconds / 3600, seconds / 60 % 60,
+               seconds % 60, of_day % MICROS_PER_SECOND)
+    }
+}
+
+impl fmt::Show for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+
+/// Parses `HH:MM:SS[.ffffff]` into microseconds since midnight.
+fn parse_time_of_day(s: &str) -> Option<i64> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let (seconds, fraction) = match parts[2].find('.') {
+        Some(i) => (parts[2][..i], parts[2][i + 1..]),
+        None => (parts[2], "")
+    };
+    if fraction.len() > 6 || !fraction.chars().all(|c| to_digit(c, 10).is_some()) {
+        return None;
+    }
+
+    let (hours, minutes, seconds): (i64, i64, i64) =
+        match (from_str(parts[0]), from_str(parts[1]), from_str(seconds)) {
+            (Some(h), Some(m), Some(s)) => (h, m, s),
+            _ => return None
+        };
+    if hours < 0 || hours > 23 || minutes < 0 || minutes > 59 || seconds < 0 || seconds > 59 {
+        return None;
+    }
+
+    // ".5" means half a second, so pad to microseconds before reading the digits
+    let mut micros = 0i64;
+    for c in fraction.chars().chain(Some('0').into_iter().cycle()).take(6) {
+        micros = micros * 10 + to_digit(c, 10).unwrap() as i64;
+    }
+
+    Some(((hours * 60 + minutes) * 60 + seconds) * MICROS_PER_SECOND + micros)
+}
+
+fn duration_micros(duration: &Duration) -> i64 {
+    duration.num_microseconds().expect("duration out of timestamp range")
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: uint) -> uint {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+// The two conversions below are Howard Hinnant's proleptic Gregorian algorithms.
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = div_floor(year, 400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = div_floor(days, 146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+                       - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    if a % b < 0 { a / b - 1 } else { a / b }
+}
+
+fn mod_floor(a: i64, b: i64) -> i64 {
+    a - div_floor(a, b) * b
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Date, Timestamp};
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(from_str::<Timestamp>("0"), Some(Timestamp::from_micros(0)))
+        assert_eq!(from_str::<Timestamp>("1404202320"), Some(Timestamp::from_micros(1404202320000000)))
+        assert_eq!(from_str::<Timestamp>("1970-01-02"), Some(Timestamp::from_micros(86400000000)))
+        assert_eq!(from_str::<Timestamp>("2014-07-01 08:12:00"), Some(Timestamp::from_micros(1404202320000000)))
+        assert_eq!(from_str::<Timestamp>("2014-07-01T08:12:00.5"), Some(Timestamp::from_micros(1404202320500000)))
+        assert_eq!(from_str::<Timestamp>("1969-12-31 23:59:59.999999"), Some(Timestamp::from_micros(-1)))
+        assert_eq!(from_str::<Timestamp>(""), None)
+        assert_eq!(from_str::<Timestamp>("2014-02-30"), None)
+        assert_eq!(from_str::<Timestamp>("2014-07-01 24:00:00"), None)
+        assert_eq!(from_str::<Timestamp>("2014-07-01 08:12:00.1234567"), None)
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Timestamp::from_micros(0).to_string(), "1970-01-01 00:00:00.000000".to_string())
+        assert_eq!(Timestamp::from_micros(-1).to_string(), "1969-12-31 23:59:59.999999".to_string())
+        assert_eq!(Date::from_ymd(2000, 2, 29).unwrap().to_string(), "2000-02-29".to_string())
+
+        for &micros in [0, 1, -1, 951782400123456, -62135596800000000, 253402300799999999].iter() {
+            let timestamp = Timestamp::from_micros(micros);
+            assert_eq!(from_str::<Timestamp>(timestamp.to_string()[]), Some(timestamp))
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let t = from_str::<Timestamp>("2014-12-31 23:00:00").unwrap();
+        let later = t + Duration::hours(2);
+        assert!(later > t)
+        assert_eq!(later.date(), Date::from_ymd(2015, 1, 1).unwrap())
+        assert_eq!(later - t, Duration::hours(2))
+        assert_eq!(later - Duration::hours(2), t)
+    }
+}
@@ -0,0 +1,222 @@
+//! Vectorized execution over a column store: operators pass on batches of
+//! up to `BATCH_SIZE` rows together with a selection vector holding the
+//! positions within the batch that passed all predicates so far, instead of
+//! one row at a time. Predicates only shrink the selection, so no values are
+//! copied, and the primitives doing the work are tight loops over a column.
+//!
+//! ```ignore
+//! // count(*), sum(s_quantity) from stock where s_quantity < 50
+//! let stock = stock_table.columns();
+//! let mut filter = NumericFilter::new(box ScanBatches::new(stock_table.size()),
+//!                                     stock.s_quantity[], IsLess, fifty);
+//! let (count, sum) = sum_numeric(&mut filter, stock.s_quantity[], zero);
+//! ```
+
+use std::cmp::min;
+
+use super::numeric::Numeric;
+use super::query::{CompareOp, IsEqual, IsNotEqual, IsLess, IsLessOrEqual, IsGreater,
+                   IsGreaterOrEqual};
+
+
+pub const BATCH_SIZE: uint = 1024;
+
+pub struct Batch {
+    /// Position of the first row of the batch in the table.
+    pub offset: uint,
+    pub len: uint,
+    /// Selected positions relative to `offset`, ascending.
+    pub selection: Vec<u16>,
+}
+
+pub trait BatchOperator {
+    /// The next batch with at least one selected row, `None` once all rows
+    /// were produced.
+    fn next(&mut self) -> Option<Batch>;
+}
+
+/// All rows of a table with `rows` rows.
+pub struct ScanBatches {
+    rows: uint,
+    pos: uint,
+}
+
+impl ScanBatches {
+    pub fn new(rows: uint) -> ScanBatches {
+        ScanBatches { rows: rows, pos: 0 }
+    }
+}
+
+impl BatchOperator for ScanBatches {
+    fn next(&mut self) -> Option<Batch> {
+        if self.pos == self.rows {
+            return None;
+        }
+        let len = min(BATCH_SIZE, self.rows - self.pos);
+        let batch = Batch {
+            offset: self.pos,
+            len: len,
+            selection: range(0, len).map(|i| i as u16).collect(),
+        };
+        self.pos += len;
+        Some(batch)
+    }
+}
+
+/// Keeps the rows for which `column op constant` holds.
+pub struct NumericFilter<'a> {
+    input: Box<BatchOperator + 'a>,
+    column: &'a [Numeric],
+    op: CompareOp,
+    constant: Numeric,
+}
+
+impl<'a> NumericFilter<'a> {
+    pub fn new(input: Box<BatchOperator + 'a>, column: &'a [Numeric], op: CompareOp,
+               constant: Numeric) -> NumericFilter<'a> {
+        NumericFilter { input: input, column: column, op: op, constant: constant }
+    }
+}
+
+impl<'a> BatchOperator for NumericFilter<'a> {
+    fn next(&mut self) -> Option<Batch> {
+        loop {
+            let mut batch = match self.input.next() {
+                Some(batch) => batch,
+                None => return None
+            };
+            let values = self.column[batch.offset..batch.offset + batch.len];
+            select_numeric(values, self.op.clone(), &self.constant, &mut batch.selection);
+            if !batch.selection.is_empty() {
+                return Some(batch);
+            }
+        }
+    }
+}
+
+/// The number of selected rows and the sum of `column` over them, starting
+/// from `zero` (which determines the precision of the sum).
+pub fn sum_numeric(input: &mut BatchOperator, column: &[Numeric], zero: Numeric)
+        -> (uint, Numeric) {
+    let mut count = 0;
+    let mut sum = zero;
+    loop {
+        let batch = match input.next() {
+            Some(batch) => batch,
+            None => return (count, sum)
+        };
+        count += batch.selection.len();
+        sum = sum_selected(column[batch.offset..batch.offset + batch.len], batch.selection[],
+                           sum);
+    }
+}
+
+// Filters `selection` by comparing the values with `constant` using `$op`,
+// on the raw values if the precisions match (as they do within a column).
+macro_rules! select_loop(
+    ($values:expr, $constant:expr, $selection:expr, $op:tt) => ({
+        let constant = $constant;
+        let selection = $selection;
+        let mut selected = 0;
+        for k in range(0, selection.len()) {
+            let pos = selection[k];
+            let value = &$values[pos as uint];
+            let keep = if value.precision() == constant.precision() {
+                value.raw_value() $op constant.raw_value()
+            } else {
+                value.cmp(constant) $op Equal
+            };
+            // write unconditionally, avoiding a hard to predict branch
+            *selection.get_mut(selected) = pos;
+            selected += keep as uint;
+        }
+        selection.truncate(selected);
+    })
+)
+
+/// Keeps the positions of `selection` for which `values[pos] op constant`
+/// holds.
+pub fn select_numeric(values: &[Numeric], op: CompareOp, constant: &Numeric,
+                      selection: &mut Vec<u16>) {
+    match op {
+        IsEqual => select_loop!(values, constant, selection, ==),
+        IsNotEqual => select_loop!(values, constant, selection, !=),
+        IsLess => select_loop!(values, constant, selection, <),
+        IsLessOrEqual => select_loop!(values, constant, selection, <=),
+        IsGreater => select_loop!(values, constant, selection, >),
+        IsGreaterOrEqual => select_loop!(values, constant, selection, >=),
+    }
+}
+
+/// `sum` plus the values at the positions of `selection`.
+pub fn sum_selected(values: &[Numeric], selection: &[u16], sum: Numeric) -> Numeric {
+    let mut raw = 0i64;
+    let mut result = sum;
+    for &pos in selection.iter() {
+        let value = &values[pos as uint];
+        if value.precision() == sum.precision() {
+            raw += value.raw_value();
+        } else {
+            result = result + *value;
+        }
+    }
+    result + Numeric::new(raw, 0, sum.precision())
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use numeric::Numeric;
+    use query::{IsLess, IsGreaterOrEqual, IsNotEqual};
+
+    use super::{BATCH_SIZE, BatchOperator, ScanBatches, NumericFilter, select_numeric,
+                sum_selected, sum_numeric};
+
+    fn numerics(values: &[i64]) -> Vec<Numeric> {
+        values.iter().map(|&v| Numeric::new(v, 4, 2)).collect()
+    }
+
+    #[test]
+    fn test_select_numeric() {
+        let values = numerics([150, 250, 50, 300]);
+        let mut selection = vec![0, 1, 2, 3];
+        select_numeric(values[], IsLess, &Numeric::new(250, 4, 2), &mut selection);
+        assert_eq!(selection, vec![0, 2])
+        // other precision
+        select_numeric(values[], IsGreaterOrEqual, &Numeric::new(1000, 4, 3), &mut selection);
+        assert_eq!(selection, vec![0])
+        // more decimal places in the column than in the constant
+        let mut selection = vec![0, 1, 2, 3];
+        select_numeric(values[], IsGreaterOrEqual, &Numeric::new(2, 1, 0), &mut selection);
+        assert_eq!(selection, vec![1, 3])
+        let mut selection = vec![0, 1, 2, 3];
+        select_numeric(values[], IsLess, &Numeric::new(1, 1, 0), &mut selection);
+        assert_eq!(selection, vec![2])
+    }
+
+    #[test]
+    fn test_sum_selected() {
+        let mut values = numerics([150, 250, 50]);
+        values.push(Numeric::new(1, 1, 0));
+        assert_eq!(sum_selected(values[], [0, 2, 3], Numeric::new(0, 12, 2)),
+                   Numeric::new(300, 12, 2))
+    }
+
+    #[test]
+    fn test_batches() {
+        let values: Vec<Numeric> = range(0, 2 * BATCH_SIZE as i64 + 10)
+            .map(|i| Numeric::new(i % 3, 1, 0))
+            .collect();
+        let mut scan = ScanBatches::new(values.len());
+        let lengths: Vec<uint> = range(0u, 3).map(|_| scan.next().unwrap().len).collect();
+        assert_eq!(lengths, vec![BATCH_SIZE, BATCH_SIZE, 10])
+        assert!(scan.next().is_none())
+
+        let mut filter = NumericFilter::new(box ScanBatches::new(values.len()), values[],
+                                            IsNotEqual, Numeric::new(0, 1, 0));
+        // 0, 1 and 2 each occur 686 times
+        assert_eq!(sum_numeric(&mut filter, values[], Numeric::new(0, 8, 0)),
+                   (1372, Numeric::new(2058, 8, 0)))
+    }
+}
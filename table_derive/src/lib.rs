@@ -0,0 +1,358 @@
+//! Item decorators generating the `FromRow`, `ToRow` and `PrimaryKey` impls of
+//! the row structs in `tables.rs`.
+//!
+//! ```ignore
+//! #[deriving(Clone, Show)]
+//! #[deriving_from_row]
+//! #[deriving_to_row]
+//! #[deriving_primary_key(d_w_id, d_id)]
+//! pub struct District {
+//!     d_id: i32,
+//!     d_w_id: i32,
+//!     #[numeric = "4,4"]
+//!     d_tax: Numeric,
+//!     ...
+//! }
+//! ```
+//!
+//! A single column key can instead be marked with `#[primary_key]` on the
+//! field. `#[deriving_columns]` generates a `DistrictColumns` struct holding a
+//! vector per field (a `Dictionary` for `Char*`/`Varchar*` fields) for use in a
+//! `column::ColumnTable`. The generated code refers to the `table`, `dynamic`
+//! and `column` modules of the crate root.
+
+#![crate_type = "dylib"]
+#![feature(plugin_registrar, quote)]
+
+extern crate rustc;
+extern crate syntax;
+
+use syntax::ast;
+use syntax::ast_util;
+use syntax::attr;
+use syntax::attr::AttrMetaMethods;
+use syntax::codemap::Span;
+use syntax::ext::base::{Decorator, ExtCtxt};
+use syntax::ext::build::AstBuilder;
+use syntax::parse::token;
+use syntax::ptr::P;
+
+use rustc::plugin::Registry;
+
+
+#[plugin_registrar]
+pub fn plugin_registrar(reg: &mut Registry) {
+    reg.register_syntax_extension(token::intern("deriving_from_row"),
+                                  Decorator(box expand_from_row));
+    reg.register_syntax_extension(token::intern("deriving_to_row"),
+                                  Decorator(box expand_to_row));
+    reg.register_syntax_extension(token::intern("deriving_primary_key"),
+                                  Decorator(box expand_primary_key));
+    reg.register_syntax_extension(token::intern("deriving_columns"),
+                                  Decorator(box expand_columns));
+}
+
+
+fn expand_from_row(cx: &mut ExtCtxt, span: Span, _: &ast::MetaItem, item: &ast::Item,
+                   push: |P<ast::Item>|) {
+    let fields = match struct_fields(cx, span, item, "deriving_from_row") {
+        Some(fields) => fields,
+        None => return
+    };
+
+    let mut inits = Vec::new();
+    let mut columns = Vec::new();
+    for field in fields.iter() {
+        let ident = field_ident(field);
+        let column = token::get_ident(ident);
+        columns.push(cx.expr_str(field.span, column.clone()));
+        let column = column.get();
+
+        let value = match numeric_attr(cx, field) {
+            Some((len, precision)) if is_option(&*field.node.ty) =>
+                quote_expr!(cx, try!(::table::nullable_numeric_field(&mut row, $column, $len,
+                                                                     $precision))),
+            Some((len, precision)) =>
+                quote_expr!(cx, try!(::table::numeric_field(&mut row, $column, $len,
+                                                            $precision))),
+            None => quote_expr!(cx, try!(::table::field(&mut row, $column)))
+        };
+        inits.push(cx.field_imm(field.span, ident, value));
+    }
+
+    let name = item.ident;
+    let value = cx.expr_struct_ident(span, name, inits);
+    let columns = cx.expr_vec_ng(span, columns);
+    push(quote_item!(cx,
+        impl ::table::FromRow for $name {
+            fn from_row<'a, I: Iterator<&'a str>>(mut row: I)
+                    -> Result<$name, ::table::RowError> {
+                Ok($value)
+            }
+
+            fn columns(_: Option<$name>) -> Vec<&'static str> {
+                $columns
+            }
+        }
+    ).unwrap());
+}
+
+fn expand_to_row(cx: &mut ExtCtxt, span: Span, _: &ast::MetaItem, item: &ast::Item,
+                 push: |P<ast::Item>|) {
+    let fields = match struct_fields(cx, span, item, "deriving_to_row") {
+        Some(fields) => fields,
+        None => return
+    };
+
+    let values = fields.iter().map(|field| {
+        let ident = field_ident(field);
+        quote_expr!(cx, self.$ident.to_value())
+    }).collect();
+    let row = cx.expr_vec_ng(span, values);
+
+    let name = item.ident;
+    push(quote_item!(cx,
+        impl ::dynamic::ToRow for $name {
+            fn to_row(&self) -> ::dynamic::Row {
+                use dynamic::ToValue;
+                $row
+            }
+        }
+    ).unwrap());
+}
+
+fn expand_primary_key(cx: &mut ExtCtxt, span: Span, meta: &ast::MetaItem, item: &ast::Item,
+                      push: |P<ast::Item>|) {
+    let fields = match struct_fields(cx, span, item, "deriving_primary_key") {
+        Some(fields) => fields,
+        None => return
+    };
+
+    // fields marked `#[primary_key]`, in declaration order
+    let marked: Vec<&ast::StructField> = fields.iter().filter(|field| {
+        field.node.attrs.iter().any(|a| {
+            if a.check_name("primary_key") {
+                attr::mark_used(a);
+                true
+            } else {
+                false
+            }
+        })
+    }).collect();
+
+    let key: Vec<&ast::StructField> = match meta.meta_item_list() {
+        Some(_) if !marked.is_empty() => {
+            cx.span_err(span, "primary key given both as list and by `#[primary_key]` fields");
+            return;
+        },
+        Some(columns) => {
+            let mut key = Vec::new();
+            for column in columns.iter() {
+                let found = fields.iter().find(|field| {
+                    token::get_ident(field_ident(*field)).get() == column.name().get()
+                });
+                match found {
+                    Some(field) => key.push(field),
+                    None => {
+                        cx.span_err(column.span, format!("no field `{}` in `{}`", column.name(),
+                                                         token::get_ident(item.ident))[]);
+                        return;
+                    }
+                }
+            }
+            key
+        },
+        None => marked
+    };
+
+    if key.is_empty() {
+        cx.span_err(span, "no primary key: list the columns in `#[deriving_primary_key(...)]` \
+                           or mark a field with `#[primary_key]`");
+        return;
+    }
+
+    let (key_ty, key_value) = if key.len() == 1 {
+        let ident = field_ident(key[0]);
+        (key[0].node.ty.clone(), quote_expr!(cx, self.$ident))
+    } else {
+        let tys = key.iter().map(|field| field.node.ty.clone()).collect();
+        let values = key.iter().map(|field| {
+            let ident = field_ident(*field);
+            quote_expr!(cx, self.$ident)
+        }).collect();
+        (cx.ty(span, ast::TyTup(tys)), cx.expr_tuple(span, values))
+    };
+
+    let name = item.ident;
+    push(quote_item!(cx,
+        impl ::table::PrimaryKey<$key_ty> for $name {
+            fn primary_key(&self) -> $key_ty {
+                $key_value
+            }
+        }
+    ).unwrap());
+}
+
+fn expand_columns(cx: &mut ExtCtxt, span: Span, _: &ast::MetaItem, item: &ast::Item,
+                  push: |P<ast::Item>|) {
+    let fields = match struct_fields(cx, span, item, "deriving_columns") {
+        Some(fields) => fields,
+        None => return
+    };
+    if fields.is_empty() {
+        cx.span_err(span, "`deriving_columns` needs at least one field");
+        return;
+    }
+
+    let name = item.ident;
+    let columns_name = token::str_to_ident(format!("{}Columns", token::get_ident(name))[]);
+
+    let mut column_fields = Vec::new();
+    let mut news = Vec::new();
+    let mut pushes = Vec::new();
+    let mut gets = Vec::new();
+    let mut sets = Vec::new();
+    for field in fields.iter() {
+        let ident = field_ident(field);
+        let ty = field.node.ty.clone();
+        let column_ty = if is_string(&*ty) {
+            quote_ty!(cx, ::column::Dictionary<$ty>)
+        } else {
+            quote_ty!(cx, Vec<$ty>)
+        };
+        column_fields.push(ast::StructField {
+            node: ast::StructField_ {
+                kind: ast::NamedField(ident, ast::Public),
+                id: ast::DUMMY_NODE_ID,
+                ty: column_ty,
+                attrs: Vec::new(),
+            },
+            span: field.span,
+        });
+
+        news.push(cx.field_imm(field.span, ident, quote_expr!(cx, ::column::Column::new())));
+        pushes.push(quote_stmt!(cx, self.$ident.push_value(&row.$ident);));
+        gets.push(cx.field_imm(field.span, ident, quote_expr!(cx, self.$ident.value(pos))));
+        sets.push(quote_stmt!(cx, self.$ident.set_value(pos, &row.$ident);));
+    }
+
+    push(P(ast::Item {
+        ident: columns_name,
+        attrs: Vec::new(),
+        id: ast::DUMMY_NODE_ID,
+        node: ast::ItemStruct(P(ast::StructDef { fields: column_fields, ctor_id: None }),
+                              ast_util::empty_generics()),
+        vis: ast::Public,
+        span: span,
+    }));
+
+    let first = field_ident(&fields[0]);
+    let new_value = cx.expr_struct_ident(span, columns_name, news);
+    let get_value = cx.expr_struct_ident(span, name, gets);
+    let push_block = cx.expr_block(cx.block(span, pushes, None));
+    let set_block = cx.expr_block(cx.block(span, sets, None));
+    push(quote_item!(cx,
+        impl ::column::Columns<$name> for $columns_name {
+            fn new() -> $columns_name {
+                $new_value
+            }
+
+            fn push(&mut self, row: &$name) {
+                use column::Column;
+                $push_block
+            }
+
+            fn get(&self, pos: uint) -> $name {
+                use column::Column;
+                $get_value
+            }
+
+            fn set(&mut self, pos: uint, row: &$name) {
+                use column::Column;
+                $set_block
+            }
+
+            fn len(&self) -> uint {
+                self.$first.len()
+            }
+        }
+    ).unwrap());
+}
+
+
+fn struct_fields<'a>(cx: &mut ExtCtxt, span: Span, item: &'a ast::Item, decorator: &str)
+        -> Option<&'a [ast::StructField]> {
+    match item.node {
+        ast::ItemStruct(ref def, ref generics) if generics.ty_params.is_empty() => {
+            if def.fields.iter().any(|field| match field.node.kind {
+                ast::NamedField(..) => false,
+                ast::UnnamedField(..) => true
+            }) {
+                cx.span_err(span, format!("`{}` needs a struct with named fields", decorator)[]);
+                None
+            } else {
+                Some(def.fields[])
+            }
+        },
+        _ => {
+            cx.span_err(span, format!("`{}` only applies to non-generic structs", decorator)[]);
+            None
+        }
+    }
+}
+
+fn field_ident(field: &ast::StructField) -> ast::Ident {
+    match field.node.kind {
+        ast::NamedField(ident, _) => ident,
+        ast::UnnamedField(..) => unreachable!()
+    }
+}
+
+/// Reads `#[numeric = "len,precision"]`, mirroring SQL's `numeric(len, precision)`.
+fn numeric_attr(cx: &mut ExtCtxt, field: &ast::StructField) -> Option<(uint, uint)> {
+    for a in field.node.attrs.iter() {
+        if !a.check_name("numeric") {
+            continue;
+        }
+        attr::mark_used(a);
+
+        let parsed = a.value_str().and_then(|value| {
+            let parts: Vec<Option<uint>> = value.get().split(',')
+                .map(|part| from_str(part.trim()))
+                .collect();
+            match parts[] {
+                [Some(len), Some(precision)] if precision <= len => Some((len, precision)),
+                _ => None
+            }
+        });
+        if parsed.is_none() {
+            cx.span_err(a.span, "expected `#[numeric = \"len,precision\"]`");
+        }
+        return parsed;
+    }
+    None
+}
+
+fn is_option(ty: &ast::Ty) -> bool {
+    match ty.node {
+        ast::TyPath(ref path, _, _) => path.segments.last().map_or(false, |segment| {
+            token::get_ident(segment.identifier).get() == "Option"
+        }),
+        _ => false
+    }
+}
+
+/// Whether `ty` is one of the `chars` types, possibly wrapped in an `Option`.
+fn is_string(ty: &ast::Ty) -> bool {
+    match ty.node {
+        ast::TyPath(ref path, _, _) => path.segments.last().map_or(false, |segment| {
+            let name = token::get_ident(segment.identifier);
+            if name.get() == "Option" {
+                segment.types.iter().next().map_or(false, |ty| is_string(&**ty))
+            } else {
+                name.get().starts_with("Char") || name.get().starts_with("Varchar")
+            }
+        }),
+        _ => false
+    }
+}